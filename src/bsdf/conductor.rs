@@ -0,0 +1,60 @@
+use crate::{
+    bsdf::SampleableBsdf,
+    math::{PdfSet, Shading, Vec3},
+    sampling::Sampler,
+    spectrum::{ComplexIor, SpectralSample, Wavelength},
+    texture::TextureContext,
+};
+
+// Polished metal, a perfect mirror tinted by the conductor Fresnel equations.
+// Every wavelength reflects in the same direction, so all lanes follow the
+// sampled path
+#[derive(Debug, Clone)]
+pub struct ConductorBsdf {
+    ior: ComplexIor,
+}
+
+impl ConductorBsdf {
+    pub fn new(ior: ComplexIor) -> Self {
+        Self { ior }
+    }
+}
+
+impl SampleableBsdf for ConductorBsdf {
+    fn evaluate(
+        &self,
+        _wi: Vec3<Shading>,
+        _wo: Vec3<Shading>,
+        _ctx: &TextureContext,
+        _hero_wavelength: Wavelength,
+    ) -> SpectralSample {
+        SpectralSample::splat(0.0)
+    }
+
+    fn pdf(
+        &self,
+        _wi: Vec3<Shading>,
+        _wo: Vec3<Shading>,
+        _ctx: &TextureContext,
+        _hero_wavelength: Wavelength,
+    ) -> PdfSet {
+        PdfSet::splat(0.0)
+    }
+
+    fn sample(
+        &self,
+        wo: Vec3<Shading>,
+        _ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+        _sampler: &mut Sampler,
+    ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
+        let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+        let cos_theta_i = wi.cos_theta().abs();
+        let fresnel = self.ior.fresnel(cos_theta_i, hero_wavelength);
+        (wi, fresnel / cos_theta_i, PdfSet::splat(1.0))
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
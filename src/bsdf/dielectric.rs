@@ -0,0 +1,225 @@
+use crate::{
+    bsdf::SampleableBsdf,
+    math::{self, PdfSet, Shading, Vec3, Vec4},
+    sampling::Sampler,
+    spectrum::{Ior, SpectralSample, Wavelength},
+    texture::{SampleableTexture, Texture, TextureContext},
+};
+
+// Smooth boundary between air and glass (or water, etc.), reflecting and
+// refracting according to the Fresnel equations. Both lobes are delta
+// distributions, so evaluate and pdf are always zero
+#[derive(Debug, Clone)]
+pub struct DielectricBsdf {
+    reflectance: Texture,
+    transmittance: Texture,
+    ior: Ior,
+}
+
+impl DielectricBsdf {
+    pub fn new<S: Into<Texture>, T: Into<Texture>>(s: S, t: T, ior: Ior) -> Self {
+        Self {
+            reflectance: s.into(),
+            transmittance: t.into(),
+            ior,
+        }
+    }
+}
+
+impl SampleableBsdf for DielectricBsdf {
+    fn evaluate(
+        &self,
+        _wi: Vec3<Shading>,
+        _wo: Vec3<Shading>,
+        _ctx: &TextureContext,
+        _hero_wavelength: Wavelength,
+    ) -> SpectralSample {
+        SpectralSample::splat(0.0)
+    }
+
+    fn pdf(
+        &self,
+        _wi: Vec3<Shading>,
+        _wo: Vec3<Shading>,
+        _ctx: &TextureContext,
+        _hero_wavelength: Wavelength,
+    ) -> PdfSet {
+        PdfSet::splat(0.0)
+    }
+
+    // Picks reflection or refraction with the hero wavelength's Fresnel
+    // reflectance. Every wavelength reflects in the same direction, but
+    // dispersion refracts each one differently, so the lanes whose index of
+    // refraction differs from the hero's can't follow the path and get a zero
+    // pdf
+    fn sample(
+        &self,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
+        let eta = self.ior.evaluate(wavelength).inner.data.to_array();
+        let cos_theta_o = wo.cos_theta();
+        let fresnel = eta.map(|eta| math::fresnel_dielectric(cos_theta_o, 1.0, eta));
+
+        if sampler.gen_0_1() < fresnel[0] {
+            let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+            let fresnel = Vec4::new(fresnel[0], fresnel[1], fresnel[2], fresnel[3]);
+            let bsdf = self.reflectance.evaluate(ctx, wavelength) / wi.cos_theta().abs();
+            return (
+                wi,
+                SpectralSample::from(bsdf.inner * fresnel),
+                PdfSet::from(fresnel),
+            );
+        }
+
+        // Relative index of refraction, from the side of wo
+        let relative_eta = |eta: f32| if cos_theta_o > 0.0 { eta } else { 1.0 / eta };
+        let normal = Vec3::new(0.0, 0.0, 1.0).face_forward(wo);
+        let wi = match math::refract(wo, normal, 1.0 / relative_eta(eta[0])) {
+            Some(wi) => wi,
+            // Total internal reflection, which the hero's Fresnel term makes
+            // impossible to get here except for rounding
+            None => {
+                return (
+                    Vec3::splat(0.0),
+                    SpectralSample::splat(0.0),
+                    PdfSet::splat(0.0),
+                )
+            }
+        };
+
+        // Radiance gets concentrated into the smaller solid angle entering a
+        // denser medium
+        let transmittance = self
+            .transmittance
+            .evaluate(ctx, wavelength)
+            .inner
+            .data
+            .to_array();
+        let cos_theta_i = wi.cos_theta().abs();
+        let mut values = [0.0; 4];
+        let mut pdfs = [0.0; 4];
+        for i in 0..4 {
+            if eta[i] == eta[0] {
+                let t = 1.0 - fresnel[i];
+                values[i] = transmittance[i] * t / (relative_eta(eta[i]).powi(2) * cos_theta_i);
+                pdfs[i] = t;
+            }
+        }
+
+        (
+            wi,
+            SpectralSample::new(values[0], values[1], values[2], values[3]),
+            PdfSet::new(pdfs[0], pdfs[1], pdfs[2], pdfs[3]),
+        )
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    fn transmits(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::World,
+        spectrum::{ConstantSpectrum, Wavelength},
+    };
+
+    // Refracts through the face with the given outward normal, in the xz-plane
+    fn refract_through(
+        bsdf: &DielectricBsdf,
+        d: Vec3<World>,
+        normal: Vec3<World>,
+        wavelength: Wavelength,
+    ) -> Vec3<World> {
+        let tangent = Vec3::new(normal.z(), 0.0, -normal.x());
+        let to_shading = |v: Vec3<World>| Vec3::<Shading>::new(v.dot(tangent), 0.0, v.dot(normal));
+
+        // Try until the sampler picks refraction
+        let wo = to_shading(-d);
+        for i in 0..64 {
+            let mut sampler = Sampler::new(0, 0, i, 0);
+            let (wi, values, pdfs) =
+                bsdf.sample(wo, &TextureContext::default(), wavelength, &mut sampler);
+            if wi.cos_theta() * wo.cos_theta() < 0.0 {
+                assert!(values.hero() > 0.0 && pdfs.hero() > 0.0);
+                return wi.x() * tangent + wi.z() * normal;
+            }
+        }
+
+        panic!("no refraction through {:?}", normal)
+    }
+
+    #[test]
+    fn test_prism() {
+        let glass =
+            |ior| DielectricBsdf::new(ConstantSpectrum::new(1.0), ConstantSpectrum::new(1.0), ior);
+
+        // Equilateral prism with its apex towards +x, lit close to minimum
+        // deviation
+        let left = Vec3::new(
+            -(60f32.to_radians().cos()),
+            0.0,
+            -(60f32.to_radians().sin()),
+        );
+        let right = Vec3::new(-(60f32.to_radians().cos()), 0.0, 60f32.to_radians().sin());
+        let d = Vec3::new(-0.5, 0.0, 0.8).normalize();
+
+        let deviation = |bsdf: &DielectricBsdf, lambda: f32| {
+            let wavelength = Wavelength::new(lambda);
+            let inside = refract_through(bsdf, d, left, wavelength);
+            let out = refract_through(bsdf, inside, right, wavelength);
+            out.dot(d).acos().to_degrees()
+        };
+
+        // Spreads out into a rainbow, with violet bent the most
+        let sf11 = glass(Ior::SF11);
+        let violet = deviation(&sf11, 400.0);
+        let green = deviation(&sf11, 550.0);
+        let red = deviation(&sf11, 700.0);
+        assert!(violet > green + 1.0 && green > red + 0.5);
+        assert!(red > 60.0 && violet < 80.0);
+
+        // Without dispersion, every lane follows the hero
+        let constant = glass(Ior::Constant(1.5));
+        let wo = Vec3::<Shading>::new(0.5, 0.0, 0.75f32.sqrt());
+        let ctx = TextureContext::default();
+        for i in 0..16 {
+            let (_, values, pdfs) = constant.sample(
+                wo,
+                &ctx,
+                Wavelength::new(500.0),
+                &mut Sampler::new(0, 0, i, 0),
+            );
+            assert!(pdfs.x() > 0.0 && pdfs.y() > 0.0 && pdfs.z() > 0.0 && pdfs.w() > 0.0);
+            assert!((values.hero() - values.y()).abs() < 1e-5);
+        }
+
+        // But only the hero refracts through dispersive glass
+        let (mut reflected, mut refracted) = (false, false);
+        for i in 0..64 {
+            let (wi, _, pdfs) = sf11.sample(
+                wo,
+                &ctx,
+                Wavelength::new(500.0),
+                &mut Sampler::new(0, 0, i, 0),
+            );
+            if wi.cos_theta() < 0.0 {
+                refracted = true;
+                assert!(pdfs.hero() > 0.0 && pdfs.y() == 0.0 && pdfs.w() == 0.0);
+            } else {
+                reflected = true;
+                assert!(pdfs.y() > 0.0);
+            }
+        }
+        assert!(reflected && refracted);
+    }
+}
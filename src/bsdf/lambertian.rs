@@ -4,18 +4,19 @@ use crate::{
     bsdf::SampleableBsdf,
     math::{PdfSet, Shading, Vec3},
     sampling::{self, Sampler},
-    spectrum::{SampleableSpectrum, SpectralSample, Spectrum, Wavelength},
+    spectrum::{SpectralSample, Wavelength},
+    texture::{SampleableTexture, Texture, TextureContext},
 };
 
 use std::f32::consts::PI;
 
 #[derive(Debug, Clone)]
 pub struct LambertianBsdf {
-    albedo: Spectrum,
+    albedo: Texture,
 }
 
 impl LambertianBsdf {
-    pub fn new<S: Into<Spectrum>>(s: S) -> Self {
+    pub fn new<S: Into<Texture>>(s: S) -> Self {
         Self { albedo: s.into() }
     }
 }
@@ -25,18 +26,26 @@ impl SampleableBsdf for LambertianBsdf {
         &self,
         wi: Vec3<Shading>,
         wo: Vec3<Shading>,
+        ctx: &TextureContext,
         hero_wavelength: Wavelength,
     ) -> SpectralSample {
-        self.albedo.evaluate(hero_wavelength) / PI
+        self.albedo.evaluate(ctx, hero_wavelength) / PI
     }
 
-    fn pdf(&self, wi: Vec3<Shading>, wo: Vec3<Shading>, hero_wavelength: Wavelength) -> PdfSet {
+    fn pdf(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> PdfSet {
         PdfSet::splat(sampling::pdf_cosine_unit_hemisphere(wi.cos_theta().abs()))
     }
 
     fn sample(
         &self,
         wo: Vec3<Shading>,
+        ctx: &TextureContext,
         wavelength: Wavelength,
         sampler: &mut Sampler,
     ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
@@ -44,8 +53,8 @@ impl SampleableBsdf for LambertianBsdf {
         let wi = if wo.same_hemisphere(wi) { wi } else { -wi };
         (
             wi,
-            self.evaluate(wi, wo, wavelength),
-            self.pdf(wi, wo, wavelength),
+            self.evaluate(wi, wo, ctx, wavelength),
+            self.pdf(wi, wo, ctx, wavelength),
         )
     }
 }
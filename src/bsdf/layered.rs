@@ -0,0 +1,444 @@
+use crate::{
+    bsdf::{Bsdf, DielectricBsdf, RoughDielectricBsdf, SampleableBsdf},
+    math::{PdfSet, Shading, Vec3},
+    sampling::{mis, Sampler},
+    spectrum::{ConstantSpectrum, Ior, SampleableSpectrum, SpectralSample, Spectrum, Wavelength},
+    texture::TextureContext,
+};
+
+use std::f32::consts::PI;
+
+// Bounces inside the coating before a random walk gives up
+const MAX_DEPTH: usize = 10;
+
+// A dielectric coating over another BSDF, like clear-coated paint or varnished
+// wood, absorbing light as it crosses the coating. Light bouncing between the
+// two layers has no closed form, so evaluate, pdf and sample all follow random
+// walks through the coating, see
+// https://pbr-book.org/4ed/Light_Transport_II_Volume_Rendering/Scattering_from_Layered_Materials
+//
+// Walks are driven by the hero wavelength, the other lanes are weighted along
+// the same path unless one of the layers can't scatter them that way
+#[derive(Debug, Clone)]
+pub struct LayeredBsdf {
+    coat: Box<Bsdf>,
+    base: Box<Bsdf>,
+    ior: Ior,
+    thickness: f32,
+    absorption: Spectrum,
+}
+
+impl LayeredBsdf {
+    // A smooth coating without roughness, or a GGX one with it
+    pub fn new<B: Into<Bsdf>, S: Into<Spectrum>>(
+        base: B,
+        ior: Ior,
+        roughness: Option<(f32, f32)>,
+        thickness: f32,
+        absorption: S,
+    ) -> Self {
+        let clear = || ConstantSpectrum::new(1.0);
+        let coat = match roughness {
+            Some((roughness_x, roughness_y)) => {
+                RoughDielectricBsdf::new(clear(), clear(), ior.clone(), roughness_x, roughness_y)
+                    .into()
+            }
+            None => DielectricBsdf::new(clear(), clear(), ior.clone()).into(),
+        };
+
+        Self {
+            coat: Box::new(coat),
+            base: Box::new(base.into()),
+            ior,
+            thickness,
+            absorption: absorption.into(),
+        }
+    }
+
+    // Fraction of light left after crossing the coating in direction w
+    fn transmittance(&self, w: Vec3<Shading>, wavelength: Wavelength) -> SpectralSample {
+        let distance = self.thickness / w.cos_theta().abs();
+        SpectralSample::from_function(wavelength, |lambda| {
+            (-self.absorption.evaluate_single(lambda) * distance).exp()
+        })
+    }
+}
+
+impl SampleableBsdf for LayeredBsdf {
+    fn evaluate(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> SpectralSample {
+        let (wi, wo) = two_sided(wi, wo);
+        if wi.cos_theta() <= 0.0 || wo.cos_theta() == 0.0 {
+            return SpectralSample::splat(0.0);
+        }
+
+        let mut sampler = seeded_sampler(wi, wo, hero_wavelength);
+        let wavelength = hero_wavelength;
+
+        // Reflection off the coating
+        let mut f = self.coat.evaluate(wi, wo, ctx, wavelength);
+
+        // Refract into the coating from both ends of the path. Sampling from
+        // wi treats light as leaving towards it, entering takes the squared
+        // relative index of refraction back out
+        let (wo_inside, values_o, pdfs_o) = self.coat.sample(wo, ctx, wavelength, &mut sampler);
+        let (wi_inside, values_i, pdfs_i) = self.coat.sample(wi, ctx, wavelength, &mut sampler);
+        if pdfs_o.hero() == 0.0
+            || pdfs_i.hero() == 0.0
+            || wo_inside.cos_theta() >= 0.0
+            || wi_inside.cos_theta() >= 0.0
+        {
+            return f;
+        }
+        let eta = self.ior.evaluate(wavelength);
+        let entering = values_i * eta * eta * wi_inside.cos_theta().abs() / pdfs_i.hero();
+
+        let mut beta = values_o * wo_inside.cos_theta().abs() / pdfs_o.hero();
+        let mut w = wo_inside;
+        let mut at_base = false;
+        for depth in 0..MAX_DEPTH {
+            if !russian_roulette(&mut beta, depth, &mut sampler) {
+                break;
+            }
+
+            at_base = !at_base;
+            beta *= self.transmittance(w, wavelength);
+
+            if !at_base {
+                // Internal reflection off the underside of the coating
+                let (wn, values, pdfs) = self.coat.sample(-w, ctx, wavelength, &mut sampler);
+                if pdfs.hero() == 0.0 || wn.cos_theta() >= 0.0 {
+                    break;
+                }
+
+                beta *= values * wn.cos_theta().abs() / pdfs.hero();
+                w = wn;
+                continue;
+            }
+
+            // Connect to the direction light enters along
+            if !self.base.is_specular() {
+                let weight = if self.coat.is_specular() {
+                    1.0
+                } else {
+                    let base_pdf = self.base.pdf(-wi_inside, -w, ctx, wavelength).hero();
+                    mis::power_heuristic(pdfs_i.hero(), base_pdf)
+                };
+                f += beta
+                    * self.base.evaluate(-wi_inside, -w, ctx, wavelength)
+                    * self.transmittance(wi_inside, wavelength)
+                    * entering
+                    * weight;
+            }
+
+            // Bounce off the base
+            let (wn, values, pdfs) = self.base.sample(-w, ctx, wavelength, &mut sampler);
+            if pdfs.hero() == 0.0 || wn.cos_theta() <= 0.0 {
+                break;
+            }
+            beta *= values * wn.cos_theta().abs() / pdfs.hero();
+            w = wn;
+
+            // And leave through the coating towards wi
+            if !self.coat.is_specular() {
+                let leaving = self.coat.evaluate(wi, -w, ctx, wavelength);
+                let weight = if self.base.is_specular() {
+                    1.0
+                } else {
+                    let coat_pdf = self.coat.pdf(-w, wi, ctx, wavelength).hero();
+                    mis::power_heuristic(pdfs.hero(), coat_pdf)
+                };
+                f += beta * self.transmittance(w, wavelength) * leaving * weight;
+            }
+        }
+
+        f
+    }
+
+    // Not the exact pdf of sample, which has no closed form either, but an
+    // estimate close enough for multiple importance sampling. Mixing in a
+    // uniform pdf keeps it from missing directions sample can reach
+    fn pdf(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> PdfSet {
+        let (wi, wo) = two_sided(wi, wo);
+        if wi.cos_theta() <= 0.0 || wo.cos_theta() == 0.0 {
+            return PdfSet::splat(0.0);
+        }
+
+        let mut sampler = seeded_sampler(wi, wo, hero_wavelength);
+        let wavelength = hero_wavelength;
+        let mut lanes = [true; 4];
+
+        // Reflection off the coating
+        let mut pdf = self.coat.pdf(wi, wo, ctx, wavelength).hero();
+
+        // Refraction in, reflection off the base and refraction back out
+        let (wo_inside, _, pdfs_o) = self.coat.sample(wo, ctx, wavelength, &mut sampler);
+        let (wi_inside, _, pdfs_i) = self.coat.sample(wi, ctx, wavelength, &mut sampler);
+        if pdfs_o.hero() > 0.0
+            && pdfs_i.hero() > 0.0
+            && wo_inside.cos_theta() < 0.0
+            && wi_inside.cos_theta() < 0.0
+        {
+            follow(&mut lanes, pdfs_o);
+            follow(&mut lanes, pdfs_i);
+
+            if self.coat.is_specular() {
+                pdf += self
+                    .base
+                    .pdf(-wi_inside, -wo_inside, ctx, wavelength)
+                    .hero();
+            } else {
+                let (wn, _, pdfs) = self.base.sample(-wo_inside, ctx, wavelength, &mut sampler);
+                if pdfs.hero() > 0.0 && wn.cos_theta() > 0.0 {
+                    let coat_pdf = self.coat.pdf(wi, -wn, ctx, wavelength).hero();
+                    if self.base.is_specular() {
+                        pdf += coat_pdf;
+                    } else {
+                        let base_pdf = self
+                            .base
+                            .pdf(-wi_inside, -wo_inside, ctx, wavelength)
+                            .hero();
+                        pdf += mis::power_heuristic(pdfs_i.hero(), base_pdf) * base_pdf;
+                        pdf += mis::power_heuristic(pdfs.hero(), coat_pdf) * coat_pdf;
+                    }
+                }
+            }
+        }
+
+        let pdf = 0.1 / (2.0 * PI) + 0.9 * pdf;
+        let lane = |alive: bool| if alive { pdf } else { 0.0 };
+        PdfSet::new(
+            lane(lanes[0]),
+            lane(lanes[1]),
+            lane(lanes[2]),
+            lane(lanes[3]),
+        )
+    }
+
+    // Reflects off the coating or walks through it until light leaves again.
+    // The walk's own pdf is only known along that one path, so the estimate
+    // from pdf stands in for it, with values scaled to match
+    fn sample(
+        &self,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
+        let invalid = (
+            Vec3::splat(0.0),
+            SpectralSample::splat(0.0),
+            PdfSet::splat(0.0),
+        );
+        let flip = if wo.cos_theta() < 0.0 { -1.0 } else { 1.0 };
+        let wavelength = hero_wavelength;
+        let mut lanes = [true; 4];
+
+        let finish = |wi: Vec3<Shading>, beta: SpectralSample, lanes: [bool; 4]| {
+            let wi = flip * wi;
+            let pdfs = self.pdf(wi, flip * wo, ctx, wavelength);
+            let lane = |alive: bool, pdf: f32| if alive { pdf } else { 0.0 };
+            (
+                wi,
+                beta * pdfs.hero(),
+                PdfSet::new(
+                    lane(lanes[0], pdfs.x()),
+                    lane(lanes[1], pdfs.y()),
+                    lane(lanes[2], pdfs.z()),
+                    lane(lanes[3], pdfs.w()),
+                ),
+            )
+        };
+
+        let wo = flip * wo;
+        if wo.cos_theta() == 0.0 {
+            return invalid;
+        }
+
+        let (mut w, values, pdfs) = self.coat.sample(wo, ctx, wavelength, sampler);
+        if pdfs.hero() == 0.0 || w.cos_theta() == 0.0 {
+            return invalid;
+        }
+        follow(&mut lanes, pdfs);
+        if w.cos_theta() > 0.0 {
+            return finish(w, values / pdfs.hero(), lanes);
+        }
+
+        let mut beta = values * w.cos_theta().abs() / pdfs.hero();
+        let mut at_base = false;
+        for depth in 0..MAX_DEPTH {
+            if !russian_roulette(&mut beta, depth, sampler) {
+                break;
+            }
+
+            at_base = !at_base;
+            beta *= self.transmittance(w, wavelength);
+
+            let layer = if at_base { &self.base } else { &self.coat };
+            let (wn, values, pdfs) = layer.sample(-w, ctx, wavelength, sampler);
+            if pdfs.hero() == 0.0 || wn.cos_theta() == 0.0 {
+                break;
+            }
+            follow(&mut lanes, pdfs);
+            beta *= values / pdfs.hero();
+            w = wn;
+
+            // Light passing through the base is lost, through the coating it
+            // leaves towards wi
+            match (at_base, wn.cos_theta() > 0.0) {
+                (true, false) => break,
+                (false, true) => return finish(w, beta, lanes),
+                _ => beta *= SpectralSample::splat(wn.cos_theta().abs()),
+            }
+        }
+
+        invalid
+    }
+
+    // Light sampling can't reach through a smooth coating, only the directions
+    // the walk refracts along
+    fn is_specular(&self) -> bool {
+        self.coat.is_specular()
+    }
+}
+
+// Coated on both sides, so flips wi and wo to the upper side of the surface
+fn two_sided(wi: Vec3<Shading>, wo: Vec3<Shading>) -> (Vec3<Shading>, Vec3<Shading>) {
+    if wo.cos_theta() < 0.0 {
+        (-wi, -wo)
+    } else {
+        (wi, wo)
+    }
+}
+
+// Evaluate and pdf have no sampler of their own, so their walks are seeded
+// from their arguments to stay deterministic. The hash only scrambles the first
+// sample, Sobol indices have to stay below 2^16
+fn seeded_sampler(wi: Vec3<Shading>, wo: Vec3<Shading>, wavelength: Wavelength) -> Sampler {
+    let seed = [
+        wi.x(),
+        wi.y(),
+        wi.z(),
+        wo.x(),
+        wo.y(),
+        wo.z(),
+        wavelength.hero(),
+    ]
+    .iter()
+    .fold(0x811c_9dc5u32, |hash, value| {
+        (hash ^ value.to_bits()).wrapping_mul(0x0100_0193)
+    });
+    Sampler::new(0, 0, 0, seed)
+}
+
+// Drops the lanes a layer couldn't scatter along the hero's path
+fn follow(lanes: &mut [bool; 4], pdfs: PdfSet) {
+    let pdfs = [pdfs.x(), pdfs.y(), pdfs.z(), pdfs.w()];
+    for (alive, pdf) in lanes.iter_mut().zip(pdfs) {
+        *alive &= pdf > 0.0;
+    }
+}
+
+// Ends long walks that carry little light, returns whether the walk goes on
+fn russian_roulette(beta: &mut SpectralSample, depth: usize, sampler: &mut Sampler) -> bool {
+    let max = beta
+        .inner
+        .data
+        .to_array()
+        .iter()
+        .fold(0.0, |a: f32, &b| a.max(b));
+    if depth <= 3 || max >= 0.25 {
+        return true;
+    }
+
+    let q = (1.0 - max).max(0.0);
+    if sampler.gen_0_1() < q {
+        return false;
+    }
+
+    *beta = *beta / (1.0 - q);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsdf::LambertianBsdf;
+
+    #[test]
+    fn test_coated_diffuse() {
+        let coated = |thickness| {
+            LayeredBsdf::new(
+                LambertianBsdf::new(ConstantSpectrum::new(1.0)),
+                Ior::Constant(1.5),
+                Some((0.01, 0.01)),
+                thickness,
+                ConstantSpectrum::new(1.0),
+            )
+        };
+        let wavelength = Wavelength::new(550.0);
+        let ctx = &TextureContext::default();
+        let wo = Vec3::<Shading>::new(0.4, 0.0, 0.9).normalize();
+
+        // Fraction of light reflected, from evaluate over the hemisphere and
+        // from sample
+        let albedo = |bsdf: &LayeredBsdf| {
+            let n = 200;
+            let mut evaluated = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let cos_theta = (i as f32 + 0.5) / n as f32;
+                    let phi = 2.0 * PI * (j as f32 + 0.5) / n as f32;
+                    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                    let wi = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                    let f = bsdf.evaluate(wi, wo, ctx, wavelength).hero();
+                    evaluated += f * cos_theta * 2.0 * PI / (n * n) as f32;
+                }
+            }
+
+            let samples = 20000;
+            let mut sampled = 0.0;
+            for i in 0..samples {
+                let (wi, values, pdfs) =
+                    bsdf.sample(wo, ctx, wavelength, &mut Sampler::new(0, 0, i, 0));
+                if pdfs.hero() > 0.0 {
+                    sampled += values.hero() * wi.cos_theta() / pdfs.hero() / samples as f32;
+                }
+            }
+
+            (evaluated, sampled)
+        };
+
+        // Without absorption, most light makes it back out, short of what
+        // single scattering microfacets lose
+        let (evaluated, sampled) = albedo(&coated(0.0));
+        assert!(sampled > 0.8 && sampled <= 1.0, "{}", sampled);
+        assert!(
+            (evaluated - sampled).abs() < 0.03,
+            "{} {}",
+            evaluated,
+            sampled
+        );
+
+        // A thicker coating absorbs more
+        let (evaluated, sampled) = albedo(&coated(0.2));
+        assert!(sampled < 0.7, "{}", sampled);
+        assert!(
+            (evaluated - sampled).abs() < 0.03,
+            "{} {}",
+            evaluated,
+            sampled
+        );
+    }
+}
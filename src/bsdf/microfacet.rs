@@ -5,20 +5,21 @@ use crate::{
     math,
     math::{PdfSet, Shading, Vec3},
     sampling::{self, ggx, Sampler},
-    spectrum::{SampleableSpectrum, SpectralSample, Spectrum, Wavelength},
+    spectrum::{SpectralSample, Wavelength},
+    texture::{SampleableTexture, Texture, TextureContext},
 };
 
 use std::f32::consts::PI;
 
 #[derive(Debug, Clone)]
 pub struct MicrofacetBsdf {
-    reflectance: Spectrum,
+    reflectance: Texture,
     alpha_x: f32,
     alpha_y: f32,
 }
 
 impl MicrofacetBsdf {
-    pub fn new<S: Into<Spectrum>>(reflectance: S, roughness_x: f32, roughness_y: f32) -> Self {
+    pub fn new<S: Into<Texture>>(reflectance: S, roughness_x: f32, roughness_y: f32) -> Self {
         assert_ne!(roughness_x, 0.0);
         assert_ne!(roughness_y, 0.0);
         Self {
@@ -34,6 +35,7 @@ impl SampleableBsdf for MicrofacetBsdf {
         &self,
         wi: Vec3<Shading>,
         wo: Vec3<Shading>,
+        ctx: &TextureContext,
         hero_wavelength: Wavelength,
     ) -> SpectralSample {
         let cos_theta_o = wo.cos_theta().abs();
@@ -53,10 +55,17 @@ impl SampleableBsdf for MicrofacetBsdf {
         let d = ggx::evaluate(wh, self.alpha_x, self.alpha_y);
         let f = math::fresnel_dielectric(wi.dot(wh_facing), 1.5, 1.0);
         let g = ggx::g(wo, wh, self.alpha_x, self.alpha_y);
-        self.reflectance.evaluate(hero_wavelength) * d * g * f / (4.0 * cos_theta_o * cos_theta_i)
+        self.reflectance.evaluate(ctx, hero_wavelength) * d * g * f
+            / (4.0 * cos_theta_o * cos_theta_i)
     }
 
-    fn pdf(&self, wi: Vec3<Shading>, wo: Vec3<Shading>, hero_wavelength: Wavelength) -> PdfSet {
+    fn pdf(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> PdfSet {
         let wh = (wi + wo).normalize();
         let res = ggx::pdf(wo, wh, self.alpha_x, self.alpha_y) / (4.0 * wo.dot(wh));
         PdfSet::splat(res)
@@ -65,11 +74,12 @@ impl SampleableBsdf for MicrofacetBsdf {
     fn sample(
         &self,
         wo: Vec3<Shading>,
+        ctx: &TextureContext,
         hero_wavelength: Wavelength,
         sampler: &mut Sampler,
     ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
         let wh = ggx::sample(wo, self.alpha_x, self.alpha_y, sampler);
-        let wi = reflect(wo, wh);
+        let wi = math::reflect(wo, wh);
 
         if wo.cos_theta() == 0.0 || wo.dot(wh) < 0.0 || !wo.same_hemisphere(wi) {
             return (
@@ -82,12 +92,8 @@ impl SampleableBsdf for MicrofacetBsdf {
         let pdf = ggx::pdf(wo, wh, self.alpha_x, self.alpha_y) / (4.0 * wo.dot(wh));
         (
             wi,
-            self.evaluate(wi, wo, hero_wavelength),
+            self.evaluate(wi, wo, ctx, hero_wavelength),
             PdfSet::splat(pdf),
         )
     }
 }
-
-fn reflect(wo: Vec3<Shading>, n: Vec3<Shading>) -> Vec3<Shading> {
-    -wo + (2.0 * wo.dot(n) * n)
-}
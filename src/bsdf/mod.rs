@@ -1,12 +1,20 @@
 use crate::{
     math::{PdfSet, Shading, Vec3},
     sampling::Sampler,
+    shape::Intersection,
     spectrum::{SpectralSample, Wavelength},
+    texture::TextureContext,
 };
 use enum_dispatch::enum_dispatch;
 
-mod fresnel;
-pub use fresnel::FresnelBsdf;
+mod conductor;
+pub use conductor::ConductorBsdf;
+
+mod dielectric;
+pub use dielectric::DielectricBsdf;
+
+mod layered;
+pub use layered::LayeredBsdf;
 
 mod lambertian;
 pub use lambertian::LambertianBsdf;
@@ -14,6 +22,18 @@ pub use lambertian::LambertianBsdf;
 mod microfacet;
 pub use microfacet::MicrofacetBsdf;
 
+mod normal_mapped;
+pub use normal_mapped::NormalMappedBsdf;
+
+mod principled;
+pub use principled::{PrincipledBsdf, PrincipledParameters};
+
+mod rough_conductor;
+pub use rough_conductor::RoughConductorBsdf;
+
+mod rough_dielectric;
+pub use rough_dielectric::RoughDielectricBsdf;
+
 mod specular;
 pub use specular::SpecularBsdf;
 
@@ -26,15 +46,23 @@ pub trait SampleableBsdf {
         &self,
         wi: Vec3<Shading>,
         wo: Vec3<Shading>,
+        ctx: &TextureContext,
         hero_wavelength: Wavelength,
     ) -> SpectralSample;
 
-    fn pdf(&self, wi: Vec3<Shading>, wo: Vec3<Shading>, hero_wavelength: Wavelength) -> PdfSet;
+    fn pdf(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> PdfSet;
 
     // Returns the sampled direction as well as the PDF for each wavelength
     fn sample(
         &self,
         wo: Vec3<Shading>,
+        ctx: &TextureContext,
         hero_wavelength: Wavelength,
         sampler: &mut Sampler,
     ) -> (Vec3<Shading>, SpectralSample, PdfSet);
@@ -42,6 +70,18 @@ pub trait SampleableBsdf {
     fn is_specular(&self) -> bool {
         false
     }
+
+    // Whether light can reach wo from the other side of the surface, so that
+    // lights behind it are worth sampling
+    fn transmits(&self) -> bool {
+        false
+    }
+
+    // The perturbed shading normal at the hit for materials with a normal or
+    // bump map, see Intersection::with_normal_map
+    fn shading_normal(&self, _hit: &Intersection, _ctx: &TextureContext) -> Option<Vec3> {
+        None
+    }
 }
 
 #[enum_dispatch(SampleableBsdf)]
@@ -50,6 +90,12 @@ pub enum Bsdf {
     LambertianBsdf,
     MicrofacetBsdf,
     SpecularBsdf,
-    FresnelBsdf,
+    DielectricBsdf,
+    RoughDielectricBsdf,
+    ConductorBsdf,
+    RoughConductorBsdf,
+    LayeredBsdf,
+    PrincipledBsdf,
+    NormalMappedBsdf,
     NullBsdf,
 }
@@ -0,0 +1,70 @@
+use crate::{
+    bsdf::{Bsdf, SampleableBsdf},
+    math::{PdfSet, Shading, Vec3},
+    sampling::Sampler,
+    shape::Intersection,
+    spectrum::{SpectralSample, Wavelength},
+    texture::{NormalMap, TextureContext},
+};
+
+// Another BSDF on a surface with a normal or bump map. Integrators apply the
+// map to the hit's shading frame before evaluating the BSDF, which is then the
+// base one as is
+#[derive(Debug, Clone)]
+pub struct NormalMappedBsdf {
+    base: Box<Bsdf>,
+    map: NormalMap,
+}
+
+impl NormalMappedBsdf {
+    pub fn new<B: Into<Bsdf>>(base: B, map: NormalMap) -> Self {
+        Self {
+            base: Box::new(base.into()),
+            map,
+        }
+    }
+}
+
+impl SampleableBsdf for NormalMappedBsdf {
+    fn evaluate(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> SpectralSample {
+        self.base.evaluate(wi, wo, ctx, hero_wavelength)
+    }
+
+    fn pdf(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> PdfSet {
+        self.base.pdf(wi, wo, ctx, hero_wavelength)
+    }
+
+    fn sample(
+        &self,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
+        self.base.sample(wo, ctx, hero_wavelength, sampler)
+    }
+
+    fn is_specular(&self) -> bool {
+        self.base.is_specular()
+    }
+
+    fn transmits(&self) -> bool {
+        self.base.transmits()
+    }
+
+    fn shading_normal(&self, hit: &Intersection, ctx: &TextureContext) -> Option<Vec3> {
+        self.map.shading_normal(hit, ctx)
+    }
+}
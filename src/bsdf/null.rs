@@ -4,6 +4,7 @@ use crate::{
     math::{PdfSet, Shading, Vec3},
     sampling::Sampler,
     spectrum::{SpectralSample, Wavelength},
+    texture::TextureContext,
 };
 
 #[derive(Debug, Clone)]
@@ -20,18 +21,26 @@ impl SampleableBsdf for NullBsdf {
         &self,
         _wi: Vec3<Shading>,
         _wo: Vec3<Shading>,
+        _ctx: &TextureContext,
         _hero_wavelength: Wavelength,
     ) -> SpectralSample {
         unreachable!()
     }
 
-    fn pdf(&self, _wi: Vec3<Shading>, _wo: Vec3<Shading>, _hero_wavelength: Wavelength) -> PdfSet {
+    fn pdf(
+        &self,
+        _wi: Vec3<Shading>,
+        _wo: Vec3<Shading>,
+        _ctx: &TextureContext,
+        _hero_wavelength: Wavelength,
+    ) -> PdfSet {
         unreachable!()
     }
 
     fn sample(
         &self,
         _wo: Vec3<Shading>,
+        _ctx: &TextureContext,
         _hero_wavelength: Wavelength,
         _sampler: &mut Sampler,
     ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
@@ -0,0 +1,331 @@
+use crate::{
+    bsdf::{RoughDielectricBsdf, SampleableBsdf},
+    math::{self, PdfSet, Shading, Vec3},
+    sampling::{self, ggx, Sampler},
+    spectrum::{ConstantSpectrum, Ior, SpectralSample, Wavelength},
+    texture::{SampleableTexture, Texture, TextureContext},
+};
+
+use std::f32::consts::PI;
+
+// Everything but the base color and index of refraction is in [0, 1]
+#[derive(Debug, Clone)]
+pub struct PrincipledParameters {
+    pub base_color: Texture,
+    pub metallic: f32,
+    pub roughness: f32,
+    // Reflectance of dielectrics at normal incidence, 0.5 being the usual 4%
+    pub specular: f32,
+    pub sheen: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub transmission: f32,
+    pub ior: Ior,
+}
+
+// Disney's principled BSDF, one material covering most surfaces through a few
+// intuitive parameters, see
+// https://media.disneyanimation.com/uploads/production/publication_asset/48/asset/s2012_pbs_disney_brdf_notes_v3.pdf
+//
+// It mixes diffuse (with sheen), specular, clearcoat and glass lobes. Sampling
+// picks one lobe by its weight, while evaluate and pdf cover all of them, so
+// the pdfs are those of the whole mixture
+#[derive(Debug, Clone)]
+pub struct PrincipledBsdf {
+    base_color: Texture,
+    metallic: f32,
+    roughness: f32,
+    specular: f32,
+    sheen: f32,
+    alpha: f32,
+    clearcoat_alpha: f32,
+    glass: RoughDielectricBsdf,
+    // Of the diffuse, specular, clearcoat and glass lobes, which are also the
+    // probabilities of sampling them once normalized
+    weights: [f32; 4],
+}
+
+impl PrincipledBsdf {
+    pub fn new(parameters: PrincipledParameters) -> Self {
+        let PrincipledParameters {
+            base_color,
+            metallic,
+            roughness,
+            specular,
+            sheen,
+            clearcoat,
+            clearcoat_roughness,
+            transmission,
+            ior,
+        } = parameters;
+
+        // Microfacets can't be perfectly smooth
+        let roughness = roughness.max(1e-3);
+        let glass = RoughDielectricBsdf::new(
+            ConstantSpectrum::new(1.0),
+            base_color.clone(),
+            ior,
+            roughness,
+            roughness,
+        );
+
+        let glass_weight = (1.0 - metallic) * transmission;
+        let weights = [
+            (1.0 - metallic) * (1.0 - transmission),
+            1.0 - glass_weight,
+            0.25 * clearcoat,
+            glass_weight,
+        ];
+
+        Self {
+            base_color,
+            metallic,
+            roughness,
+            specular,
+            sheen,
+            alpha: ggx::roughness_to_alpha(roughness),
+            clearcoat_alpha: ggx::roughness_to_alpha(clearcoat_roughness.max(1e-3)),
+            glass,
+            weights,
+        }
+    }
+
+    fn probabilities(&self) -> [f32; 4] {
+        let total: f32 = self.weights.iter().sum();
+        self.weights.map(|weight| weight / total)
+    }
+
+    // The reflection lobes, for wi and wo on the upper side of the surface
+    fn evaluate_reflection(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        wavelength: Wavelength,
+    ) -> SpectralSample {
+        let (cos_theta_i, cos_theta_o) = (wi.cos_theta(), wo.cos_theta());
+        let wh = wi + wo;
+        if cos_theta_i <= 0.0 || cos_theta_o <= 0.0 || wh == Vec3::splat(0.0) {
+            return SpectralSample::splat(0.0);
+        }
+        let wh = wh.normalize();
+        let cos_theta_d = wi.dot(wh);
+        let base_color = self.base_color.evaluate(ctx, wavelength);
+
+        // Diffuse, with retro-reflection at grazing angles on rough surfaces,
+        // plus sheen
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_theta_d * cos_theta_d;
+        let fd = (1.0 + (fd90 - 1.0) * schlick_weight(cos_theta_i))
+            * (1.0 + (fd90 - 1.0) * schlick_weight(cos_theta_o));
+        let diffuse = base_color * (fd / PI)
+            + SpectralSample::splat(self.sheen * schlick_weight(cos_theta_d));
+
+        // Specular, tinted by the base color as the surface gets metallic
+        let f0 = base_color * self.metallic
+            + SpectralSample::splat(0.08 * self.specular * (1.0 - self.metallic));
+        let weight = schlick_weight(cos_theta_d);
+        let fresnel = f0 * (1.0 - weight) + SpectralSample::splat(weight);
+        let specular = fresnel * microfacet(wi, wo, wh, self.alpha);
+
+        // Clearcoat, a colorless layer of varnish with an index of 1.5
+        let clearcoat = math::fresnel_dielectric(cos_theta_d, 1.0, 1.5)
+            * microfacet(wi, wo, wh, self.clearcoat_alpha);
+
+        diffuse * self.weights[0]
+            + specular * self.weights[1]
+            + SpectralSample::splat(clearcoat * self.weights[2])
+    }
+
+    fn pdf_reflection(&self, wi: Vec3<Shading>, wo: Vec3<Shading>) -> f32 {
+        let wh = wi + wo;
+        if wi.cos_theta() <= 0.0 || wo.cos_theta() <= 0.0 || wh == Vec3::splat(0.0) {
+            return 0.0;
+        }
+        let wh = wh.normalize();
+
+        let [diffuse, specular, clearcoat, _] = self.probabilities();
+        let reflect_pdf = |alpha| ggx::pdf(wo, wh, alpha, alpha) / (4.0 * wo.dot(wh));
+        diffuse * sampling::pdf_cosine_unit_hemisphere(wi.cos_theta())
+            + specular * reflect_pdf(self.alpha)
+            + clearcoat * reflect_pdf(self.clearcoat_alpha)
+    }
+}
+
+impl SampleableBsdf for PrincipledBsdf {
+    fn evaluate(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> SpectralSample {
+        let (wi_up, wo_up) = two_sided(wi, wo);
+        let mut f = self.evaluate_reflection(wi_up, wo_up, ctx, hero_wavelength);
+        if self.weights[3] > 0.0 {
+            f += self.glass.evaluate(wi, wo, ctx, hero_wavelength) * self.weights[3];
+        }
+
+        f
+    }
+
+    fn pdf(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> PdfSet {
+        let (wi_up, wo_up) = two_sided(wi, wo);
+        let mut pdf = PdfSet::splat(self.pdf_reflection(wi_up, wo_up));
+        let glass = self.probabilities()[3];
+        if glass > 0.0 {
+            pdf = pdf + self.glass.pdf(wi, wo, ctx, hero_wavelength) * glass;
+        }
+
+        pdf
+    }
+
+    fn sample(
+        &self,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
+        let invalid = (
+            Vec3::splat(0.0),
+            SpectralSample::splat(0.0),
+            PdfSet::splat(0.0),
+        );
+        if wo.cos_theta() == 0.0 {
+            return invalid;
+        }
+
+        // Pick a lobe
+        let [diffuse, specular, clearcoat, _] = self.probabilities();
+        let u = sampler.gen_0_1();
+        let flip = wo.cos_theta().signum();
+        let wi = if u < diffuse {
+            flip * sampling::cosine_unit_hemisphere(sampler.gen_0_1(), sampler.gen_0_1())
+        } else if u < diffuse + specular + clearcoat {
+            let alpha = if u < diffuse + specular {
+                self.alpha
+            } else {
+                self.clearcoat_alpha
+            };
+            let wh = flip * ggx::sample(flip * wo, alpha, alpha, sampler);
+            let wi = math::reflect(wo, wh);
+            // Don't let steep microfacets pass reflections off as glass
+            if !wi.same_hemisphere(wo) {
+                return invalid;
+            }
+            wi
+        } else {
+            self.glass.sample(wo, ctx, hero_wavelength, sampler).0
+        };
+
+        let pdfs = self.pdf(wi, wo, ctx, hero_wavelength);
+        if pdfs.hero() == 0.0 {
+            return invalid;
+        }
+        (wi, self.evaluate(wi, wo, ctx, hero_wavelength), pdfs)
+    }
+
+    fn transmits(&self) -> bool {
+        self.weights[3] > 0.0
+    }
+}
+
+// Flips wi and wo to the upper side of the surface, the reflection lobes are
+// the same from both sides
+fn two_sided(wi: Vec3<Shading>, wo: Vec3<Shading>) -> (Vec3<Shading>, Vec3<Shading>) {
+    if wo.cos_theta() < 0.0 {
+        (-wi, -wo)
+    } else {
+        (wi, wo)
+    }
+}
+
+fn schlick_weight(cos_theta: f32) -> f32 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+// GGX reflection without the Fresnel term
+fn microfacet(wi: Vec3<Shading>, wo: Vec3<Shading>, wh: Vec3<Shading>, alpha: f32) -> f32 {
+    ggx::evaluate(wh, alpha, alpha) * ggx::g(wo, wi, alpha, alpha)
+        / (4.0 * wi.cos_theta() * wo.cos_theta())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_principled() {
+        let principled = PrincipledBsdf::new(PrincipledParameters {
+            base_color: ConstantSpectrum::new(0.8).into(),
+            metallic: 0.2,
+            roughness: 0.3,
+            specular: 0.5,
+            sheen: 0.5,
+            clearcoat: 1.0,
+            clearcoat_roughness: 0.1,
+            transmission: 0.3,
+            ior: Ior::BK7,
+        });
+        let (ctx, wavelength) = (TextureContext::default(), Wavelength::new(550.0));
+
+        for &wo in [
+            Vec3::<Shading>::new(0.4, 0.0, 0.9).normalize(),
+            Vec3::<Shading>::new(-0.7, 0.2, -0.3).normalize(),
+        ]
+        .iter()
+        {
+            // Light scattered over the sphere, from evaluate and from sample
+            let n = 400;
+            let mut evaluated = 0.0;
+            let mut pdf_integral = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let cos_theta = 1.0 - 2.0 * (i as f32 + 0.5) / n as f32;
+                    let phi = 2.0 * PI * (j as f32 + 0.5) / n as f32;
+                    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                    let wi = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                    let d_omega = 4.0 * PI / (n * n) as f32;
+                    evaluated += principled.evaluate(wi, wo, &ctx, wavelength).hero()
+                        * cos_theta.abs()
+                        * d_omega;
+                    pdf_integral += principled.pdf(wi, wo, &ctx, wavelength).hero() * d_omega;
+                }
+            }
+
+            // The pdf integrates to the fraction of samples that aren't lost
+            // off steep microfacets
+            let samples = 20000;
+            let (mut sampled, mut valid) = (0.0, 0);
+            for i in 0..samples {
+                let (wi, values, pdfs) =
+                    principled.sample(wo, &ctx, wavelength, &mut Sampler::new(0, 0, i, 0));
+                if pdfs.hero() > 0.0 {
+                    sampled += values.hero() * wi.cos_theta().abs() / pdfs.hero();
+                    valid += 1;
+                }
+            }
+            let sampled = sampled / samples as f32;
+            let valid = valid as f32 / samples as f32;
+
+            assert!(
+                (pdf_integral - valid).abs() < 0.03,
+                "{} {}",
+                pdf_integral,
+                valid
+            );
+            assert!(
+                (evaluated - sampled).abs() < 0.03,
+                "{} {}",
+                evaluated,
+                sampled
+            );
+        }
+    }
+}
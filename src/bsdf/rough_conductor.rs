@@ -0,0 +1,103 @@
+use crate::{
+    bsdf::SampleableBsdf,
+    math::{self, PdfSet, Shading, Vec3},
+    sampling::{ggx, Sampler},
+    spectrum::{ComplexIor, SpectralSample, Wavelength},
+    texture::TextureContext,
+};
+
+// Brushed or worn metal, GGX microfacets that each reflect with the conductor
+// Fresnel equations
+#[derive(Debug, Clone)]
+pub struct RoughConductorBsdf {
+    ior: ComplexIor,
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl RoughConductorBsdf {
+    pub fn new(ior: ComplexIor, roughness_x: f32, roughness_y: f32) -> Self {
+        assert_ne!(roughness_x, 0.0);
+        assert_ne!(roughness_y, 0.0);
+        Self {
+            ior,
+            alpha_x: ggx::roughness_to_alpha(roughness_x),
+            alpha_y: ggx::roughness_to_alpha(roughness_y),
+        }
+    }
+
+    fn half_vector(wi: Vec3<Shading>, wo: Vec3<Shading>) -> Option<Vec3<Shading>> {
+        let wh = wi + wo;
+        if !wi.same_hemisphere(wo)
+            || wi.cos_theta() == 0.0
+            || wo.cos_theta() == 0.0
+            || wh == Vec3::splat(0.0)
+        {
+            return None;
+        }
+
+        Some(wh.normalize())
+    }
+}
+
+impl SampleableBsdf for RoughConductorBsdf {
+    fn evaluate(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        _ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> SpectralSample {
+        let wh = match Self::half_vector(wi, wo) {
+            Some(wh) => wh,
+            None => return SpectralSample::splat(0.0),
+        };
+
+        let d = ggx::evaluate(wh, self.alpha_x, self.alpha_y);
+        let g = ggx::g(wo, wi, self.alpha_x, self.alpha_y);
+        let fresnel = self.ior.fresnel(wo.dot(wh).abs(), hero_wavelength);
+        fresnel * d * g / (4.0 * (wo.cos_theta() * wi.cos_theta()).abs())
+    }
+
+    fn pdf(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        _ctx: &TextureContext,
+        _hero_wavelength: Wavelength,
+    ) -> PdfSet {
+        match Self::half_vector(wi, wo) {
+            Some(wh) => PdfSet::splat(
+                ggx::pdf(wo, wh, self.alpha_x, self.alpha_y) / (4.0 * wo.dot(wh).abs()),
+            ),
+            None => PdfSet::splat(0.0),
+        }
+    }
+
+    fn sample(
+        &self,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
+        if wo.cos_theta() == 0.0 {
+            return (
+                Vec3::splat(0.0),
+                SpectralSample::splat(0.0),
+                PdfSet::splat(0.0),
+            );
+        }
+
+        // The microfacet distribution is sampled from above, so flip wo over
+        // when it's below the surface
+        let flip = wo.cos_theta().signum();
+        let wh = flip * ggx::sample(flip * wo, self.alpha_x, self.alpha_y, sampler);
+        let wi = math::reflect(wo, wh);
+        (
+            wi,
+            self.evaluate(wi, wo, ctx, hero_wavelength),
+            self.pdf(wi, wo, ctx, hero_wavelength),
+        )
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{
+    bsdf::SampleableBsdf,
+    math::{self, PdfSet, Shading, Vec3},
+    sampling::{ggx, Sampler},
+    spectrum::{Ior, SpectralSample, Wavelength},
+    texture::{SampleableTexture, Texture, TextureContext},
+};
+
+// Rough boundary between air and glass, made of GGX microfacets that each
+// reflect or refract, see
+// https://www.graphics.cornell.edu/~bjw/microfacetbsdf.pdf
+//
+// Unlike smooth glass, every wavelength can refract in any direction, so with
+// dispersion all the lanes still get a pdf, just from differently oriented
+// microfacets
+#[derive(Debug, Clone)]
+pub struct RoughDielectricBsdf {
+    reflectance: Texture,
+    transmittance: Texture,
+    ior: Ior,
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl RoughDielectricBsdf {
+    pub fn new<S: Into<Texture>, T: Into<Texture>>(
+        s: S,
+        t: T,
+        ior: Ior,
+        roughness_x: f32,
+        roughness_y: f32,
+    ) -> Self {
+        assert_ne!(roughness_x, 0.0);
+        assert_ne!(roughness_y, 0.0);
+        Self {
+            reflectance: s.into(),
+            transmittance: t.into(),
+            ior,
+            alpha_x: ggx::roughness_to_alpha(roughness_x),
+            alpha_y: ggx::roughness_to_alpha(roughness_y),
+        }
+    }
+
+    // The BSDF without the reflectance or transmittance and its pdf, for a
+    // wavelength with index of refraction eta
+    fn evaluate_lane(&self, wi: Vec3<Shading>, wo: Vec3<Shading>, eta: f32) -> (f32, f32) {
+        let (cos_theta_o, cos_theta_i) = (wo.cos_theta(), wi.cos_theta());
+        if cos_theta_o == 0.0 || cos_theta_i == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        // Half vector, generalized to refraction with the relative index of
+        // refraction of wi's side
+        let reflect = cos_theta_o * cos_theta_i > 0.0;
+        let relative_eta = if cos_theta_o > 0.0 { eta } else { 1.0 / eta };
+        let wh = if reflect {
+            wo + wi
+        } else {
+            wo + relative_eta * wi
+        };
+        if wh == Vec3::splat(0.0) {
+            return (0.0, 0.0);
+        }
+        let wh = wh.normalize().face_forward(Vec3::new(0.0, 0.0, 1.0));
+
+        // Microfacets facing away from either direction don't contribute
+        if wo.dot(wh) * cos_theta_o <= 0.0 || wi.dot(wh) * cos_theta_i <= 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let d = ggx::evaluate(wh, self.alpha_x, self.alpha_y);
+        let g = ggx::g(wo, wi, self.alpha_x, self.alpha_y);
+        let fresnel = math::fresnel_dielectric(wo.dot(wh), 1.0, eta);
+        let pdf_wh = ggx::pdf(wo, wh, self.alpha_x, self.alpha_y);
+
+        if reflect {
+            let f = d * g * fresnel / (4.0 * (cos_theta_o * cos_theta_i).abs());
+            let pdf = fresnel * pdf_wh / (4.0 * wo.dot(wh).abs());
+            (f, pdf)
+        } else {
+            // Includes the radiance scaling by the squared relative index of
+            // refraction, like smooth glass
+            let denom = (wo.dot(wh) + relative_eta * wi.dot(wh)).powi(2);
+            let t = 1.0 - fresnel;
+            let f = t * d * g * (wi.dot(wh) * wo.dot(wh)).abs()
+                / ((cos_theta_o * cos_theta_i).abs() * denom);
+            let pdf = t * pdf_wh * relative_eta.powi(2) * wi.dot(wh).abs() / denom;
+            (f, pdf)
+        }
+    }
+
+    fn evaluate_lanes(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        wavelength: Wavelength,
+    ) -> (SpectralSample, PdfSet) {
+        let eta = self.ior.evaluate(wavelength).inner.data.to_array();
+        let lanes = eta.map(|eta| self.evaluate_lane(wi, wo, eta));
+
+        let color = if wi.same_hemisphere(wo) {
+            &self.reflectance
+        } else {
+            &self.transmittance
+        };
+        let f = SpectralSample::new(lanes[0].0, lanes[1].0, lanes[2].0, lanes[3].0);
+        (
+            color.evaluate(ctx, wavelength) * f,
+            PdfSet::new(lanes[0].1, lanes[1].1, lanes[2].1, lanes[3].1),
+        )
+    }
+}
+
+impl SampleableBsdf for RoughDielectricBsdf {
+    fn evaluate(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> SpectralSample {
+        self.evaluate_lanes(wi, wo, ctx, hero_wavelength).0
+    }
+
+    fn pdf(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> PdfSet {
+        self.evaluate_lanes(wi, wo, ctx, hero_wavelength).1
+    }
+
+    // Samples a visible microfacet, then reflects or refracts with the hero
+    // wavelength's Fresnel reflectance
+    fn sample(
+        &self,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
+        let invalid = (
+            Vec3::splat(0.0),
+            SpectralSample::splat(0.0),
+            PdfSet::splat(0.0),
+        );
+        if wo.cos_theta() == 0.0 {
+            return invalid;
+        }
+
+        // The microfacet distribution is sampled from above, flip wo over and
+        // the microfacet back from below the surface
+        let flip = wo.cos_theta().signum();
+        let wh = flip * ggx::sample(flip * wo, self.alpha_x, self.alpha_y, sampler);
+
+        let eta = self.ior.evaluate_single(hero_wavelength.hero());
+        let fresnel = math::fresnel_dielectric(wo.dot(wh) * flip, 1.0, eta);
+        let reflect = sampler.gen_0_1() < fresnel;
+        let wi = if reflect {
+            math::reflect(wo, wh)
+        } else {
+            let relative_eta = if flip > 0.0 { eta } else { 1.0 / eta };
+            match math::refract(wo, wh, 1.0 / relative_eta) {
+                Some(wi) => wi,
+                None => return invalid,
+            }
+        };
+
+        // Steep microfacets can send wi to the wrong side of the surface,
+        // where it would be mistaken for the other lobe
+        if wi.same_hemisphere(wo) != reflect {
+            return invalid;
+        }
+
+        let (values, pdfs) = self.evaluate_lanes(wi, wo, ctx, hero_wavelength);
+        (wi, values, pdfs)
+    }
+
+    fn transmits(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::ConstantSpectrum;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_rough_dielectric() {
+        let glass = RoughDielectricBsdf::new(
+            ConstantSpectrum::new(1.0),
+            ConstantSpectrum::new(1.0),
+            Ior::SF11,
+            0.5,
+            0.3,
+        );
+        let (ctx, wavelength) = (TextureContext::default(), Wavelength::new(500.0));
+
+        for &wo in [
+            Vec3::<Shading>::new(0.3, 0.2, 0.9).normalize(),
+            Vec3::<Shading>::new(-0.6, 0.1, -0.5).normalize(),
+        ]
+        .iter()
+        {
+            // Every lane's pdf integrates to the fraction of samples that
+            // aren't lost, which happens when reflecting or refracting off a
+            // microfacet sends wi to the wrong side of the surface
+            let n = 400;
+            let mut integral = [0.0; 4];
+            for i in 0..n {
+                for j in 0..n {
+                    let cos_theta = 1.0 - 2.0 * (i as f32 + 0.5) / n as f32;
+                    let phi = 2.0 * PI * (j as f32 + 0.5) / n as f32;
+                    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                    let wi = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                    let pdfs = glass.pdf(wi, wo, &ctx, wavelength);
+                    let pdfs = [pdfs.x(), pdfs.y(), pdfs.z(), pdfs.w()];
+                    for (sum, pdf) in integral.iter_mut().zip(pdfs) {
+                        *sum += pdf * 4.0 * PI / (n * n) as f32;
+                    }
+                }
+            }
+
+            let samples = 4096;
+            let (mut valid, mut dropped) = (0, 0);
+            for i in 0..samples {
+                let (_, values, pdfs) =
+                    glass.sample(wo, &ctx, wavelength, &mut Sampler::new(0, 0, i, 0));
+                if pdfs.hero() == 0.0 {
+                    continue;
+                }
+
+                valid += 1;
+                assert!(values.hero() > 0.0);
+                // Refraction rarely needs to drop the other wavelengths
+                if pdfs.y() == 0.0 || pdfs.w() == 0.0 {
+                    dropped += 1;
+                }
+            }
+
+            assert!(dropped < samples / 100, "{}", dropped);
+            let valid = valid as f32 / samples as f32;
+            for &sum in integral.iter() {
+                assert!((sum - valid).abs() < 0.03, "{} {:?}", valid, integral);
+            }
+        }
+    }
+}
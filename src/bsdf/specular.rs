@@ -4,18 +4,19 @@ use crate::{
     bsdf::SampleableBsdf,
     math::{self, PdfSet, Shading, Vec3},
     sampling::{self, Sampler},
-    spectrum::{SampleableSpectrum, SpectralSample, Spectrum, Wavelength},
+    spectrum::{SpectralSample, Wavelength},
+    texture::{SampleableTexture, Texture, TextureContext},
 };
 
 use std::f32::consts::PI;
 
 #[derive(Debug, Clone)]
 pub struct SpecularBsdf {
-    reflected_color: Spectrum,
+    reflected_color: Texture,
 }
 
 impl SpecularBsdf {
-    pub fn new<S: Into<Spectrum>>(s: S) -> Self {
+    pub fn new<S: Into<Texture>>(s: S) -> Self {
         Self {
             reflected_color: s.into(),
         }
@@ -27,23 +28,31 @@ impl SampleableBsdf for SpecularBsdf {
         &self,
         wi: Vec3<Shading>,
         wo: Vec3<Shading>,
+        ctx: &TextureContext,
         hero_wavelength: Wavelength,
     ) -> SpectralSample {
         SpectralSample::splat(0.0)
     }
 
-    fn pdf(&self, wi: Vec3<Shading>, wo: Vec3<Shading>, hero_wavelength: Wavelength) -> PdfSet {
+    fn pdf(
+        &self,
+        wi: Vec3<Shading>,
+        wo: Vec3<Shading>,
+        ctx: &TextureContext,
+        hero_wavelength: Wavelength,
+    ) -> PdfSet {
         PdfSet::splat(0.0)
     }
 
     fn sample(
         &self,
         wo: Vec3<Shading>,
+        ctx: &TextureContext,
         hero_wavelength: Wavelength,
         sampler: &mut Sampler,
     ) -> (Vec3<Shading>, SpectralSample, PdfSet) {
         let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
-        let bsdf = self.reflected_color.evaluate(hero_wavelength)
+        let bsdf = self.reflected_color.evaluate(ctx, hero_wavelength)
             / wi.cos_theta().abs();
         (wi, bsdf, PdfSet::splat(1.0))
     }
@@ -0,0 +1,94 @@
+use std::f32::consts::PI;
+
+use crate::{
+    camera::{CameraRay, Projection},
+    math::{Camera as CameraCoord, Matrix, PdfSet, Point3, Ray, Vec3, World},
+    sampling::Sampler,
+    spectrum::{SpectralSample, Wavelength},
+};
+
+// Every direction around the camera, laid out like environment maps (with
+// camera space in place of world space). Rendered with the default camera
+// orientation, the image can be used as the environment of another scene
+#[derive(Debug, Clone)]
+pub struct EquirectangularCamera {
+    camera_to_world: Matrix<CameraCoord, World>,
+}
+
+impl EquirectangularCamera {
+    pub fn new(camera_to_world: Matrix<CameraCoord, World>) -> Self {
+        Self { camera_to_world }
+    }
+}
+
+impl Projection for EquirectangularCamera {
+    fn generate_ray(
+        &self,
+        (x, y): (f32, f32),
+        _wavelength: Wavelength,
+        _sampler: &mut Sampler,
+    ) -> Option<CameraRay> {
+        let (sin_phi, cos_phi) = (2.0 * PI * x).sin_cos();
+        let (sin_theta, cos_theta) = (PI * y).sin_cos();
+        let dir = Vec3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi);
+
+        Some(CameraRay {
+            ray: Ray::new(
+                &self.camera_to_world * Point3::new(0.0, 0.0, 0.0),
+                &self.camera_to_world * dir,
+            ),
+            weight: SpectralSample::splat(1.0),
+            wavelength_pdfs: PdfSet::splat(1.0),
+            differential: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::look_at;
+
+    #[test]
+    fn test_equirectangular() {
+        let camera = EquirectangularCamera::new(look_at(
+            Point3::new(1.0, 2.0, 3.0),
+            Point3::new(1.0, 2.0, 4.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ));
+
+        let mut sampler = Sampler::new(0, 0, 0, 0);
+        let wavelength = Wavelength::new(550.0);
+        let mut generate_ray = |film| {
+            camera
+                .generate_ray(film, wavelength, &mut sampler)
+                .unwrap()
+                .ray
+        };
+
+        // Laid out like environment maps: +x on the left edge, the view
+        // direction a quarter of the way across and +y at the top
+        let left = generate_ray((0.0, 0.5));
+        assert!(left.o().distance(Point3::new(1.0, 2.0, 3.0)) < 1e-5);
+        assert!((left.d() - Vec3::new(1.0, 0.0, 0.0)).len() < 1e-5);
+        let forward = generate_ray((0.25, 0.5)).d();
+        assert!((forward - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-5);
+        let top = generate_ray((0.5, 0.0)).d();
+        assert!((top - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-5);
+
+        // Directions map back to the film coordinates they came from
+        for i in 0..8 {
+            for j in 1..8 {
+                let (x, y) = (i as f32 / 8.0, j as f32 / 8.0);
+                let d = generate_ray((x, y)).d();
+                assert!((d.len() - 1.0).abs() < 1e-5);
+
+                let phi = d.z().atan2(d.x());
+                let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+                let theta = d.y().clamp(-1.0, 1.0).acos();
+                assert!((phi / (2.0 * PI) - x).abs() < 1e-4, "{} {}", x, y);
+                assert!((theta / PI - y).abs() < 1e-4, "{} {}", x, y);
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    camera::{CameraRay, Projection},
+    math::{Camera as CameraCoord, Matrix, PdfSet, Point3, Ray, Vec3, World},
+    sampling::Sampler,
+    spectrum::{SpectralSample, Wavelength},
+};
+
+// Equidistant fisheye, the angle from the view direction grows linearly with
+// the distance from the center of the image. The image circle spans the height
+// of the film, and fov (in degrees) is the angle across it
+#[derive(Debug, Clone)]
+pub struct FisheyeCamera {
+    camera_to_world: Matrix<CameraCoord, World>,
+    // Half the field of view, in radians
+    max_theta: f32,
+    aspect_ratio: f32,
+}
+
+impl FisheyeCamera {
+    pub fn new(camera_to_world: Matrix<CameraCoord, World>, fov: f32, aspect_ratio: f32) -> Self {
+        Self {
+            camera_to_world,
+            max_theta: fov.to_radians() / 2.0,
+            aspect_ratio,
+        }
+    }
+}
+
+impl Projection for FisheyeCamera {
+    fn generate_ray(
+        &self,
+        (x, y): (f32, f32),
+        _wavelength: Wavelength,
+        _sampler: &mut Sampler,
+    ) -> Option<CameraRay> {
+        // Relative to the radius of the image circle
+        let u = (x - 0.5) * 2.0 * self.aspect_ratio;
+        let v = (0.5 - y) * 2.0;
+        let r = (u * u + v * v).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+
+        let (sin_theta, cos_theta) = (r * self.max_theta).sin_cos();
+        let (sin_phi, cos_phi) = if r > 0.0 { (v / r, u / r) } else { (0.0, 1.0) };
+        let dir = Vec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+
+        Some(CameraRay {
+            ray: Ray::new(
+                &self.camera_to_world * Point3::new(0.0, 0.0, 0.0),
+                &self.camera_to_world * dir,
+            ),
+            weight: SpectralSample::splat(1.0),
+            wavelength_pdfs: PdfSet::splat(1.0),
+            differential: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::look_at;
+
+    #[test]
+    fn test_fisheye() {
+        let camera = FisheyeCamera::new(
+            look_at(
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(0.0, 0.0, 1.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            ),
+            180.0,
+            2.0,
+        );
+
+        let mut sampler = Sampler::new(0, 0, 0, 0);
+        let wavelength = Wavelength::new(550.0);
+        let mut generate_ray = |film| {
+            camera
+                .generate_ray(film, wavelength, &mut sampler)
+                .map(|camera_ray| camera_ray.ray.d())
+        };
+
+        let center = generate_ray((0.5, 0.5)).unwrap();
+        assert!((center - Vec3::new(0.0, 0.0, 1.0)).len() < 1e-5);
+
+        // The top and sides of the image circle are 90 degrees off
+        let top = generate_ray((0.5, 0.0)).unwrap();
+        assert!((top - Vec3::new(0.0, 1.0, 0.0)).len() < 1e-5);
+        let right = generate_ray((0.75, 0.5)).unwrap();
+        assert!((right - Vec3::new(1.0, 0.0, 0.0)).len() < 1e-5);
+        let halfway = generate_ray((0.5, 0.25)).unwrap();
+        assert!((halfway.dot(center) - 45f32.to_radians().cos()).abs() < 1e-5);
+
+        // Outside of the image circle
+        assert!(generate_ray((0.0, 0.5)).is_none());
+        assert!(generate_ray((0.74, 0.01)).is_none());
+    }
+}
@@ -0,0 +1,108 @@
+use enum_dispatch::enum_dispatch;
+
+use crate::{
+    math::{Camera as CameraCoord, Matrix, PdfSet, Point3, Ray, RayDifferential, Vec3, World},
+    sampling::Sampler,
+    spectrum::{SpectralSample, Wavelength},
+};
+
+mod perspective;
+pub use perspective::{Aperture, PerspectiveCamera};
+
+mod orthographic;
+pub use orthographic::OrthographicCamera;
+
+mod fisheye;
+pub use fisheye::FisheyeCamera;
+
+mod equirectangular;
+pub use equirectangular::EquirectangularCamera;
+
+mod realistic;
+pub use realistic::{parse_prescription, RealisticCamera};
+
+pub struct CameraRay {
+    pub ray: Ray,
+    // Falloff towards the edges of the image, e.g. vignetting, and zero for the
+    // wavelengths the ray was not traced for (e.g. through a dispersive lens)
+    pub weight: SpectralSample,
+    // Relative pdfs of generating the ray for each wavelength, which the path is
+    // weighted with
+    pub wavelength_pdfs: PdfSet,
+    // Only filled in by generate_ray_differential
+    pub differential: Option<RayDifferential>,
+}
+
+#[enum_dispatch]
+pub trait Projection {
+    // (x, y) is the position on the film in [0, 1]^2, starting from the top
+    // left. Rays that don't make it out of the camera are None
+    fn generate_ray(
+        &self,
+        film: (f32, f32),
+        wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> Option<CameraRay>;
+
+    // Also generates the rays offset by pixel_size on the film, through the
+    // same point of the lens. There is no differential when those don't make it
+    // out of the camera
+    fn generate_ray_differential(
+        &self,
+        film: (f32, f32),
+        pixel_size: (f32, f32),
+        wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> Option<CameraRay> {
+        // The offset rays replay the samples drawn for the main one
+        let offset_sampler = sampler.clone();
+        let mut camera_ray = self.generate_ray(film, wavelength, sampler)?;
+
+        let offset = |film| {
+            let ray = self.generate_ray(film, wavelength, &mut offset_sampler.clone())?;
+            Some((ray.ray.o(), ray.ray.d()))
+        };
+        let rx = offset((film.0 + pixel_size.0, film.1));
+        let ry = offset((film.0, film.1 + pixel_size.1));
+        if let (Some((rx_o, rx_d)), Some((ry_o, ry_d))) = (rx, ry) {
+            camera_ray.differential = Some(RayDifferential {
+                rx_o,
+                rx_d,
+                ry_o,
+                ry_d,
+            });
+        }
+
+        Some(camera_ray)
+    }
+}
+
+#[enum_dispatch(Projection)]
+#[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
+pub enum Camera {
+    PerspectiveCamera,
+    RealisticCamera,
+    OrthographicCamera,
+    FisheyeCamera,
+    EquirectangularCamera,
+}
+
+// Camera space has x to the right, y up and looks down +z
+pub fn look_at(position: Point3, target: Point3, up: Vec3) -> Matrix<CameraCoord, World> {
+    let forward = (target - position).normalize();
+    let mut right = up.cross(forward);
+    if right.len_squared() < 1e-8 {
+        // up is parallel to the view direction, any other up will do
+        let up = if forward.y().abs() < 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(0.0, 0.0, 1.0)
+        };
+        right = up.cross(forward);
+    }
+    let right = right.normalize();
+    let up = forward.cross(right);
+
+    Matrix::coordinate_system(right, up, forward, position)
+}
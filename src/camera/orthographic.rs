@@ -0,0 +1,84 @@
+use crate::{
+    camera::{CameraRay, Projection},
+    math::{Camera as CameraCoord, Matrix, PdfSet, Point3, Ray, Vec3, World},
+    sampling::Sampler,
+    spectrum::{SpectralSample, Wavelength},
+};
+
+// Parallel rays from a rectangle of the given size (in world units) around the
+// camera position
+#[derive(Debug, Clone)]
+pub struct OrthographicCamera {
+    camera_to_world: Matrix<CameraCoord, World>,
+    width: f32,
+    height: f32,
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        camera_to_world: Matrix<CameraCoord, World>,
+        height: f32,
+        aspect_ratio: f32,
+    ) -> Self {
+        Self {
+            camera_to_world,
+            width: height * aspect_ratio,
+            height,
+        }
+    }
+}
+
+impl Projection for OrthographicCamera {
+    fn generate_ray(
+        &self,
+        (x, y): (f32, f32),
+        _wavelength: Wavelength,
+        _sampler: &mut Sampler,
+    ) -> Option<CameraRay> {
+        let origin = Point3::new((x - 0.5) * self.width, (0.5 - y) * self.height, 0.0);
+        let dir = Vec3::new(0.0, 0.0, 1.0);
+
+        Some(CameraRay {
+            ray: Ray::new(&self.camera_to_world * origin, &self.camera_to_world * dir),
+            weight: SpectralSample::splat(1.0),
+            wavelength_pdfs: PdfSet::splat(1.0),
+            differential: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::look_at;
+
+    #[test]
+    fn test_orthographic() {
+        let camera = OrthographicCamera::new(
+            look_at(
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            ),
+            2.0,
+            2.0,
+        );
+
+        let mut sampler = Sampler::new(0, 0, 0, 0);
+        let wavelength = Wavelength::new(550.0);
+        let top_left = camera
+            .generate_ray((0.0, 0.0), wavelength, &mut sampler)
+            .unwrap()
+            .ray;
+        let bottom_right = camera
+            .generate_ray((1.0, 1.0), wavelength, &mut sampler)
+            .unwrap()
+            .ray;
+
+        // Looking down +x with y up, right is -z
+        assert!(top_left.o().distance(Point3::new(0.0, 1.0, 2.0)) < 1e-5);
+        assert!(bottom_right.o().distance(Point3::new(0.0, -1.0, -2.0)) < 1e-5);
+        assert!((top_left.d() - bottom_right.d()).len() < 1e-5);
+        assert!((top_left.d() - Vec3::new(1.0, 0.0, 0.0)).len() < 1e-5);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{
+    camera::{CameraRay, Projection},
+    math::{Camera as CameraCoord, Clip, Matrix, PdfSet, Point3, Ray, Vec3, World},
+    sampling::{self, Sampler},
+    spectrum::{SpectralSample, Wavelength},
+};
+
+#[derive(Debug, Clone)]
+pub enum Aperture {
+    Circle,
+    // Regular polygon inscribed in the lens, rotation is in degrees
+    Polygon { blades: u32, rotation: f32 },
+}
+
+impl Aperture {
+    // Uniformly distributed point on the aperture, scaled to the unit disk
+    fn sample(&self, sampler: &mut Sampler) -> (f32, f32) {
+        let (r1, r2) = (sampler.gen_0_1(), sampler.gen_0_1());
+        match *self {
+            Aperture::Circle => sampling::concentric_disk(r1, r2),
+            Aperture::Polygon { blades, rotation } => {
+                sampling::regular_polygon(r1, r2, blades, rotation.to_radians())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PerspectiveCamera {
+    camera_to_world: Matrix<CameraCoord, World>,
+    clip_to_camera: Matrix<Clip, CameraCoord>,
+    // Zero for a pinhole camera
+    lens_radius: f32,
+    focus_distance: f32,
+    aperture: Aperture,
+}
+
+impl PerspectiveCamera {
+    // A pinhole camera, fov is vertical and in degrees
+    pub fn new(camera_to_world: Matrix<CameraCoord, World>, fov: f32, aspect_ratio: f32) -> Self {
+        let camera_to_clip = Matrix::<CameraCoord, Clip>::projection(aspect_ratio, 0.1, 100.0, fov);
+
+        Self {
+            camera_to_world,
+            clip_to_camera: camera_to_clip.inverse(),
+            lens_radius: 0.0,
+            focus_distance: 1.0,
+            aperture: Aperture::Circle,
+        }
+    }
+
+    // Turns the camera into a thin lens camera, points focus_distance away along
+    // the view direction are in focus
+    pub fn with_lens(self, lens_radius: f32, focus_distance: f32, aperture: Aperture) -> Self {
+        Self {
+            lens_radius,
+            focus_distance,
+            aperture,
+            ..self
+        }
+    }
+}
+
+impl Projection for PerspectiveCamera {
+    fn generate_ray(
+        &self,
+        (x, y): (f32, f32),
+        _wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> Option<CameraRay> {
+        let film_clip = Point3::new((x - 0.5) * 2.0, (y - 0.5) * -2.0, 0.0);
+        let dir = (&self.clip_to_camera * film_clip).to_vec();
+
+        let (origin, dir) = if self.lens_radius > 0.0 {
+            // Rays through every point of the lens meet on the plane of focus
+            let focus = dir * (self.focus_distance / dir.z());
+            let (lens_x, lens_y) = self.aperture.sample(sampler);
+            let lens = Vec3::new(lens_x, lens_y, 0.0) * self.lens_radius;
+            (lens.to_point(), focus - lens)
+        } else {
+            (Point3::new(0.0, 0.0, 0.0), dir)
+        };
+
+        Some(CameraRay {
+            ray: Ray::new(&self.camera_to_world * origin, &self.camera_to_world * dir),
+            weight: SpectralSample::splat(1.0),
+            wavelength_pdfs: PdfSet::splat(1.0),
+            differential: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::look_at;
+
+    fn generate_ray(camera: &PerspectiveCamera, film: (f32, f32), sampler: &mut Sampler) -> Ray {
+        camera
+            .generate_ray(film, Wavelength::new(550.0), sampler)
+            .unwrap()
+            .ray
+    }
+
+    #[test]
+    fn test_look_at() {
+        let position = Point3::new(1.0, 2.0, 3.0);
+        let target = Point3::new(-2.0, 0.5, 4.0);
+        let camera = PerspectiveCamera::new(
+            look_at(position, target, Vec3::new(0.0, 1.0, 0.0)),
+            60.0,
+            1.5,
+        );
+
+        let mut sampler = Sampler::new(0, 0, 0, 0);
+        let center = generate_ray(&camera, (0.5, 0.5), &mut sampler);
+        let to_target = (target - position).normalize();
+        assert!((center.d() - to_target).len() < 1e-4);
+        assert!(center.o().distance(position) < 1e-4);
+
+        // The top of the image is up, and the vertical field of view is 60 degrees
+        let top = generate_ray(&camera, (0.5, 0.0), &mut sampler);
+        assert!(top.d().y() > center.d().y());
+        assert!((top.d().dot(center.d()) - 30f32.to_radians().cos()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_thin_lens_focus() {
+        let position = Point3::new(0.0, 1.0, -5.0);
+        let camera = PerspectiveCamera::new(
+            look_at(
+                position,
+                Point3::new(0.0, 1.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            ),
+            45.0,
+            1.0,
+        )
+        .with_lens(
+            0.2,
+            5.0,
+            Aperture::Polygon {
+                blades: 6,
+                rotation: 15.0,
+            },
+        );
+
+        // Every ray through the center of the film passes through the point in
+        // focus, and leaves from within the aperture
+        for i in 0..64 {
+            let mut sampler = Sampler::new(0, 0, i, 0);
+            let ray = generate_ray(&camera, (0.5, 0.5), &mut sampler);
+            assert!(ray.o().distance(position) <= 0.2 + 1e-4);
+
+            let t = (0.0 - ray.o().z()) / ray.d().z();
+            let p = ray.o() + ray.d() * t;
+            assert!(p.distance(Point3::new(0.0, 1.0, 0.0)) < 1e-3);
+        }
+    }
+}
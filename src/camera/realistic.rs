@@ -0,0 +1,599 @@
+// Traces rays through the elements of a real lens, after
+// https://pbr-book.org/3ed-2018/Camera_Models/Realistic_Cameras
+//
+// Lens space is camera space with z flipped, so that the film is at z = 0 and
+// the lens extends towards -z
+use crate::{
+    camera::{CameraRay, Projection},
+    math::{refract, Camera as CameraCoord, Matrix, PdfSet, Point3, Ray, Vec3, World},
+    sampling::Sampler,
+    spectrum::{SpectralSample, Wavelength},
+};
+
+// Fraunhofer d, F and C lines (in nm), which the index of refraction and Abbe
+// number of glasses are measured at
+const LAMBDA_D: f32 = 587.6;
+const LAMBDA_F: f32 = 486.1;
+const LAMBDA_C: f32 = 656.3;
+
+// Number of film radii the exit pupil is bounded for, and the number of rays
+// (per side) traced for each
+const EXIT_PUPIL_BOUNDS: usize = 64;
+const EXIT_PUPIL_SAMPLES: usize = 64;
+
+// A spherical interface between two media, in meters
+#[derive(Debug, Clone)]
+pub struct LensElement {
+    // Zero for the aperture stop
+    pub curvature_radius: f32,
+    // Distance to the next element, or to the film for the last one
+    pub thickness: f32,
+    // Of the medium behind the element (towards the film), 1 for air
+    pub ior: f32,
+    // None for media without dispersion
+    pub abbe: Option<f32>,
+    pub aperture_radius: f32,
+}
+
+impl LensElement {
+    pub fn is_stop(&self) -> bool {
+        self.curvature_radius == 0.0
+    }
+
+    // Cauchy's equation, fit to the index at the d line and the Abbe number
+    fn ior(&self, lambda: f32) -> f32 {
+        match self.abbe {
+            Some(abbe) => {
+                let b = (self.ior - 1.0) / abbe / (LAMBDA_F.powi(-2) - LAMBDA_C.powi(-2));
+                self.ior + b * (lambda.powi(-2) - LAMBDA_D.powi(-2))
+            }
+            None => self.ior,
+        }
+    }
+}
+
+// Prescriptions list one element per line, from the front of the lens to the
+// back: curvature radius, thickness, index of refraction and aperture diameter
+// in millimeters, optionally followed by the Abbe number of the glass. A
+// radius of zero is the aperture stop and an index of zero is air. Lines
+// starting with # are comments
+pub fn parse_prescription(source: &str) -> Result<Vec<LensElement>, String> {
+    let mut elements = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let values = line
+            .split_whitespace()
+            .map(|value| value.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("line {}: {}", i + 1, e))?;
+        if values.len() != 4 && values.len() != 5 {
+            return Err(format!(
+                "line {}: expected 4 or 5 values, got {}",
+                i + 1,
+                values.len()
+            ));
+        }
+
+        let ior = if values[2] == 0.0 { 1.0 } else { values[2] };
+        elements.push(LensElement {
+            curvature_radius: values[0] * 0.001,
+            thickness: values[1] * 0.001,
+            ior,
+            // Air doesn't disperse
+            abbe: values.get(4).copied().filter(|_| ior != 1.0),
+            aperture_radius: values[3] * 0.001 / 2.0,
+        });
+    }
+
+    if elements.is_empty() {
+        return Err("the prescription has no elements".into());
+    }
+
+    Ok(elements)
+}
+
+// Bounds on the plane of the rear element
+#[derive(Debug, Copy, Clone)]
+struct PupilBounds {
+    min: (f32, f32),
+    max: (f32, f32),
+}
+
+impl PupilBounds {
+    fn empty() -> Self {
+        Self {
+            min: (f32::INFINITY, f32::INFINITY),
+            max: (f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.min.0 > self.max.0
+    }
+
+    fn contains(&self, (x, y): (f32, f32)) -> bool {
+        x >= self.min.0 && x <= self.max.0 && y >= self.min.1 && y <= self.max.1
+    }
+
+    fn add(&mut self, (x, y): (f32, f32)) {
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+
+    fn expand(self, delta: f32) -> Self {
+        Self {
+            min: (self.min.0 - delta, self.min.1 - delta),
+            max: (self.max.0 + delta, self.max.1 + delta),
+        }
+    }
+
+    fn area(&self) -> f32 {
+        (self.max.0 - self.min.0) * (self.max.1 - self.min.1)
+    }
+
+    fn lerp(&self, (u0, u1): (f32, f32)) -> (f32, f32) {
+        (
+            lerp(u0, self.min.0, self.max.0),
+            lerp(u1, self.min.1, self.max.1),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RealisticCamera {
+    camera_to_world: Matrix<CameraCoord, World>,
+    // From the front of the lens to the back
+    elements: Vec<LensElement>,
+    film_size: (f32, f32),
+    // Indexed by the distance from the center of the film
+    exit_pupil_bounds: Vec<PupilBounds>,
+    // Whether rays have to be traced for each wavelength on their own
+    dispersive: bool,
+}
+
+impl RealisticCamera {
+    // film_diagonal is in meters, focus_distance is measured from the film
+    pub fn new(
+        camera_to_world: Matrix<CameraCoord, World>,
+        elements: Vec<LensElement>,
+        film_diagonal: f32,
+        focus_distance: f32,
+        aspect_ratio: f32,
+    ) -> Result<Self, String> {
+        let film_height = film_diagonal / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let dispersive = elements.iter().any(|element| element.abbe.is_some());
+
+        let mut camera = Self {
+            camera_to_world,
+            elements,
+            film_size: (aspect_ratio * film_height, film_height),
+            exit_pupil_bounds: Vec::new(),
+            dispersive,
+        };
+
+        let thickness = camera
+            .focus_thick_lens(focus_distance)
+            .ok_or_else(|| format!("the lens can't focus at {}m", focus_distance))?;
+        camera.elements.last_mut().unwrap().thickness = thickness;
+
+        let film_radius = film_diagonal / 2.0;
+        camera.exit_pupil_bounds = (0..EXIT_PUPIL_BOUNDS)
+            .map(|i| {
+                let r0 = i as f32 / EXIT_PUPIL_BOUNDS as f32 * film_radius;
+                let r1 = (i + 1) as f32 / EXIT_PUPIL_BOUNDS as f32 * film_radius;
+                camera.bound_exit_pupil(r0, r1)
+            })
+            .collect();
+
+        if camera.exit_pupil_bounds[0].is_empty() {
+            return Err("no light makes it through the lens".into());
+        }
+
+        Ok(camera)
+    }
+
+    fn lens_rear_z(&self) -> f32 {
+        self.elements.last().unwrap().thickness
+    }
+
+    fn lens_front_z(&self) -> f32 {
+        self.elements.iter().map(|element| element.thickness).sum()
+    }
+
+    fn film_radius(&self) -> f32 {
+        (self.film_size.0.powi(2) + self.film_size.1.powi(2)).sqrt() / 2.0
+    }
+
+    // Takes and returns rays in camera space, None if the ray is blocked
+    fn trace_from_film(
+        &self,
+        o: Point3<CameraCoord>,
+        d: Vec3<CameraCoord>,
+        lambda: f32,
+    ) -> Option<(Point3<CameraCoord>, Vec3<CameraCoord>)> {
+        let (mut o, mut d) = (flip_point(o), flip_vec(d));
+        let mut element_z = 0.0;
+
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            element_z -= element.thickness;
+
+            let (t, normal) = if element.is_stop() {
+                if d.z() >= 0.0 {
+                    return None;
+                }
+                ((element_z - o.z()) / d.z(), None)
+            } else {
+                let radius = element.curvature_radius;
+                let (t, normal) = intersect_spherical(radius, element_z + radius, o, d)?;
+                (t, Some(normal))
+            };
+
+            o += d * t;
+            if o.x().powi(2) + o.y().powi(2) > element.aperture_radius.powi(2) {
+                return None;
+            }
+
+            if let Some(normal) = normal {
+                let eta_i = element.ior(lambda);
+                let eta_t = match i {
+                    0 => 1.0,
+                    _ => self.elements[i - 1].ior(lambda),
+                };
+                d = refract((-d).normalize(), normal, eta_i / eta_t)?;
+            }
+        }
+
+        Some((flip_point(o), flip_vec(d)))
+    }
+
+    // Takes and returns rays in camera space, None if the ray is blocked
+    fn trace_from_scene(
+        &self,
+        o: Point3<CameraCoord>,
+        d: Vec3<CameraCoord>,
+        lambda: f32,
+    ) -> Option<(Point3<CameraCoord>, Vec3<CameraCoord>)> {
+        let (mut o, mut d) = (flip_point(o), flip_vec(d));
+        let mut element_z = -self.lens_front_z();
+
+        for (i, element) in self.elements.iter().enumerate() {
+            let (t, normal) = if element.is_stop() {
+                ((element_z - o.z()) / d.z(), None)
+            } else {
+                let radius = element.curvature_radius;
+                let (t, normal) = intersect_spherical(radius, element_z + radius, o, d)?;
+                (t, Some(normal))
+            };
+
+            o += d * t;
+            if o.x().powi(2) + o.y().powi(2) > element.aperture_radius.powi(2) {
+                return None;
+            }
+
+            if let Some(normal) = normal {
+                let eta_i = match i {
+                    0 => 1.0,
+                    _ => self.elements[i - 1].ior(lambda),
+                };
+                let eta_t = element.ior(lambda);
+                d = refract((-d).normalize(), normal, eta_i / eta_t)?;
+            }
+
+            element_z += element.thickness;
+        }
+
+        Some((flip_point(o), flip_vec(d)))
+    }
+
+    // The z of the principal planes and focal points on the film side, then on
+    // the scene side, found by tracing rays parallel to the axis
+    fn thick_lens_approximation(&self) -> Option<([f32; 2], [f32; 2])> {
+        let x = 0.001 * 2.0 * self.film_radius();
+
+        let scene_ray = (
+            Point3::new(x, 0.0, self.lens_front_z() + 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        );
+        let film_ray = self.trace_from_scene(scene_ray.0, scene_ray.1, LAMBDA_D)?;
+        let (pz0, fz0) = cardinal_points(scene_ray, film_ray);
+
+        let film_ray = (
+            Point3::new(x, 0.0, self.lens_rear_z() - 1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        let scene_ray = self.trace_from_film(film_ray.0, film_ray.1, LAMBDA_D)?;
+        let (pz1, fz1) = cardinal_points(film_ray, scene_ray);
+
+        Some(([pz0, pz1], [fz0, fz1]))
+    }
+
+    // Distance between the rear element and the film that brings points
+    // focus_distance away into focus
+    fn focus_thick_lens(&self, focus_distance: f32) -> Option<f32> {
+        let (pz, fz) = self.thick_lens_approximation()?;
+
+        let f = fz[0] - pz[0];
+        let z = -focus_distance;
+        let c = (pz[1] - z - pz[0]) * (pz[1] - z - 4.0 * f - pz[0]);
+        if c < 0.0 {
+            return None;
+        }
+
+        let delta = 0.5 * (pz[1] - z + pz[0] - c.sqrt());
+        let thickness = self.lens_rear_z() + delta;
+        if thickness > 0.0 {
+            Some(thickness)
+        } else {
+            None
+        }
+    }
+
+    // Bounds of the points on the rear element that rays from the film
+    // between r0 and r1 along the x axis make it through the lens from
+    fn bound_exit_pupil(&self, r0: f32, r1: f32) -> PupilBounds {
+        let rear_radius = 1.5 * self.elements.last().unwrap().aperture_radius;
+        let rear = PupilBounds {
+            min: (-rear_radius, -rear_radius),
+            max: (rear_radius, rear_radius),
+        };
+
+        let num_samples = EXIT_PUPIL_SAMPLES * EXIT_PUPIL_SAMPLES;
+        let mut bounds = PupilBounds::empty();
+
+        for i in 0..num_samples {
+            let t = (i as f32 + 0.5) / num_samples as f32;
+            let film = Point3::new(lerp(t, r0, r1), 0.0, 0.0);
+            let (x, y) = rear.lerp((radical_inverse(2, i), radical_inverse(3, i)));
+            let on_rear = Point3::new(x, y, self.lens_rear_z());
+
+            if bounds.contains((x, y))
+                || self
+                    .trace_from_film(film, on_rear - film, LAMBDA_D)
+                    .is_some()
+            {
+                bounds.add((x, y));
+            }
+        }
+
+        if bounds.is_empty() {
+            return bounds;
+        }
+
+        // Samples are spaced this far apart, pad the bounds so that they aren't
+        // cut short
+        let spacing = 2.0 * 2f32.sqrt() * 2.0 * rear_radius / EXIT_PUPIL_SAMPLES as f32;
+        bounds.expand(spacing)
+    }
+
+    // Uniformly distributed point on the exit pupil for the film point, and the
+    // area it was sampled from
+    fn sample_exit_pupil(&self, (x, y): (f32, f32), u: (f32, f32)) -> (Point3<CameraCoord>, f32) {
+        let r = (x * x + y * y).sqrt();
+        let index = ((r / self.film_radius() * EXIT_PUPIL_BOUNDS as f32) as usize)
+            .min(EXIT_PUPIL_BOUNDS - 1);
+        let bounds = &self.exit_pupil_bounds[index];
+
+        // The bounds are for points along the x axis, rotate them to the film
+        // point
+        let (px, py) = bounds.lerp(u);
+        let (sin, cos) = if r > 0.0 { (y / r, x / r) } else { (0.0, 1.0) };
+        let on_rear = Point3::new(cos * px - sin * py, sin * px + cos * py, self.lens_rear_z());
+
+        (on_rear, bounds.area())
+    }
+}
+
+impl Projection for RealisticCamera {
+    fn generate_ray(
+        &self,
+        (x, y): (f32, f32),
+        wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> Option<CameraRay> {
+        // The image on the film is upside down
+        let film = Point3::new(
+            -(x - 0.5) * self.film_size.0,
+            (y - 0.5) * self.film_size.1,
+            0.0,
+        );
+
+        let u = (sampler.gen_0_1(), sampler.gen_0_1());
+        let (on_rear, bounds_area) = self.sample_exit_pupil((film.x(), film.y()), u);
+        if bounds_area == 0.0 {
+            return None;
+        }
+
+        // Only the hero wavelength follows the ray through a dispersive lens
+        let (lambda, lanes, wavelength_pdfs) = if self.dispersive {
+            (
+                wavelength.hero(),
+                SpectralSample::new(1.0, 0.0, 0.0, 0.0),
+                PdfSet::new(1.0, 0.0, 0.0, 0.0),
+            )
+        } else {
+            (LAMBDA_D, SpectralSample::splat(1.0), PdfSet::splat(1.0))
+        };
+
+        let to_rear = on_rear - film;
+        let (o, d) = self.trace_from_film(film, to_rear, lambda)?;
+
+        // Natural vignetting, relative to the center of the film
+        let cos_theta = to_rear.normalize().z();
+        let falloff = cos_theta.powi(4) * bounds_area / self.exit_pupil_bounds[0].area();
+
+        Some(CameraRay {
+            ray: Ray::new(&self.camera_to_world * o, &self.camera_to_world * d),
+            weight: lanes * falloff,
+            wavelength_pdfs,
+            differential: None,
+        })
+    }
+}
+
+// Returns the distance along the ray to the sphere, and the normal facing the
+// ray
+fn intersect_spherical(
+    radius: f32,
+    z_center: f32,
+    o: Point3<CameraCoord>,
+    d: Vec3<CameraCoord>,
+) -> Option<(f32, Vec3<CameraCoord>)> {
+    let oc = o.to_vec() - Vec3::new(0.0, 0.0, z_center);
+
+    let a = d.len_squared();
+    let b = 2.0 * d.dot(oc);
+    let c = oc.len_squared() - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let q = if b < 0.0 {
+        -0.5 * (b - discriminant.sqrt())
+    } else {
+        -0.5 * (b + discriminant.sqrt())
+    };
+    let (t0, t1) = (q / a, c / q);
+    let (t0, t1) = (t0.min(t1), t0.max(t1));
+
+    // Convex and concave elements are hit on opposite sides of the sphere
+    let use_closer = (d.z() > 0.0) ^ (radius < 0.0);
+    let t = if use_closer { t0 } else { t1 };
+    if t < 0.0 {
+        return None;
+    }
+
+    let normal = (oc + d * t).normalize().face_forward(-d);
+    Some((t, normal))
+}
+
+// The z (in lens space) of the principal plane and focal point, from a ray
+// parallel to the axis and the same ray after it went through the lens
+fn cardinal_points(
+    (in_o, _): (Point3<CameraCoord>, Vec3<CameraCoord>),
+    (out_o, out_d): (Point3<CameraCoord>, Vec3<CameraCoord>),
+) -> (f32, f32) {
+    let tf = -out_o.x() / out_d.x();
+    let fz = -(out_o + out_d * tf).z();
+    let tp = (in_o.x() - out_o.x()) / out_d.x();
+    let pz = -(out_o + out_d * tp).z();
+    (pz, fz)
+}
+
+fn flip_point(p: Point3<CameraCoord>) -> Point3<CameraCoord> {
+    Point3::new(p.x(), p.y(), -p.z())
+}
+
+fn flip_vec(v: Vec3<CameraCoord>) -> Vec3<CameraCoord> {
+    Vec3::new(v.x(), v.y(), -v.z())
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    (1.0 - t) * a + t * b
+}
+
+fn radical_inverse(base: usize, mut i: usize) -> f32 {
+    let inv_base = 1.0 / base as f32;
+    let mut inv_base_n = 1.0;
+    let mut reversed = 0;
+
+    while i > 0 {
+        reversed = reversed * base + i % base;
+        inv_base_n *= inv_base;
+        i /= base;
+    }
+
+    (reversed as f32 * inv_base_n).min(1.0 - f32::EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::look_at;
+
+    const DGAUSS: &str = include_str!("../../scenes/lenses/dgauss.50mm.dat");
+
+    fn dgauss(focus_distance: f32, dispersive: bool) -> RealisticCamera {
+        let mut elements = parse_prescription(DGAUSS).unwrap();
+        if !dispersive {
+            for element in &mut elements {
+                element.abbe = None;
+            }
+        }
+
+        let camera_to_world = look_at(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        RealisticCamera::new(camera_to_world, elements, 0.035, focus_distance, 1.0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_prescription() {
+        let elements = parse_prescription(DGAUSS).unwrap();
+        assert_eq!(elements.len(), 11);
+        assert_eq!(elements.iter().filter(|e| e.is_stop()).count(), 1);
+        assert!((elements[0].curvature_radius - 0.029475).abs() < 1e-6);
+        assert_eq!(elements[1].ior, 1.0);
+
+        assert!(parse_prescription("# nothing\n").is_err());
+        assert!(parse_prescription("1 2 3\n").is_err());
+        assert!(parse_prescription("1 2 x 4\n").is_err());
+    }
+
+    #[test]
+    fn test_focus() {
+        // Rays from the center of the film converge on the axis at the focus
+        // distance, up to spherical aberration
+        let camera = dgauss(2.0, false);
+        let mut traced = 0;
+        for i in 0..64 {
+            let mut sampler = Sampler::new(0, 0, i, 0);
+            let ray = match camera.generate_ray((0.5, 0.5), Wavelength::new(550.0), &mut sampler) {
+                Some(camera_ray) => camera_ray.ray,
+                None => continue,
+            };
+            traced += 1;
+
+            let t = (2.0 - ray.o().z()) / ray.d().z();
+            let p = ray.o() + ray.d() * t;
+            assert!((p.x().powi(2) + p.y().powi(2)).sqrt() < 0.01);
+        }
+        assert!(traced > 32);
+
+        // The top of the image is up, and the edges are darker
+        let mut sampler = Sampler::new(0, 0, 0, 0);
+        let wavelength = Wavelength::new(550.0);
+        let top = (0..16)
+            .find_map(|_| camera.generate_ray((0.5, 0.1), wavelength, &mut sampler))
+            .unwrap();
+        assert!(top.ray.d().y() > 0.0);
+        assert!(top.weight.hero() < 1.0);
+    }
+
+    #[test]
+    fn test_dispersion() {
+        let camera = dgauss(2.0, true);
+        assert!(camera.dispersive);
+
+        let mut directions = [400.0, 700.0].iter().map(|&lambda| {
+            let mut sampler = Sampler::new(0, 0, 0, 0);
+            let camera_ray = camera
+                .generate_ray((0.9, 0.5), Wavelength::new(lambda), &mut sampler)
+                .unwrap();
+            assert_eq!(camera_ray.wavelength_pdfs.hero(), 1.0);
+            assert_eq!(camera_ray.wavelength_pdfs.y(), 0.0);
+            camera_ray.ray.d()
+        });
+
+        // Lateral chromatic aberration
+        let (blue, red) = (directions.next().unwrap(), directions.next().unwrap());
+        assert!((blue - red).len() > 1e-5);
+    }
+}
@@ -0,0 +1,127 @@
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    Parser,
+};
+
+use crate::{filter::Filter, integrator::IntegratorType};
+
+pub const DEFAULT_SEED: u32 = 123_456_789;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Spectral CPU path tracer")]
+pub struct Args {
+    /// Scene description file (RON)
+    #[arg(default_value = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/default.ron"))]
+    pub scene: PathBuf,
+
+    /// Image width in pixels, overrides the scene file
+    #[arg(long)]
+    pub width: Option<NonZeroUsize>,
+
+    /// Image height in pixels, overrides the scene file
+    #[arg(long)]
+    pub height: Option<NonZeroUsize>,
+
+    /// Samples per pixel, overrides the scene file
+    #[arg(long)]
+    pub spp: Option<usize>,
+
+    /// Light transport integrator. Pass several (comma separated) to render an A/B comparison,
+    /// writing one image per integrator
+    #[arg(
+        short,
+        long = "integrator",
+        value_name = "INTEGRATOR",
+        value_delimiter = ',',
+        default_value = "hwss-naive",
+        value_parser = PossibleValuesParser::new(IntegratorType::NAMES)
+            .try_map(|s| s.parse::<IntegratorType>()),
+    )]
+    pub integrators: Vec<IntegratorType>,
+
+    /// Pixel reconstruction filter with its default parameters, overrides the
+    /// scene file
+    #[arg(
+        long,
+        value_parser = PossibleValuesParser::new(Filter::NAMES).try_map(|s| s.parse::<Filter>()),
+    )]
+    pub filter: Option<Filter>,
+
+    /// Turns on adaptive sampling: pixels stop taking samples once the relative
+    /// standard error of their mean is below this, --spp becomes the maximum
+    #[arg(long)]
+    pub error_threshold: Option<f32>,
+
+    /// Turns on adaptive sampling and stops refining the image after this many
+    /// seconds
+    #[arg(long, value_name = "SECONDS")]
+    pub time_limit: Option<f32>,
+
+    /// Number of render threads [default: $NTHREADS or the number of CPUs]
+    #[arg(short = 'j', long)]
+    pub threads: Option<NonZeroUsize>,
+
+    /// Seed used to scramble the sample sequences
+    #[arg(long, default_value_t = DEFAULT_SEED)]
+    pub seed: u32,
+
+    /// Output image path (EXR)
+    #[arg(short, long, default_value = "out.exr")]
+    pub output: PathBuf,
+
+    /// Also writes a spectral EXR next to the output (out.spectral.exr), with
+    /// this many wavelength bins between 360 and 830 nm
+    #[arg(long, value_name = "BINS")]
+    pub spectral_bins: Option<NonZeroUsize>,
+}
+
+impl Args {
+    // NTHREADS=0 is ignored like any other invalid value
+    pub fn num_threads(&self) -> usize {
+        self.threads
+            .or_else(|| {
+                std::env::var("NTHREADS")
+                    .ok()
+                    .and_then(|s| s.parse::<NonZeroUsize>().ok())
+            })
+            .map_or_else(num_cpus::get, usize::from)
+    }
+
+    // When comparing integrators, out.exr becomes out-hwss-naive.exr, out-swss-naive.exr, ...
+    pub fn output_path(&self, integrator: &IntegratorType) -> PathBuf {
+        if self.integrators.len() <= 1 {
+            return self.output.clone();
+        }
+
+        let stem = self
+            .output
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut file_name = format!("{}-{}", stem, integrator.name());
+        if let Some(extension) = self.output.extension() {
+            file_name.push('.');
+            file_name.push_str(&extension.to_string_lossy());
+        }
+
+        self.output.with_file_name(file_name)
+    }
+
+    // out.exr becomes out.spectral.exr
+    pub fn spectral_output_path(&self, integrator: &IntegratorType) -> PathBuf {
+        let output = self.output_path(integrator);
+        let mut file_name = output
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        file_name.push_str(".spectral");
+        if let Some(extension) = output.extension() {
+            file_name.push('.');
+            file_name.push_str(&extension.to_string_lossy());
+        }
+
+        output.with_file_name(file_name)
+    }
+}
@@ -1,6 +1,9 @@
 #![allow(clippy::excessive_precision, clippy::unreadable_literal)]
 
-use crate::spectrum::wavelength::{LAMBDA_MAX_NM, LAMBDA_MIN_NM};
+use crate::spectrum::{
+    wavelength::{LAMBDA_MAX_NM, LAMBDA_MIN_NM},
+    SampleableSpectrum,
+};
 
 const CIE_SAMPLES: usize = 830 - 360 + 1;
 const CIE_Y_INTEGRAL: f32 = 116.661843131358;
@@ -33,6 +36,21 @@ impl Xyz {
         }
     }
 
+    // The color of a whole spectrum, integrated at the 1 nm steps of the CIE
+    // tables. ConstantSpectrum::new(1.0) has a luminance (y) of 1
+    pub fn from_spectrum<S: SampleableSpectrum>(spectrum: &S) -> Self {
+        let mut xyz = Self::new(0.0, 0.0, 0.0);
+        for i in 0..CIE_SAMPLES {
+            let lambda = LAMBDA_MIN_NM + i as f32;
+            xyz += Self::from_wavelength(lambda, spectrum.evaluate_single(lambda));
+        }
+        xyz / (LAMBDA_MAX_NM - LAMBDA_MIN_NM)
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+
     pub fn to_rgb_hdr(self) -> (f32, f32, f32) {
         let r = 3.240479 * self.x - 1.537150 * self.y - 0.498535 * self.z;
         let g = -0.969256 * self.x + 1.875991 * self.y + 0.041556 * self.z;
@@ -0,0 +1,325 @@
+use std::sync::RwLock;
+
+use crate::{
+    color::Xyz,
+    filter::{Filter, ReconstructionFilter},
+    spectrum::{
+        wavelength::{LAMBDA_MIN_NM, LAMBDA_RANGE_NM},
+        SpectralSample,
+        Wavelength,
+    },
+};
+
+#[derive(Debug, Copy, Clone)]
+pub struct FilmPixel {
+    // Sum of the filter weighted samples
+    pub xyz_sum: Xyz,
+    pub weight_sum: f32,
+}
+
+impl Default for FilmPixel {
+    fn default() -> Self {
+        Self {
+            xyz_sum: Xyz::new(0.0, 0.0, 0.0),
+            weight_sum: 0.0,
+        }
+    }
+}
+
+impl FilmPixel {
+    pub fn resolve(&self) -> Xyz {
+        if self.weight_sum == 0.0 {
+            Xyz::new(0.0, 0.0, 0.0)
+        } else {
+            self.xyz_sum * (1.0 / self.weight_sum)
+        }
+    }
+}
+
+// A camera sample's contribution to the film, its color and, when the film
+// keeps spectra, the bin and value of each of its wavelengths
+#[derive(Debug, Copy, Clone)]
+pub struct FilmSample {
+    pub xyz: Xyz,
+    bins: [(usize, f32); 4],
+}
+
+impl From<Xyz> for FilmSample {
+    fn from(xyz: Xyz) -> Self {
+        Self {
+            xyz,
+            bins: [(0, 0.0); 4],
+        }
+    }
+}
+
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    pixels: RwLock<Vec<FilmPixel>>,
+    // Equal wavelength bins between LAMBDA_MIN_NM and LAMBDA_MAX_NM, none
+    // unless spectral output was asked for
+    spectral_bins: usize,
+    // Filter weighted sums of spectral_bins values per pixel, divided by the
+    // weight sums of pixels
+    spectra: RwLock<Vec<f32>>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: RwLock::new(vec![FilmPixel::default(); width * height]),
+            spectral_bins: 0,
+            spectra: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn with_spectral_bins(self, bins: usize) -> Self {
+        Self {
+            spectral_bins: bins,
+            spectra: RwLock::new(vec![0.0; self.width * self.height * bins]),
+            ..self
+        }
+    }
+
+    pub fn spectral_bins(&self) -> usize {
+        self.spectral_bins
+    }
+
+    // Center wavelength of each bin in nm
+    pub fn bin_wavelengths(&self) -> Vec<f32> {
+        let width = LAMBDA_RANGE_NM / self.spectral_bins as f32;
+        (0..self.spectral_bins)
+            .map(|i| LAMBDA_MIN_NM + (i as f32 + 0.5) * width)
+            .collect()
+    }
+
+    pub fn sample(&self, radiance: SpectralSample, wavelength: Wavelength) -> FilmSample {
+        let mut sample = FilmSample::from(radiance.to_xyz(wavelength));
+        if self.spectral_bins == 0 {
+            return sample;
+        }
+
+        let lambdas = [
+            wavelength.x(),
+            wavelength.y(),
+            wavelength.z(),
+            wavelength.w(),
+        ];
+        let values = [radiance.x(), radiance.y(), radiance.z(), radiance.w()];
+        for (bin, (&lambda, &value)) in sample.bins.iter_mut().zip(lambdas.iter().zip(&values)) {
+            let t = (lambda - LAMBDA_MIN_NM) / LAMBDA_RANGE_NM;
+            let i = ((t * self.spectral_bins as f32) as usize).min(self.spectral_bins - 1);
+            // Like Xyz::from_wavelength with a box matching function the width of
+            // the bin, so that bins hold the mean radiance over their wavelengths
+            *bin = (i, value * self.spectral_bins as f32);
+        }
+        sample
+    }
+
+    // A block covering the given pixels, padded by the filter radius so that
+    // samples near the edges also reach the neighbouring pixels
+    pub fn block(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        filter: &Filter,
+    ) -> FilmBlock {
+        let pad = (filter.radius() - 0.5).ceil().max(0.0) as usize;
+        let x0 = x.saturating_sub(pad);
+        let y0 = y.saturating_sub(pad);
+        let x1 = (x + width + pad).min(self.width);
+        let y1 = (y + height + pad).min(self.height);
+
+        FilmBlock {
+            x0,
+            y0,
+            width: x1 - x0,
+            height: y1 - y0,
+            pixels: vec![FilmPixel::default(); (x1 - x0) * (y1 - y0)],
+            spectral_bins: self.spectral_bins,
+            spectra: vec![0.0; (x1 - x0) * (y1 - y0) * self.spectral_bins],
+        }
+    }
+
+    pub fn add_block(&self, block: &FilmBlock) {
+        let mut pixels = self.pixels.write().unwrap();
+        let mut spectra = self.spectra.write().unwrap();
+        let bins = self.spectral_bins;
+
+        for (i, row) in block.pixels.chunks_exact(block.width).enumerate() {
+            let abs = (block.y0 + i) * self.width + block.x0;
+            for (pixel, block_pixel) in pixels[abs..(abs + block.width)].iter_mut().zip(row) {
+                pixel.xyz_sum += block_pixel.xyz_sum;
+                pixel.weight_sum += block_pixel.weight_sum;
+            }
+
+            let row_spectra = &block.spectra[i * block.width * bins..(i + 1) * block.width * bins];
+            for (sum, &value) in spectra[abs * bins..(abs + block.width) * bins]
+                .iter_mut()
+                .zip(row_spectra)
+            {
+                *sum += value;
+            }
+        }
+    }
+
+    pub fn to_xyz(&self) -> Vec<Xyz> {
+        let pixels = self.pixels.read().unwrap();
+        pixels.iter().map(FilmPixel::resolve).collect()
+    }
+
+    // spectral_bins values per pixel, row by row
+    pub fn to_spectra(&self) -> Vec<f32> {
+        let pixels = self.pixels.read().unwrap();
+        let spectra = self.spectra.read().unwrap();
+        let bins = self.spectral_bins;
+
+        if bins == 0 {
+            return Vec::new();
+        }
+
+        let mut resolved = spectra.clone();
+        for (pixel, spectrum) in pixels.iter().zip(resolved.chunks_exact_mut(bins)) {
+            for value in spectrum {
+                *value = if pixel.weight_sum == 0.0 {
+                    0.0
+                } else {
+                    *value / pixel.weight_sum
+                };
+            }
+        }
+        resolved
+    }
+}
+
+pub struct FilmBlock {
+    x0: usize,
+    y0: usize,
+    width: usize,
+    height: usize,
+    pixels: Vec<FilmPixel>,
+    spectral_bins: usize,
+    spectra: Vec<f32>,
+}
+
+impl FilmBlock {
+    // For samples that only count towards the pixel they were taken in
+    pub fn add_pixel_sample(&mut self, (x, y): (usize, usize), sample: FilmSample, weight: f32) {
+        self.accumulate((y - self.y0) * self.width + (x - self.x0), sample, weight);
+    }
+
+    // pos is in continuous pixel coordinates, pixel (x, y) has its center at
+    // (x + 0.5, y + 0.5)
+    pub fn add_sample(&mut self, (px, py): (f32, f32), sample: FilmSample, filter: &Filter) {
+        let radius = filter.radius();
+
+        // Pixels whose center lies within the radius, clamped to the block
+        let x_min = ((px - 0.5 - radius).ceil().max(0.0) as usize).max(self.x0);
+        let y_min = ((py - 0.5 - radius).ceil().max(0.0) as usize).max(self.y0);
+        let x_max = ((px - 0.5 + radius).floor().max(0.0) as usize).min(self.x0 + self.width - 1);
+        let y_max = ((py - 0.5 + radius).floor().max(0.0) as usize).min(self.y0 + self.height - 1);
+
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                let weight = filter.evaluate(x as f32 + 0.5 - px, y as f32 + 0.5 - py);
+                if weight == 0.0 {
+                    continue;
+                }
+
+                self.accumulate((y - self.y0) * self.width + (x - self.x0), sample, weight);
+            }
+        }
+    }
+
+    fn accumulate(&mut self, index: usize, sample: FilmSample, weight: f32) {
+        let pixel = &mut self.pixels[index];
+        pixel.xyz_sum += sample.xyz * weight;
+        pixel.weight_sum += weight;
+
+        if self.spectral_bins > 0 {
+            let spectrum = &mut self.spectra[index * self.spectral_bins..];
+            for &(bin, value) in &sample.bins {
+                spectrum[bin] += value * weight;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splat_across_blocks() {
+        let film = Film::new(8, 4);
+
+        for name in Filter::NAMES {
+            let filter = name.parse::<Filter>().unwrap();
+
+            // Two blocks side by side, a constant image must stay constant across the
+            // border
+            for x in [0, 4] {
+                let mut block = film.block(x, 0, 4, 4, &filter);
+                for py in 0..4 {
+                    for px in x..x + 4 {
+                        for (jx, jy) in [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)] {
+                            let pos = (px as f32 + jx, py as f32 + jy);
+                            block.add_sample(pos, Xyz::new(1.0, 2.0, 3.0).into(), &filter);
+                        }
+                    }
+                }
+                film.add_block(&block);
+            }
+
+            for xyz in film.to_xyz() {
+                let (r, g, b) = xyz.to_rgb_hdr();
+                let (er, eg, eb) = Xyz::new(1.0, 2.0, 3.0).to_rgb_hdr();
+                assert!((r - er).abs() < 1e-3 && (g - eg).abs() < 1e-3 && (b - eb).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_box_matches_average() {
+        let film = Film::new(2, 1);
+        let filter = "box".parse::<Filter>().unwrap();
+
+        let mut block = film.block(0, 0, 2, 1, &filter);
+        block.add_sample((0.2, 0.5), Xyz::new(1.0, 1.0, 1.0).into(), &filter);
+        block.add_sample((0.9, 0.5), Xyz::new(3.0, 3.0, 3.0).into(), &filter);
+        block.add_sample((1.5, 0.5), Xyz::new(5.0, 5.0, 5.0).into(), &filter);
+        film.add_block(&block);
+
+        let xyz = film.to_xyz();
+        assert_eq!(xyz[0].to_rgb_hdr(), Xyz::new(2.0, 2.0, 2.0).to_rgb_hdr());
+        assert_eq!(xyz[1].to_rgb_hdr(), Xyz::new(5.0, 5.0, 5.0).to_rgb_hdr());
+    }
+
+    #[test]
+    fn test_spectral_bins() {
+        // The hero wavelengths are a quarter of the range apart, one per bin
+        let film = Film::new(2, 1).with_spectral_bins(4);
+        assert_eq!(
+            film.bin_wavelengths()[0],
+            LAMBDA_MIN_NM + LAMBDA_RANGE_NM / 8.0
+        );
+
+        let filter = "box".parse::<Filter>().unwrap();
+        let mut block = film.block(0, 0, 2, 1, &filter);
+        let radiance = SpectralSample::new(0.25, 0.5, 0.75, 1.0);
+        let sample = film.sample(radiance, Wavelength::new(LAMBDA_MIN_NM + 1.0));
+        block.add_pixel_sample((1, 0), sample, 2.0);
+        film.add_block(&block);
+
+        // Bins hold the mean radiance over their wavelengths
+        assert_eq!(
+            film.to_spectra(),
+            vec![0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 3.0, 4.0]
+        );
+    }
+}
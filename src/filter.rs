@@ -0,0 +1,328 @@
+// Pixel reconstruction filters, evaluated at offsets (in pixels) from a pixel
+// center. Each sample is splatted into every pixel within the filter radius,
+// and pixels are normalized by the sum of the weights they received
+use std::f32::consts::PI;
+
+use enum_dispatch::enum_dispatch;
+
+use crate::sampling::distribution::Distribution1D;
+
+const FILTER_SAMPLER_RESOLUTION: usize = 64;
+
+#[enum_dispatch]
+pub trait ReconstructionFilter {
+    fn radius(&self) -> f32;
+
+    fn evaluate_1d(&self, x: f32) -> f32;
+
+    // All the filters are separable
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BoxFilter {
+    pub radius: f32,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Default for BoxFilter {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+impl ReconstructionFilter for BoxFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        if x.abs() <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TentFilter {
+    pub radius: f32,
+}
+
+impl TentFilter {
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Default for TentFilter {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl ReconstructionFilter for TentFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        (self.radius - x.abs()).max(0.0)
+    }
+}
+
+// Shifted down so that it reaches zero at the radius
+#[derive(Debug, Clone)]
+pub struct GaussianFilter {
+    pub radius: f32,
+    pub sigma: f32,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f32, sigma: f32) -> Self {
+        Self { radius, sigma }
+    }
+
+    fn gaussian(&self, x: f32) -> f32 {
+        (-x * x / (2.0 * self.sigma * self.sigma)).exp()
+    }
+}
+
+impl Default for GaussianFilter {
+    fn default() -> Self {
+        Self::new(1.5, 0.5)
+    }
+}
+
+impl ReconstructionFilter for GaussianFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        (self.gaussian(x) - self.gaussian(self.radius)).max(0.0)
+    }
+}
+
+// Mitchell-Netravali cubic, has negative lobes for most (b, c)
+#[derive(Debug, Clone)]
+pub struct MitchellFilter {
+    pub radius: f32,
+    pub b: f32,
+    pub c: f32,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f32, b: f32, c: f32) -> Self {
+        Self { radius, b, c }
+    }
+}
+
+impl Default for MitchellFilter {
+    fn default() -> Self {
+        Self::new(2.0, 1.0 / 3.0, 1.0 / 3.0)
+    }
+}
+
+impl ReconstructionFilter for MitchellFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        // The cubic is defined over [-2, 2]
+        let x = (2.0 * x / self.radius).abs();
+        let (b, c) = (self.b, self.c);
+
+        let value = if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            (-b - 6.0 * c) * x.powi(3)
+                + (6.0 * b + 30.0 * c) * x.powi(2)
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)
+        } else {
+            (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                + (6.0 - 2.0 * b)
+        };
+
+        value / 6.0
+    }
+}
+
+// Four term Blackman-Harris window spanning the filter diameter
+#[derive(Debug, Clone)]
+pub struct BlackmanHarrisFilter {
+    pub radius: f32,
+}
+
+impl BlackmanHarrisFilter {
+    pub fn new(radius: f32) -> Self {
+        Self { radius }
+    }
+}
+
+impl Default for BlackmanHarrisFilter {
+    fn default() -> Self {
+        Self::new(2.0)
+    }
+}
+
+impl ReconstructionFilter for BlackmanHarrisFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        if x.abs() > self.radius {
+            return 0.0;
+        }
+
+        let n = 2.0 * PI * (x + self.radius) / (2.0 * self.radius);
+        0.35875 - 0.48829 * n.cos() + 0.14128 * (2.0 * n).cos() - 0.01168 * (3.0 * n).cos()
+    }
+}
+
+#[enum_dispatch(ReconstructionFilter)]
+#[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
+pub enum Filter {
+    BoxFilter,
+    TentFilter,
+    GaussianFilter,
+    MitchellFilter,
+    BlackmanHarrisFilter,
+}
+
+impl Filter {
+    pub const NAMES: &'static [&'static str] =
+        &["box", "tent", "gaussian", "mitchell", "blackman-harris"];
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        GaussianFilter::default().into()
+    }
+}
+
+// Parses a filter name, with default parameters
+impl std::str::FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "box" => Ok(BoxFilter::default().into()),
+            "tent" => Ok(TentFilter::default().into()),
+            "gaussian" => Ok(GaussianFilter::default().into()),
+            "mitchell" => Ok(MitchellFilter::default().into()),
+            "blackman-harris" => Ok(BlackmanHarrisFilter::default().into()),
+            _ => Err(format!(
+                "unknown filter '{}', expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+// Samples offsets from a pixel center proportionally to the magnitude of the
+// filter, so that samples can stay in their own pixel instead of being
+// splatted. The weights are the filter value over the pdf, which is negative
+// in the lobes of filters like Mitchell's
+#[derive(Debug, Clone)]
+pub struct FilterSampler {
+    filter: Filter,
+    distribution: Distribution1D,
+}
+
+impl FilterSampler {
+    pub fn new(filter: Filter) -> Self {
+        let radius = filter.radius();
+        let func = (0..FILTER_SAMPLER_RESOLUTION)
+            .map(|i| {
+                let t = (i as f32 + 0.5) / FILTER_SAMPLER_RESOLUTION as f32;
+                filter.evaluate_1d((2.0 * t - 1.0) * radius).abs()
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            filter,
+            distribution: Distribution1D::new(&func),
+        }
+    }
+
+    // Returns the offset from the pixel center and the weight of the sample
+    pub fn sample(&self, u0: f32, u1: f32) -> ((f32, f32), f32) {
+        let (x, pdf_x) = self.sample_1d(u0);
+        let (y, pdf_y) = self.sample_1d(u1);
+        ((x, y), self.filter.evaluate(x, y) / (pdf_x * pdf_y))
+    }
+
+    fn sample_1d(&self, u: f32) -> (f32, f32) {
+        let radius = self.filter.radius();
+        let (t, pdf, _) = self.distribution.sample_continuous(u);
+        ((2.0 * t - 1.0) * radius, pdf / (2.0 * radius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        for name in Filter::NAMES {
+            let filter = name.parse::<Filter>().unwrap();
+            let r = filter.radius();
+
+            assert!(filter.evaluate(0.0, 0.0) > 0.0, "{}", name);
+            assert!(filter.evaluate(r + 0.01, 0.0).abs() < 1e-6, "{}", name);
+            assert!(filter.evaluate(0.0, -r - 0.01).abs() < 1e-6, "{}", name);
+            assert!(
+                filter.evaluate(0.3, 0.0) <= filter.evaluate(0.0, 0.0),
+                "{}",
+                name
+            );
+            let symmetric = filter.evaluate(0.2, -0.7) - filter.evaluate(-0.2, 0.7);
+            assert!(symmetric.abs() < 1e-6, "{}", name);
+        }
+
+        // Mitchell's negative lobe
+        let mitchell = "mitchell".parse::<Filter>().unwrap();
+        assert!(mitchell.evaluate_1d(1.5) < 0.0);
+    }
+
+    #[test]
+    fn test_filter_sampler() {
+        const N: usize = 64;
+
+        let sampler = FilterSampler::new("box".parse::<Filter>().unwrap());
+        for i in 0..N {
+            let u = (i as f32 + 0.5) / N as f32;
+            let ((x, y), weight) = sampler.sample(u, 1.0 - u);
+            assert!(x.abs() <= 0.5 && y.abs() <= 0.5);
+            assert!((weight - 1.0).abs() < 1e-4);
+        }
+
+        // The average weight is the integral of the filter, 1 for the tent
+        let sampler = FilterSampler::new("tent".parse::<Filter>().unwrap());
+        let mut sum = 0.0;
+        for i in 0..N {
+            for j in 0..N {
+                let u0 = (i as f32 + 0.5) / N as f32;
+                let u1 = (j as f32 + 0.5) / N as f32;
+                sum += sampler.sample(u0, u1).1;
+            }
+        }
+        assert!((sum / (N * N) as f32 - 1.0).abs() < 1e-2);
+    }
+}
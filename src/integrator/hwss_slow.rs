@@ -1,21 +1,23 @@
 #[allow(unused)]
 use crate::{
-    bsdf::{Bsdf, FresnelBsdf, LambertianBsdf, MicrofacetBsdf, SampleableBsdf, SpecularBsdf},
+    bsdf::{Bsdf, DielectricBsdf, LambertianBsdf, MicrofacetBsdf, SampleableBsdf, SpecularBsdf},
     integrator::Integrator,
-    math::Ray,
     math::{PdfSet, Point3, Shading, Vec3},
+    math::{Ray, RayDifferential},
     sampling::Sampler,
     sampling::{self, mis},
     scene::Scene,
     shape::{Geometry, Intersection, Primitive, Shape, Sphere},
     spectrum::{upsample::UpsampleTable, ConstantSpectrum, Spectrum, UpsampledHdrSpectrum},
     spectrum::{SampleableSpectrum, SpectralSample, Wavelength},
+    texture::TextureContext,
     types::PrimIndex,
 };
 
 const MAX_DEPTH: u32 = 15;
 const MIN_DEPTH: u32 = 2;
 
+#[derive(Debug, Clone)]
 pub struct HwssSlow;
 
 impl Default for HwssSlow {
@@ -30,17 +32,24 @@ impl Integrator for HwssSlow {
         &self,
         scene: &Scene,
         mut ray: Ray,
+        mut differential: Option<RayDifferential>,
         wavelength: Wavelength,
+        camera_pdfs: PdfSet,
         sampler: &mut Sampler,
     ) -> SpectralSample {
         let mut radiance = SpectralSample::splat(0.0);
         let mut throughput = SpectralSample::splat(1.0);
-        let mut path_pdfs = PdfSet::splat(1.0);
+        let mut path_pdfs = camera_pdfs;
 
         for bounces in 0..MAX_DEPTH {
             let (prim, hit) = match scene.intersection(&ray) {
                 Some(ph) => ph,
-                None => break,
+                None => {
+                    radiance += throughput
+                        * scene.background_emission(&ray, wavelength)
+                        * mis::balance_heuristic_1(path_pdfs);
+                    break;
+                }
             };
 
             // Accumulate emission
@@ -54,9 +63,11 @@ impl Integrator for HwssSlow {
                 None => break,
             };
 
+            let ctx = hit.texture_context(differential.as_ref());
+            let hit = hit.with_normal_map(bsdf, &ctx);
             let shading_wo = hit.world_to_shading(-ray.d());
             let (bsdf_sampled_wi, bsdf_values, bsdf_pdfs) =
-                bsdf.sample(shading_wo, wavelength, sampler);
+                bsdf.sample(shading_wo, &ctx, wavelength, sampler);
             let cos_theta = bsdf_sampled_wi.cos_theta().abs();
             if bsdf_pdfs.hero() == 0.0 || cos_theta == 0.0 {
                 break;
@@ -77,7 +88,15 @@ impl Integrator for HwssSlow {
 
             // Spawn new ray
             let world_wi = hit.shading_to_world(bsdf_sampled_wi);
-            ray = Ray::spawn(hit.point, world_wi, hit.normal);
+            // Footprints are only followed through specular bounces, others
+            // spread them too much to be worth filtering over
+            differential = match differential {
+                Some(differential) if bsdf.is_specular() => {
+                    hit.specular_differential(&differential, ray.d(), world_wi)
+                }
+                _ => None,
+            };
+            ray = hit.spawn_ray(world_wi);
         }
 
         radiance
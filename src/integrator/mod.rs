@@ -1,5 +1,7 @@
+use enum_dispatch::enum_dispatch;
+
 use crate::{
-    math::Ray,
+    math::{PdfSet, Ray, RayDifferential},
     sampling::Sampler,
     spectrum::{SpectralSample, Wavelength},
     scene::Scene,
@@ -10,6 +12,213 @@ pub mod hwss_slow;
 pub mod swss_naive;
 pub mod hwss_naive;
 
+pub use hwss_naive::HwssNaive;
+pub use hwss_slow::HwssSlow;
+pub use swss_naive::SwssNaive;
+pub use swss_slow::SwssSlow;
+
+#[enum_dispatch]
 pub trait Integrator {
-    fn radiance(&self, scene: &Scene, ray: Ray, wavelength: Wavelength, sampler: &mut Sampler) -> SpectralSample;
+    // camera_pdfs are the relative pdfs of the camera generating the ray for each
+    // wavelength. The differential, when there is one, sizes texture lookups
+    fn radiance(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        differential: Option<RayDifferential>,
+        wavelength: Wavelength,
+        camera_pdfs: PdfSet,
+        sampler: &mut Sampler,
+    ) -> SpectralSample;
+}
+
+#[enum_dispatch(Integrator)]
+#[derive(Debug, Clone)]
+pub enum IntegratorType {
+    HwssNaive,
+    SwssNaive,
+    HwssSlow,
+    SwssSlow,
+}
+
+impl IntegratorType {
+    pub const NAMES: &'static [&'static str] = &["hwss-naive", "swss-naive", "hwss-slow", "swss-slow"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntegratorType::HwssNaive(_) => "hwss-naive",
+            IntegratorType::SwssNaive(_) => "swss-naive",
+            IntegratorType::HwssSlow(_) => "hwss-slow",
+            IntegratorType::SwssSlow(_) => "swss-slow",
+        }
+    }
+}
+
+impl Default for IntegratorType {
+    fn default() -> Self {
+        Self::from(HwssNaive)
+    }
+}
+
+impl std::str::FromStr for IntegratorType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "hwss-naive" => Ok(HwssNaive.into()),
+            "swss-naive" => Ok(SwssNaive.into()),
+            "hwss-slow" => Ok(HwssSlow.into()),
+            "swss-slow" => Ok(SwssSlow.into()),
+            _ => Err(format!(
+                "unknown integrator '{}', expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for IntegratorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{Point3, Vec3},
+        scene::SceneDescription,
+    };
+
+    // Mean radiance, summed over the wavelengths of each sample, of rays from
+    // the origin in a small cone around +z
+    fn mean_radiance(integrator: &IntegratorType, scene: &Scene) -> f32 {
+        const N: usize = 64;
+        const SAMPLES: usize = 4;
+
+        let mut total = 0.0;
+        for y in 0..N {
+            for x in 0..N {
+                for i in 0..SAMPLES {
+                    let mut sampler = Sampler::new(x, y, i, 0);
+                    let wavelength = Wavelength::sample(&mut sampler);
+                    let d = Vec3::new(
+                        (x as f32 + sampler.gen_0_1()) / N as f32 - 0.5,
+                        (y as f32 + sampler.gen_0_1()) / N as f32 - 0.5,
+                        3.0,
+                    );
+                    let radiance = integrator.radiance(
+                        scene,
+                        Ray::new(Point3::splat(0.0), d.normalize()),
+                        None,
+                        wavelength,
+                        PdfSet::splat(1.0),
+                        &mut sampler,
+                    );
+                    total += radiance.sum();
+                }
+            }
+        }
+        total / (N * N * SAMPLES) as f32
+    }
+
+    // Renders the objects in front of the origin with every integrator, and
+    // checks that they agree with the first one within the given tolerance
+    fn assert_integrators_agree(objects: &str, integrators: &[&str], tolerance: f32) {
+        let source = format!("Scene(objects: [{}])", objects);
+        let scene = SceneDescription::parse(&source)
+            .unwrap()
+            .build_scene()
+            .unwrap();
+
+        let means: Vec<_> = integrators
+            .iter()
+            .map(|name| mean_radiance(&name.parse().unwrap(), &scene))
+            .collect();
+        assert!(means[0] > 0.0, "{:?}", means);
+        for (name, mean) in integrators.iter().zip(&means) {
+            assert!(
+                (mean / means[0] - 1.0).abs() < tolerance,
+                "{}: {:?} for {:?}",
+                name,
+                means,
+                integrators
+            );
+        }
+    }
+
+    #[test]
+    fn test_specular_direct_light() {
+        // A mirror reflecting an emitter behind the origin, which is only seen
+        // through BSDF samples
+        assert_integrators_agree(
+            "(shape: Sphere(center: (0, 0, 4), radius: 1.0), material: Specular(reflectance: \
+             Constant(1.0))),
+             (shape: Sphere(center: (0, 0, -8), radius: 4.0), material: Lambertian(albedo: \
+             Constant(0.0)), emission: Constant(1.0))",
+            &["swss-naive", "hwss-naive", "hwss-slow"],
+            0.02,
+        );
+    }
+
+    #[test]
+    fn test_emission_without_material() {
+        // An emitter seen directly behind a diffuse sphere it lights
+        assert_integrators_agree(
+            "(shape: Sphere(center: (0, 0, 4), radius: 0.5), material: Lambertian(albedo: \
+             Constant(0.8))),
+             (shape: Sphere(center: (0, 0, 12), radius: 5.0), emission: Constant(1.0))",
+            &["swss-slow", "hwss-slow", "swss-naive", "hwss-naive"],
+            0.05,
+        );
+    }
+
+    #[test]
+    fn test_dispersive_direct_light() {
+        // Light reaching a diffuse wall through dispersive glass is only
+        // carried by the hero wavelength
+        assert_integrators_agree(
+            "(shape: Sphere(center: (0, 0, 4), radius: 1.0), material: Dielectric(ior: Bk7)),
+             (shape: Sphere(center: (0, 0, 12), radius: 6.0), material: Lambertian(albedo: \
+             Constant(0.8))),
+             (shape: Sphere(center: (2, 2, 3), radius: 1.0), material: Lambertian(albedo: \
+             Constant(0.0)), emission: Constant(4.0))",
+            &["hwss-slow", "hwss-naive"],
+            0.05,
+        );
+    }
+
+    #[test]
+    fn test_transmitted_direct_light() {
+        // Rough glass hiding an emitter, which is only lit through it
+        assert_integrators_agree(
+            "(shape: Sphere(center: (0, 0, 4), radius: 1.0), material: RoughDielectric(ior: \
+             Constant(1.5), roughness: (0.3, 0.3))),
+             (shape: Sphere(center: (0, 0, 8), radius: 1.0), material: Lambertian(albedo: \
+             Constant(0.0)), emission: Constant(1.0))",
+            &["swss-slow", "hwss-slow", "swss-naive", "hwss-naive"],
+            0.05,
+        );
+
+        // And through the glass lobe of the principled BSDF
+        assert_integrators_agree(
+            "(shape: Sphere(center: (0, 0, 4), radius: 1.0), material: Principled(base_color: \
+             Constant(0.9), roughness: 0.3, transmission: 1.0)),
+             (shape: Sphere(center: (0, 0, 8), radius: 1.0), material: Lambertian(albedo: \
+             Constant(0.0)), emission: Constant(1.0))",
+            &["swss-slow", "hwss-slow", "swss-naive", "hwss-naive"],
+            0.05,
+        );
+    }
+
+    #[test]
+    fn test_names_round_trip() {
+        for name in IntegratorType::NAMES {
+            assert_eq!(name.parse::<IntegratorType>().unwrap().name(), *name);
+        }
+
+        assert!("path".parse::<IntegratorType>().is_err());
+    }
 }
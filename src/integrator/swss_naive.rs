@@ -1,21 +1,23 @@
 #[allow(unused)]
 use crate::{
-    bsdf::{Bsdf, FresnelBsdf, LambertianBsdf, MicrofacetBsdf, SampleableBsdf, SpecularBsdf},
+    bsdf::{Bsdf, DielectricBsdf, LambertianBsdf, MicrofacetBsdf, SampleableBsdf, SpecularBsdf},
     integrator::Integrator,
-    math::Ray,
     math::{PdfSet, Point3, Shading, Vec3},
+    math::{Ray, RayDifferential},
     sampling::Sampler,
     sampling::{self, mis},
     scene::Scene,
     shape::{Geometry, Intersection, Primitive, Shape, Sphere},
     spectrum::{upsample::UpsampleTable, ConstantSpectrum, Spectrum, UpsampledHdrSpectrum},
     spectrum::{SampleableSpectrum, SpectralSample, Wavelength},
+    texture::TextureContext,
     types::PrimIndex,
 };
 
 const MAX_DEPTH: u32 = 15;
 const MIN_DEPTH: u32 = 2;
 
+#[derive(Debug, Clone)]
 pub struct SwssNaive;
 
 impl Default for SwssNaive {
@@ -30,7 +32,9 @@ impl Integrator for SwssNaive {
         &self,
         scene: &Scene,
         mut ray: Ray,
+        mut differential: Option<RayDifferential>,
         wavelength: Wavelength,
+        _camera_pdfs: PdfSet,
         sampler: &mut Sampler,
     ) -> SpectralSample {
         let mut radiance = SpectralSample::splat(0.0);
@@ -39,12 +43,13 @@ impl Integrator for SwssNaive {
         for bounces in 0..MAX_DEPTH {
             let (prim, hit) = match scene.intersection(&ray) {
                 Some(ph) => ph,
-                None => break,
-            };
-
-            let bsdf = match prim.get_material(&scene.materials) {
-                Some(bsdf) => bsdf,
-                None => break, // TODO: Should this break be below?
+                None => {
+                    if bounces == 0 {
+                        // We didn't do NEE last step, accumulate the environment directly
+                        radiance += throughput * scene.background_emission(&ray, wavelength);
+                    }
+                    break;
+                }
             };
 
             if bounces == 0 {
@@ -54,14 +59,23 @@ impl Integrator for SwssNaive {
                 }
             }
 
+            // Emitters without a material don't reflect anything
+            let bsdf = match prim.get_material(&scene.materials) {
+                Some(bsdf) => bsdf,
+                None => break,
+            };
+
+            let ctx = hit.texture_context(differential.as_ref());
+            let hit = hit.with_normal_map(bsdf, &ctx);
+
             // Calculate direct lighting (next event estimation)
             radiance +=
-                throughput * self.direct_light(bsdf, &hit, scene, &ray, wavelength, sampler);
+                throughput * self.direct_light(bsdf, &hit, &ctx, scene, &ray, wavelength, sampler);
 
             // Calculate indirect lighting - generate next ray direction
             let shading_wo = hit.world_to_shading(-ray.d());
             let (bsdf_sampled_wi, bsdf_values, bsdf_pdfs) =
-                bsdf.sample(shading_wo, wavelength, sampler);
+                bsdf.sample(shading_wo, &ctx, wavelength, sampler);
             let cos_theta = bsdf_sampled_wi.cos_theta().abs();
             if bsdf_pdfs.hero() == 0.0 || cos_theta == 0.0 {
                 break;
@@ -81,7 +95,15 @@ impl Integrator for SwssNaive {
 
             // Spawn new ray
             let world_wi = hit.shading_to_world(bsdf_sampled_wi);
-            ray = Ray::spawn(hit.point, world_wi, hit.normal);
+            // Footprints are only followed through specular bounces, others
+            // spread them too much to be worth filtering over
+            differential = match differential {
+                Some(differential) if bsdf.is_specular() => {
+                    hit.specular_differential(&differential, ray.d(), world_wi)
+                }
+                _ => None,
+            };
+            ray = hit.spawn_ray(world_wi);
         }
 
         SpectralSample::new(radiance.hero(), 0.0, 0.0, 0.0)
@@ -89,10 +111,12 @@ impl Integrator for SwssNaive {
 }
 
 impl SwssNaive {
+    #[allow(clippy::too_many_arguments)]
     fn direct_light(
         &self,
         bsdf: &Bsdf,
         hit: &Intersection,
+        ctx: &TextureContext,
         scene: &Scene,
         ray: &Ray,
         wavelength: Wavelength,
@@ -101,27 +125,29 @@ impl SwssNaive {
         let mut radiance = SpectralSample::splat(0.0);
 
         let shading_wo = hit.world_to_shading(-ray.d());
-        let (light_spectrum, light_prim, light_pick_weight) = scene.pick_one_light(sampler);
-        let light_emission = light_spectrum.evaluate(wavelength);
-
-        // Sample light
-        {
-            let (light_pos, light_pdf) = light_prim.sample(&hit, sampler);
-
-            let ray_to_light = Ray::spawn_to(hit.point, light_pos, hit.normal);
-            let facing_forward = (light_pos - hit.point).dot(hit.normal) > 0.0;
+        let (light, light_pick_weight) = match scene.pick_one_light(sampler) {
+            Some(light) => light,
+            None => return radiance,
+        };
+
+        // Sample light, which can't hit the directions of a specular BSDF
+        let light_sample = if bsdf.is_specular() {
+            None
+        } else {
+            light.sample(hit, wavelength, sampler)
+        };
+        if let Some((ray_to_light, light_pdf, light_emission)) = light_sample {
+            // Lights behind the surface can only be seen through BSDFs that
+            // transmit
+            let facing_forward = ray_to_light.d().dot(hit.normal) > 0.0;
+            let visible_side = facing_forward != hit.back_face || bsdf.transmits();
 
             // Check that the light has a non-zero contribution
-            // These checks are very important otherwise lights will illuminate themselves
-            if light_pdf > 0.0
-                && facing_forward != hit.back_face
-                && light_pos.distance_squared(hit.point) > 0.00001
-                && scene.ray_hits_point(&ray_to_light, light_pos)
-            {
+            if visible_side && !scene.occluded(&ray_to_light) {
                 // Add light sample contribution
                 let shading_wi = hit.world_to_shading(ray_to_light.d());
-                let bsdf_values = bsdf.evaluate(shading_wi, shading_wo, wavelength);
-                let bsdf_pdfs = bsdf.pdf(shading_wi, shading_wo, wavelength);
+                let bsdf_values = bsdf.evaluate(shading_wi, shading_wo, ctx, wavelength);
+                let bsdf_pdfs = bsdf.pdf(shading_wi, shading_wo, ctx, wavelength);
                 let cos_theta = shading_wi.cos_theta().abs();
 
                 // Balance heuristic
@@ -133,18 +159,25 @@ impl SwssNaive {
         // Sample BSDF
         {
             let (bsdf_sampled_wi, bsdf_values, bsdf_pdfs) =
-                bsdf.sample(shading_wo, wavelength, sampler);
+                bsdf.sample(shading_wo, ctx, wavelength, sampler);
             let cos_theta = bsdf_sampled_wi.cos_theta().abs();
-            let ray_to_light =
-                Ray::spawn(hit.point, hit.shading_to_world(bsdf_sampled_wi), hit.normal);
 
-            // Check that the light has a non-zero contribution
-            if bsdf_pdfs.hero() > 0.0 && scene.ray_hits_object(&ray_to_light, light_prim) {
-                // Add light sample contribution
-                let light_pdf = light_prim.pdf(&hit, ray_to_light.d());
-                let mis_weight = bsdf_pdfs.hero() / (bsdf_pdfs.hero() + light_pdf);
-                radiance +=
-                    mis_weight * light_emission * bsdf_values * cos_theta / bsdf_pdfs.hero();
+            // Check that the light has a non-zero contribution. Failed samples
+            // have no direction to spawn a ray along
+            if bsdf_pdfs.hero() > 0.0 {
+                let ray_to_light = hit.spawn_ray(hit.shading_to_world(bsdf_sampled_wi));
+                if let Some((light_pdf, light_emission)) =
+                    light.hit_by(scene, hit, &ray_to_light, wavelength)
+                {
+                    // Add light sample contribution
+                    let mis_weight = if bsdf.is_specular() {
+                        1.0
+                    } else {
+                        bsdf_pdfs.hero() / (bsdf_pdfs.hero() + light_pdf)
+                    };
+                    radiance +=
+                        mis_weight * light_emission * bsdf_values * cos_theta / bsdf_pdfs.hero();
+                }
             }
         }
 
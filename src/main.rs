@@ -4,71 +4,203 @@ extern crate sobol_burley as sobol;
 
 use std::{
     collections::BinaryHeap,
-    sync::{Arc, Mutex, RwLock},
-    time::Instant,
+    num::NonZeroUsize,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 mod bsdf;
 mod camera;
+mod cli;
 mod color;
+mod film;
+mod filter;
 mod integrator;
 mod math;
 mod sampling;
 mod scene;
 mod shape;
 mod spectrum;
+mod texture;
 mod tile;
 mod types;
 
 use camera::Camera;
-use scene::Scene;
-use tile::TileData;
-
-const WIDTH: usize = 512;
-const HEIGHT: usize = 512;
-const TOTAL_SPP: usize = 100;
-
-type CurrentIntegrator = integrator::hwss_naive::HwssNaive;
+use clap::Parser;
+use cli::Args;
+use film::Film;
+use filter::{Filter, FilterSampler};
+use integrator::IntegratorType;
+use scene::{AdaptiveDescription, Scene, SceneDescription, SceneError};
+use tile::{AdaptiveSampling, TileData};
 
 pub struct Render {
     pub width: usize,
     pub height: usize,
     pub spp: usize,
-    pub scene: Scene,
+    pub seed: u32,
+    pub scene: Arc<Scene>,
     pub camera: Camera,
-    pub buffer: RwLock<Vec<(f32, f32, f32)>>,
-    pub integrator: CurrentIntegrator,
+    // Open and close times of the camera shutter
+    pub shutter: (f32, f32),
+    pub film: Film,
+    pub filter: Filter,
+    pub filter_sampler: FilterSampler,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub integrator: IntegratorType,
 }
 
 fn main() {
-    let render = Arc::new(Render {
-        width: WIDTH,
-        height: HEIGHT,
-        spp: TOTAL_SPP,
-        integrator: CurrentIntegrator::default(),
-        scene: scene::Scene::dummy(),
-        buffer: RwLock::new(vec![(0.0, 0.0, 0.0); WIDTH * HEIGHT]),
-        camera: Camera::new(
-            math::Point3::new(0.0, 0.0, 0.0),
-            (WIDTH as f32) / (HEIGHT as f32),
-        ),
-    });
-
-    let tile_priorities = Arc::new(Mutex::new(
-        // TODO: Make this nice
-        (0..)
-            .map(|idx| TileData::new(&render, idx))
-            .take_while(|t| t.is_some())
-            .map(|t| t.unwrap())
-            .collect::<BinaryHeap<TileData>>(),
-    ));
-
-    let num_threads = std::env::var("NTHREADS")
-        .ok()
-        .and_then(|s| s.parse::<usize>().ok())
-        .unwrap_or_else(num_cpus::get);
-
-    do_render(render, tile_priorities, num_threads);
+    let args = Args::parse();
+
+    let (description, scene) = match load_scene(&args.scene) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("error: {}: {}", args.scene.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let width = args.width.map_or(description.render.width, usize::from);
+    let height = args.height.map_or(description.render.height, usize::from);
+    let spp = args.spp.unwrap_or(description.render.spp);
+
+    // Either flag turns adaptive sampling on
+    let adaptive = description
+        .render
+        .adaptive
+        .clone()
+        .or_else(|| {
+            (args.error_threshold.is_some() || args.time_limit.is_some())
+                .then(AdaptiveDescription::default)
+        })
+        .map(|adaptive| {
+            AdaptiveDescription {
+                threshold: args.error_threshold.unwrap_or(adaptive.threshold),
+                time_limit: args.time_limit.or(adaptive.time_limit),
+                ..adaptive
+            }
+            .build()
+        });
+
+    // Shared between renders when comparing integrators
+    let scene = Arc::new(scene);
+
+    let camera = match description.build_camera((width as f32) / (height as f32)) {
+        Ok(camera) => camera,
+        Err(e) => {
+            eprintln!("error: {}: {}", args.scene.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let filter = args
+        .filter
+        .clone()
+        .unwrap_or_else(|| description.render.filter.build());
+
+    for integrator in &args.integrators {
+        let render = Arc::new(Render {
+            width,
+            height,
+            spp,
+            seed: args.seed,
+            integrator: integrator.clone(),
+            scene: scene.clone(),
+            film: Film::new(width, height)
+                .with_spectral_bins(args.spectral_bins.map_or(0, NonZeroUsize::get)),
+            filter: filter.clone(),
+            filter_sampler: FilterSampler::new(filter.clone()),
+            adaptive: adaptive.clone(),
+            camera: camera.clone(),
+            shutter: description.camera.shutter(),
+        });
+
+        let tile_priorities = Arc::new(Mutex::new(
+            // TODO: Make this nice
+            (0..)
+                .map(|idx| TileData::new(&render, idx))
+                .take_while(|t| t.is_some())
+                .map(|t| t.unwrap())
+                .collect::<BinaryHeap<TileData>>(),
+        ));
+
+        do_render(render.clone(), tile_priorities, args.num_threads());
+
+        use exr::prelude::*;
+
+        let buffer = render.film.to_xyz();
+
+        write_rgb_file(
+            args.output_path(integrator),
+            render.width,
+            render.height,
+            |x, y| {
+                let (r, g, b) = buffer[x + y * render.width].to_rgb_hdr();
+                (r.max(0.0), g.max(0.0), b.max(0.0))
+            },
+        )
+        .unwrap();
+
+        if render.film.spectral_bins() > 0 {
+            write_spectral_exr(&args.spectral_output_path(integrator), &render).unwrap();
+        }
+    }
+}
+
+// Follows "An OpenEXR Layout for Spectral Images" (Fichet et al. 2021): one
+// S0.<wavelength>nm channel of emitted radiance per bin, with a comma for the
+// decimal point as dots separate layers, and RGB for viewers that don't know
+// about spectra
+fn write_spectral_exr(path: &Path, render: &Render) -> exr::error::UnitResult {
+    use exr::prelude::*;
+
+    let (width, height) = (render.width, render.height);
+    let xyz = render.film.to_xyz();
+    let spectra = render.film.to_spectra();
+    let bins = render.film.spectral_bins();
+
+    let mut channels = SmallVec::<[AnyChannel<FlatSamples>; 4]>::new();
+    for (c, name) in ["R", "G", "B"].iter().enumerate() {
+        let samples = xyz
+            .iter()
+            .map(|xyz| {
+                let (r, g, b) = xyz.to_rgb_hdr();
+                [r, g, b][c].max(0.0)
+            })
+            .collect();
+        channels.push(AnyChannel::new(*name, FlatSamples::F32(samples)));
+    }
+    for (i, lambda) in render.film.bin_wavelengths().iter().enumerate() {
+        let name = format!("S0.{}nm", format!("{:.6}", lambda).replace('.', ","));
+        let samples = spectra.iter().skip(i).step_by(bins).copied().collect();
+        channels.push(AnyChannel::new(name.as_str(), FlatSamples::F32(samples)));
+    }
+
+    let mut attributes = LayerAttributes::default();
+    attributes.other.insert(
+        Text::from("spectralLayoutVersion"),
+        AttributeValue::Text(Text::from("1.0")),
+    );
+    attributes.other.insert(
+        Text::from("emissiveUnits"),
+        AttributeValue::Text(Text::from("W.m^-2.sr^-1")),
+    );
+
+    let layer = Layer::new(
+        (width, height),
+        attributes,
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(channels),
+    );
+    Image::from_layer(layer).write().to_file(path)
+}
+
+fn load_scene(path: &Path) -> Result<(SceneDescription, Scene), SceneError> {
+    let description = SceneDescription::load(path)?;
+    let scene = description.build_scene()?;
+    Ok((description, scene))
 }
 
 #[cfg(not(feature = "progressive"))]
@@ -77,22 +209,41 @@ fn do_render(
     tile_priorities: Arc<Mutex<BinaryHeap<TileData>>>,
     num_threads: usize,
 ) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     println!(
-        "Starting render, {}x{}@{}spp...",
-        render.width, render.height, render.spp
+        "Starting render, {}x{}@{}spp with {}...",
+        render.width, render.height, render.spp, render.integrator
     );
 
     let start = Instant::now();
+    let samples_taken = Arc::new(AtomicUsize::new(0));
+    let tiles_left = Arc::new(AtomicUsize::new(tile_priorities.lock().unwrap().len()));
 
     let threads = (0..num_threads)
         .map(|_| {
             let tile_priorities = tile_priorities.clone();
             let render = render.clone();
+            let samples_taken = samples_taken.clone();
+            let tiles_left = tiles_left.clone();
             std::thread::spawn(move || loop {
                 let popped = tile_priorities.lock().unwrap().pop();
                 match popped {
                     Some(tile) => {
-                        tile.render(&render);
+                        let pass_size = tile.pass_size(&render);
+                        let tile = tile.render(&render, pass_size);
+
+                        // Adaptive sampling refines tiles over several passes
+                        if tile.needs_samples(&render, start.elapsed()) {
+                            tile_priorities.lock().unwrap().push(tile);
+                        } else {
+                            samples_taken.fetch_add(tile.pixel_samples_taken(), Ordering::Relaxed);
+                            tiles_left.fetch_sub(1, Ordering::Relaxed);
+                        }
+                    }
+                    // Other threads may still put their tiles back
+                    None if tiles_left.load(Ordering::Relaxed) > 0 => {
+                        std::thread::sleep(Duration::from_millis(1));
                     }
                     None => {
                         break;
@@ -106,21 +257,24 @@ fn do_render(
         thread.join().unwrap();
     }
 
+    print_done(&render, start, samples_taken.load(Ordering::Relaxed));
+}
+
+fn print_done(render: &Render, start: Instant, samples_taken: usize) {
     let elapsed = start.elapsed().as_secs_f32();
+    let pixels = render.width * render.height;
     println!(
         "Done in {}s ({}m ray/s)",
         elapsed,
-        ((render.spp * WIDTH * HEIGHT) as f32) / (1_000_000.0 * elapsed),
+        samples_taken as f32 / (1_000_000.0 * elapsed),
     );
 
-    use exr::prelude::*;
-
-    let buffer = render.buffer.read().unwrap();
-
-    write_rgb_file("out.exr", render.width, render.height, |x, y| {
-        buffer[x + y * render.width]
-    })
-    .unwrap();
+    if render.adaptive.is_some() {
+        println!(
+            "Adaptive sampling took {:.1}spp on average",
+            samples_taken as f32 / pixels as f32
+        );
+    }
 }
 
 #[cfg(feature = "progressive")]
@@ -136,10 +290,14 @@ fn do_render(
 
     use minifb::{Key, Window, WindowOptions};
 
+    // Samples per pixel taken by a tile in one go, the first passes are smaller so
+    // that a preview of the whole image shows up quickly
+    const MAX_SAMPLES_PER_PASS: usize = 16;
+
     let mut window = Window::new(
         "Iris",
-        WIDTH,
-        HEIGHT,
+        render.width,
+        render.height,
         WindowOptions {
             resize: false,
             ..Default::default()
@@ -147,10 +305,19 @@ fn do_render(
     )
     .expect("failed to create window");
 
+    let total_samples = render.spp * render.width * render.height;
+    // Counts whole passes, adaptive sampling usually finishes before reaching
+    // the total
     let samples_taken = Arc::new(AtomicUsize::new(0));
-    static DONE: AtomicBool = AtomicBool::new(false);
+    let tiles_left = Arc::new(AtomicUsize::new(tile_priorities.lock().unwrap().len()));
+    let pixel_samples_taken = Arc::new(AtomicUsize::new(0));
+    // Set when the window is closed before the render is done
+    let cancelled = Arc::new(AtomicBool::new(false));
 
-    println!("Starting render...");
+    println!(
+        "Starting render, {}x{}@{}spp with {}...",
+        render.width, render.height, render.spp, render.integrator
+    );
 
     let start = Instant::now();
 
@@ -158,33 +325,44 @@ fn do_render(
         let tile_priorities = tile_priorities.clone();
         let render = render.clone();
         let samples_taken = samples_taken.clone();
+        let tiles_left = tiles_left.clone();
+        let pixel_samples_taken = pixel_samples_taken.clone();
+        let cancelled = cancelled.clone();
         std::thread::spawn(move || loop {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+
             let popped = tile_priorities.lock().unwrap().pop();
             match popped {
                 Some(tile) => {
                     let samples_before = tile.remaining_samples;
-                    let tile = tile.render(&render);
+                    let pass_size = tile
+                        .samples_taken(&render)
+                        .clamp(1, MAX_SAMPLES_PER_PASS)
+                        .min(tile.pass_size(&render));
+                    let tile = tile.render(&render, pass_size);
                     let samples_after = tile.remaining_samples;
-                    samples_taken.fetch_add(
-                        (samples_before - samples_after) * tile.width * tile.height,
-                        Ordering::Relaxed,
-                    );
 
-                    if samples_after > 0 {
+                    let pass_samples = (samples_before - samples_after) * tile.width * tile.height;
+                    samples_taken.fetch_add(pass_samples, Ordering::Relaxed);
+
+                    if tile.needs_samples(&render, start.elapsed()) {
                         tile_priorities.lock().unwrap().push(tile);
+                    } else {
+                        pixel_samples_taken
+                            .fetch_add(tile.pixel_samples_taken(), Ordering::Relaxed);
+                        if tiles_left.fetch_sub(1, Ordering::AcqRel) == 1 {
+                            let taken = pixel_samples_taken.load(Ordering::Relaxed);
+                            print_done(&render, start, taken);
+                        }
                     }
                 }
-                None => {
-                    if !DONE.swap(true, Ordering::Relaxed) {
-                        let elapsed = start.elapsed().as_secs_f32();
-                        println!(
-                            "Done in {}s ({}m ray/s)",
-                            elapsed,
-                            ((render.spp * WIDTH * HEIGHT) as f32) / (1_000_000.0 * elapsed),
-                        );
-                    }
-                    break;
+                // Other threads may still put their tiles back
+                None if tiles_left.load(Ordering::Relaxed) > 0 => {
+                    std::thread::sleep(Duration::from_millis(1));
                 }
+                None => break,
             }
         });
     }
@@ -194,24 +372,21 @@ fn do_render(
     let mut fb = vec![0u32; render.width * render.height];
 
     while window.is_open() && !window.is_key_down(Key::Escape) {
-        if !DONE.load(Ordering::Relaxed) {
-            let progress = samples_taken.load(Ordering::Relaxed) as f32
-                / (render.spp * render.width * render.height) as f32;
+        let samples_so_far = samples_taken.load(Ordering::Relaxed);
+        if tiles_left.load(Ordering::Relaxed) > 0 {
+            let progress = samples_so_far as f32 / total_samples as f32;
             print!("Progress: {:>5.2}%\r", 100.0 * progress);
             std::io::stdout().flush().unwrap();
         }
 
-        let buffer = render.buffer.read().unwrap();
-
-        for (i, pixel) in buffer.iter().enumerate() {
-            fb[i] = ((pixel.0 as u32) << 16) | ((pixel.1 as u32) << 8) | (pixel.2 as u32);
-            if *pixel != (0.0, 0.0, 0.0) {
-                dbg!(pixel);
-            }
+        for (pixel, xyz) in fb.iter_mut().zip(render.film.to_xyz()) {
+            *pixel = xyz.to_srgb().to_u32();
         }
 
         window
             .update_with_buffer(&fb, render.width, render.height)
             .expect("failed to update window buffer with pixel data");
     }
+
+    cancelled.store(true, Ordering::Relaxed);
 }
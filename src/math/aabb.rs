@@ -0,0 +1,166 @@
+#![allow(dead_code)]
+
+use super::{Point3, Ray, World};
+
+// Axis-aligned bounding box
+pub struct Aabb<System = World> {
+    pub min: Point3<System>,
+    pub max: Point3<System>,
+}
+
+// Required because #[derive(Copy, Clone)] places bounds on type parameters
+impl<S> Copy for Aabb<S> {}
+impl<S> Clone for Aabb<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> std::fmt::Debug for Aabb<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Aabb")
+            .field("min", &self.min)
+            .field("max", &self.max)
+            .finish()
+    }
+}
+
+impl<S> PartialEq for Aabb<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.min == other.min && self.max == other.max
+    }
+}
+
+impl<S> Aabb<S> {
+    pub fn new(a: Point3<S>, b: Point3<S>) -> Self {
+        Self {
+            min: Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            max: Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z())),
+        }
+    }
+
+    // Contains nothing, the identity for union
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::splat(f32::INFINITY),
+            max: Point3::splat(f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: Point3::new(
+                self.min.x().min(other.min.x()),
+                self.min.y().min(other.min.y()),
+                self.min.z().min(other.min.z()),
+            ),
+            max: Point3::new(
+                self.max.x().max(other.max.x()),
+                self.max.y().max(other.max.y()),
+                self.max.z().max(other.max.z()),
+            ),
+        }
+    }
+
+    pub fn union_point(self, p: Point3<S>) -> Self {
+        self.union(Self { min: p, max: p })
+    }
+
+    pub fn centroid(self) -> Point3<S> {
+        Point3::new(
+            0.5 * (self.min.x() + self.max.x()),
+            0.5 * (self.min.y() + self.max.y()),
+            0.5 * (self.min.z() + self.max.z()),
+        )
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.min.x() > self.max.x() || self.min.y() > self.max.y() || self.min.z() > self.max.z()
+    }
+
+    pub fn surface_area(self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let d = self.max - self.min;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    // Index of the longest axis
+    pub fn max_extent(self) -> usize {
+        let d = self.max - self.min;
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    // Position of p along an axis, 0 at the min corner and 1 at the max corner
+    pub fn offset(self, p: Point3<S>, axis: usize) -> f32 {
+        let extent = self.max.get(axis) - self.min.get(axis);
+        if extent > 0.0 {
+            (p.get(axis) - self.min.get(axis)) / extent
+        } else {
+            0.0
+        }
+    }
+
+    // Slab test against the ray's [t_min, t_max], inv_d is the reciprocal of the
+    // ray direction
+    pub fn hit(&self, ray: &Ray<S>, inv_d: [f32; 3]) -> bool {
+        let o = ray.o();
+        let mut t0 = ray.t_min();
+        let mut t1 = ray.t_max();
+
+        for (axis, inv) in inv_d.iter().enumerate() {
+            let near = (self.min.get(axis) - o.get(axis)) * inv;
+            let far = (self.max.get(axis) - o.get(axis)) * inv;
+            let (near, far) = if near > far { (far, near) } else { (near, far) };
+
+            // f32::max/min ignore NaNs, which occur when the origin lies on a slab boundary
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+            if t0 > t1 {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec3;
+
+    #[test]
+    fn test_hit() {
+        let aabb: Aabb = Aabb::new(Point3::new(-1.0, -1.0, 2.0), Point3::new(1.0, 1.0, 4.0));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let inv_d = [1.0 / ray.d().x(), 1.0 / ray.d().y(), 1.0 / ray.d().z()];
+
+        assert!(aabb.hit(&ray, inv_d));
+        assert!(!aabb.hit(&ray.clone().with_t_max(1.0), inv_d));
+
+        let miss = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!aabb.hit(&miss, inv_d));
+    }
+
+    #[test]
+    fn test_union() {
+        let a: Aabb = Aabb::new(Point3::splat(0.0), Point3::splat(1.0));
+        let b = Aabb::new(Point3::splat(2.0), Point3::splat(3.0));
+        assert_eq!(
+            a.union(b),
+            Aabb::new(Point3::splat(0.0), Point3::splat(3.0))
+        );
+        assert_eq!(Aabb::empty().union(a), a);
+        assert_eq!(a.surface_area(), 6.0);
+        assert_eq!(Aabb::<World>::empty().surface_area(), 0.0);
+    }
+}
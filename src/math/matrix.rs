@@ -61,6 +61,32 @@ impl<U, V> Matrix<U, V> {
         }
     }
 
+    pub fn scale(scale: Vec3) -> Self {
+        Self {
+            m: [
+                [scale.x(), 0.0, 0.0, 0.0],
+                [0.0, scale.y(), 0.0, 0.0],
+                [0.0, 0.0, scale.z(), 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+            _coord: PhantomData,
+        }
+    }
+
+    pub fn transpose(&self) -> Matrix<V, U> {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+
+        Matrix {
+            m,
+            _coord: PhantomData,
+        }
+    }
+
     pub fn inverse(&self) -> Matrix<V, U> {
         // Adapted from https://github.com/mmp/pbrt-v3/blob/master/src/core/transform.cpp#L82
         let mut indxc = [0; 4];
@@ -144,8 +170,8 @@ impl<U, V> Matrix<U, V> {
         Self {
             m: [
                 [u.x(), v.x(), w.x(), point.x()],
-                [u.x(), v.x(), w.x(), point.x()],
-                [u.x(), v.x(), w.x(), point.x()],
+                [u.y(), v.y(), w.y(), point.y()],
+                [u.z(), v.z(), w.z(), point.z()],
                 [0.0, 0.0, 0.0, 1.0],
             ],
             _coord: PhantomData,
@@ -198,8 +224,12 @@ impl<U, V> std::ops::Mul<Point3<U>> for &'_ Matrix<U, V> {
 impl<U, V> std::ops::Mul<Ray<U>> for &'_ Matrix<U, V> {
     type Output = Ray<V>;
 
+    // The direction is normalized again, so the range is scaled along with it
     fn mul(self, other: Ray<U>) -> Ray<V> {
-        Ray::new(self * other.o(), self * other.d())
+        let d = self * other.d();
+        Ray::new(self * other.o(), d)
+            .with_t_max(other.t_max() * d.len())
+            .with_time(other.time())
     }
 }
 
@@ -1,14 +1,20 @@
+mod aabb;
 mod matrix;
 mod pdf;
 mod point3;
+mod quaternion;
 mod ray;
+mod transform;
 mod vec3;
 mod vec4;
 
+pub use aabb::*;
 pub use matrix::*;
 pub use pdf::*;
 pub use point3::*;
+pub use quaternion::*;
 pub use ray::*;
+pub use transform::*;
 pub use vec3::*;
 pub use vec4::*;
 
@@ -58,7 +64,33 @@ pub fn fresnel_dielectric(cos_theta_i: f32, eta_i: f32, eta_t: f32) -> f32 {
     (r_par.powi(2) + r_perp.powi(2)) / 2.0
 }
 
-pub fn refract(wi: Vec3<Shading>, n: Vec3<Shading>, eta: f32) -> Option<Vec3<Shading>> {
+// Reflectance of a conductor with complex index of refraction eta + ik, lit
+// from a medium with index 1, see
+// https://seblagarde.wordpress.com/2013/04/29/memo-on-fresnel-equations/
+pub fn fresnel_conductor(cos_theta_i: f32, eta: f32, k: f32) -> f32 {
+    let cos_2_theta_i = f32::clamp(cos_theta_i * cos_theta_i, 0.0, 1.0);
+    let sin_2_theta_i = 1.0 - cos_2_theta_i;
+
+    let t0 = eta * eta - k * k - sin_2_theta_i;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let t1 = a2_plus_b2 + cos_2_theta_i;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta_i.abs() * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos_2_theta_i * a2_plus_b2 + sin_2_theta_i * sin_2_theta_i;
+    let t4 = t2 * sin_2_theta_i;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+
+    (r_p + r_s) / 2.0
+}
+
+// Mirrors wo around n
+pub fn reflect<S>(wo: Vec3<S>, n: Vec3<S>) -> Vec3<S> {
+    -wo + (2.0 * wo.dot(n) * n)
+}
+
+pub fn refract<S>(wi: Vec3<S>, n: Vec3<S>, eta: f32) -> Option<Vec3<S>> {
     let cos_theta_i = n.dot(wi);
     let sin_2_theta_i = (1.0 - cos_theta_i.powi(2)).max(0.0);
     let sin_2_theta_t = eta * eta * sin_2_theta_i;
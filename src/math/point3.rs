@@ -41,6 +41,15 @@ impl<S> Point3<S> {
         self.z
     }
 
+    pub fn get(self, axis: usize) -> f32 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            2 => self.z,
+            _ => panic!("invalid axis {}", axis),
+        }
+    }
+
     pub fn distance(self, other: Self) -> f32 {
         self.distance_squared(other).sqrt()
     }
@@ -52,6 +61,10 @@ impl<S> Point3<S> {
     pub fn to_vec(self) -> Vec3<S> {
         Vec3::new(self.x(), self.y(), self.z())
     }
+
+    pub fn coerce_system<V>(self) -> Point3<V> {
+        Point3::new(self.x, self.y, self.z)
+    }
 }
 
 // Required because #[derive(Copy, Clone)] places bounds on type parameters
@@ -0,0 +1,155 @@
+use super::{Matrix, Vec3};
+
+// Unit quaternion representing a rotation
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quaternion {
+    x: f32,
+    y: f32,
+    z: f32,
+    w: f32,
+}
+
+impl Quaternion {
+    pub fn id() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 1.0,
+        }
+    }
+
+    // Counter-clockwise rotation by angle (in radians) around the axis
+    pub fn from_axis_angle<S>(axis: Vec3<S>, angle: f32) -> Self {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+
+        Self {
+            x: axis.x() * sin,
+            y: axis.y() * sin,
+            z: axis.z() * sin,
+            w: cos,
+        }
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn normalize(self) -> Self {
+        self * (1.0 / self.dot(self).sqrt())
+    }
+
+    // The inverse rotation
+    pub fn conjugate(self) -> Self {
+        Self {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+            w: self.w,
+        }
+    }
+
+    // Interpolates along the shortest arc between the two rotations, at constant
+    // angular velocity
+    pub fn slerp(t: f32, a: Self, b: Self) -> Self {
+        let cos_theta = a.dot(b);
+        // q and -q are the same rotation, go the short way around
+        let (b, cos_theta) = if cos_theta < 0.0 {
+            (b * -1.0, -cos_theta)
+        } else {
+            (b, cos_theta)
+        };
+
+        if cos_theta > 0.9995 {
+            // Nearly parallel, lerping is accurate and avoids dividing by sin(theta)
+            return (a * (1.0 - t) + b * t).normalize();
+        }
+
+        let theta = cos_theta.clamp(-1.0, 1.0).acos();
+        let sin_theta = theta.sin();
+        a * (((1.0 - t) * theta).sin() / sin_theta) + b * ((t * theta).sin() / sin_theta)
+    }
+
+    pub fn to_matrix<U, V>(self) -> Matrix<U, V> {
+        let Self { x, y, z, w } = self;
+
+        Matrix::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - z * w),
+                2.0 * (x * z + y * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - x * w),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - y * w),
+                2.0 * (y * z + x * w),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl std::ops::Add for Quaternion {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+            w: self.w + other.w,
+        }
+    }
+}
+
+impl std::ops::Mul<f32> for Quaternion {
+    type Output = Self;
+
+    fn mul(self, other: f32) -> Self {
+        Self {
+            x: self.x * other,
+            y: self.y * other,
+            z: self.z * other,
+            w: self.w * other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::World;
+
+    #[test]
+    fn test_slerp() {
+        let up = Vec3::<World>::new(0.0, 1.0, 0.0);
+        let a = Quaternion::id();
+        let b = Quaternion::from_axis_angle(up, 90f32.to_radians());
+
+        // Halfway is a 45 degree rotation, which takes +x towards -z
+        let half = Quaternion::slerp(0.5, a, b).to_matrix::<World, World>();
+        let x = &half * Vec3::<World>::new(1.0, 0.0, 0.0);
+        let expected = Vec3::new(1.0, 0.0, -1.0).normalize();
+        assert!((x - expected).len() < 1e-5);
+
+        assert_eq!(Quaternion::slerp(0.0, a, b), a);
+        let end = Quaternion::slerp(1.0, a, b);
+        assert!((end.dot(b) - 1.0).abs() < 1e-5);
+
+        // The shortest way from 0 to 270 degrees is backwards
+        let c = Quaternion::from_axis_angle(up, 270f32.to_radians());
+        let quarter = Quaternion::slerp(0.5, a, c).to_matrix::<World, World>();
+        let x = &quarter * Vec3::<World>::new(1.0, 0.0, 0.0);
+        let expected = Vec3::new(1.0, 0.0, 1.0).normalize();
+        assert!((x - expected).len() < 1e-5);
+    }
+}
@@ -8,6 +8,11 @@ pub const RAY_EPSILON: f32 = 0.001;
 pub struct Ray<System = World> {
     o: Point3<System>,
     d: Vec3<System>,
+    // Only hits with t_min < t < t_max are reported
+    t_min: f32,
+    t_max: f32,
+    // Within the camera's shutter interval, for motion blur
+    time: f32,
 }
 
 impl<S> Ray<S> {
@@ -15,6 +20,9 @@ impl<S> Ray<S> {
         Self {
             o,
             d: d.normalize(),
+            t_min: 0.0,
+            t_max: f32::INFINITY,
+            time: 0.0,
         }
     }
 
@@ -28,9 +36,13 @@ impl<S> Ray<S> {
         Self {
             o,
             d: d.normalize(),
+            t_min: 0.0,
+            t_max: f32::INFINITY,
+            time: 0.0,
         }
     }
 
+    // Stops just short of p, so that the ray can be used to test its visibility
     pub fn spawn_to(o: Point3<S>, p: Point3<S>, normal: Vec3<S>) -> Self {
         let o = if (p - o).dot(normal) < 0.0 {
             offset_origin(o, -normal)
@@ -38,9 +50,32 @@ impl<S> Ray<S> {
             offset_origin(o, normal)
         };
 
+        let to_p = p - o;
         Self {
             o,
-            d: (p - o).normalize(),
+            d: to_p.normalize(),
+            t_min: 0.0,
+            t_max: to_p.len() * (1.0 - RAY_EPSILON),
+            time: 0.0,
+        }
+    }
+
+    pub fn with_t_max(self, t_max: f32) -> Self {
+        Self { t_max, ..self }
+    }
+
+    pub fn with_time(self, time: f32) -> Self {
+        Self { time, ..self }
+    }
+
+    // The same ray, labeled as being in another coordinate system
+    pub fn coerce_system<V>(self) -> Ray<V> {
+        Ray {
+            o: self.o.coerce_system(),
+            d: self.d.coerce_system(),
+            t_min: self.t_min,
+            t_max: self.t_max,
+            time: self.time,
         }
     }
 
@@ -52,6 +87,22 @@ impl<S> Ray<S> {
         self.d
     }
 
+    pub fn t_min(&self) -> f32 {
+        self.t_min
+    }
+
+    pub fn t_max(&self) -> f32 {
+        self.t_max
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn in_range(&self, t: f32) -> bool {
+        t > self.t_min && t < self.t_max
+    }
+
     pub fn point_at(&self, t: f32) -> Point3<S> {
         self.o + self.d * t
     }
@@ -83,3 +134,13 @@ pub fn offset_origin<S>(p: Point3<S>, n: Vec3<S>) -> Point3<S> {
         if p.z.abs() < ORIGIN { p.z() + FLOAT_SCALE * n.z() } else { p_i[2] },
     )
 }
+
+// Rays through the neighbouring pixels, one across and one down the image,
+// which give the footprint of a pixel on the surfaces it sees
+#[derive(Debug, Clone, Copy)]
+pub struct RayDifferential {
+    pub rx_o: Point3,
+    pub rx_d: Vec3,
+    pub ry_o: Point3,
+    pub ry_d: Vec3,
+}
@@ -0,0 +1,259 @@
+use std::{borrow::Cow, sync::Arc};
+
+use super::{Aabb, Local, Matrix, Point3, Quaternion, Ray, Vec3, World};
+
+// Number of steps between keyframes that bounds are evaluated at
+const BOUNDS_STEPS: usize = 32;
+
+// Object to world transform along with its inverse
+#[derive(Debug, Clone)]
+pub struct Transform {
+    to_world: Matrix<Local, World>,
+    to_local: Matrix<World, Local>,
+}
+
+impl Transform {
+    pub fn new(to_world: Matrix<Local, World>) -> Self {
+        Self {
+            to_local: to_world.inverse(),
+            to_world,
+        }
+    }
+
+    pub fn point_to_world(&self, p: Point3<Local>) -> Point3 {
+        &self.to_world * p
+    }
+
+    pub fn point_to_local(&self, p: Point3) -> Point3<Local> {
+        &self.to_local * p
+    }
+
+    pub fn vector_to_world(&self, v: Vec3<Local>) -> Vec3 {
+        &self.to_world * v
+    }
+
+    pub fn vector_to_local(&self, v: Vec3) -> Vec3<Local> {
+        &self.to_local * v
+    }
+
+    // Normals transform by the inverse transpose to stay perpendicular to the
+    // surface under non-uniform scaling
+    pub fn normal_to_world(&self, n: Vec3<Local>) -> Vec3 {
+        (&self.to_local.transpose() * n).normalize()
+    }
+
+    pub fn normal_to_local(&self, n: Vec3) -> Vec3<Local> {
+        (&self.to_world.transpose() * n).normalize()
+    }
+
+    pub fn bounds_to_world(&self, bounds: Aabb<Local>) -> Aabb {
+        corners(bounds)
+            .iter()
+            .fold(Aabb::empty(), |b, &p| b.union_point(self.point_to_world(p)))
+    }
+
+    // Distances along the returned ray are in local units, divide them by the
+    // scale to get back world distances
+    pub fn ray_to_local(&self, ray: &Ray) -> (Ray<Local>, f32) {
+        let scale = self.vector_to_local(ray.d()).len();
+        (&self.to_local * ray.clone(), scale)
+    }
+}
+
+// Translation, rotation and scale at a point in time, applied to the object in
+// the reverse order
+#[derive(Debug, Clone)]
+pub struct Keyframe {
+    pub time: f32,
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn transform(&self) -> Transform {
+        let Self {
+            translation,
+            rotation,
+            scale,
+            ..
+        } = *self;
+
+        let to_world = &(&Matrix::<Local, World>::translation(translation)
+            * &rotation.to_matrix::<Local, Local>())
+            * &Matrix::<Local, Local>::scale(scale);
+
+        // Cheaper and more accurate than inverting to_world
+        let inv_scale = Vec3::new(1.0 / scale.x(), 1.0 / scale.y(), 1.0 / scale.z());
+        let to_local = &(&Matrix::<Local, Local>::scale(inv_scale)
+            * &rotation.conjugate().to_matrix::<Local, Local>())
+            * &Matrix::<World, Local>::translation(-translation);
+
+        Transform { to_world, to_local }
+    }
+}
+
+// Object to world transform interpolated between keyframes, translation and
+// scale linearly and rotation with slerp. Before the first and after the last
+// keyframe the object stays put
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform {
+    // Sorted by time
+    keyframes: Vec<Keyframe>,
+}
+
+impl AnimatedTransform {
+    pub fn new(mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(
+            !keyframes.is_empty(),
+            "animated transform without keyframes"
+        );
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { keyframes }
+    }
+
+    pub fn at(&self, time: f32) -> Transform {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keyframes[0].transform();
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].transform();
+        }
+
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        Keyframe {
+            time,
+            translation: (1.0 - t) * a.translation + t * b.translation,
+            rotation: Quaternion::slerp(t, a.rotation, b.rotation),
+            scale: (1.0 - t) * a.scale + t * b.scale,
+        }
+        .transform()
+    }
+
+    // Bounds of the local bounds swept over all keyframes. Rotating corners move
+    // along arcs between the steps, which bulge out from the chords by less than
+    // half the distance they travel
+    pub fn motion_bounds(&self, bounds: Aabb<Local>) -> Aabb {
+        let corners = corners(bounds);
+        let transformed = |time: f32| {
+            let transform = self.at(time);
+            corners
+                .iter()
+                .map(|&p| transform.point_to_world(p))
+                .collect::<Vec<_>>()
+        };
+
+        let first = self.keyframes[0].time;
+        let mut previous = transformed(first);
+        let mut motion_bounds = previous
+            .iter()
+            .fold(Aabb::empty(), |b, &p| b.union_point(p));
+
+        for pair in self.keyframes.windows(2) {
+            for step in 1..=BOUNDS_STEPS {
+                let t = step as f32 / BOUNDS_STEPS as f32;
+                let current = transformed((1.0 - t) * pair[0].time + t * pair[1].time);
+
+                for (&a, &b) in previous.iter().zip(&current) {
+                    let pad = Vec3::splat(a.distance(b) / 2.0);
+                    motion_bounds = motion_bounds.union(Aabb::new(a - pad, a + pad));
+                    motion_bounds = motion_bounds.union(Aabb::new(b - pad, b + pad));
+                }
+                previous = current;
+            }
+        }
+
+        motion_bounds
+    }
+}
+
+// Object to world transform of a primitive, fixed or moving over time
+#[derive(Debug, Clone)]
+pub enum ObjectTransform {
+    Static(Arc<Transform>),
+    Animated(Arc<AnimatedTransform>),
+}
+
+impl ObjectTransform {
+    pub fn at(&self, time: f32) -> Cow<'_, Transform> {
+        match self {
+            Self::Static(transform) => Cow::Borrowed(transform),
+            Self::Animated(motion) => Cow::Owned(motion.at(time)),
+        }
+    }
+
+    // World bounds of the local bounds at any time
+    pub fn bounds(&self, bounds: Aabb<Local>) -> Aabb {
+        match self {
+            Self::Static(transform) => transform.bounds_to_world(bounds),
+            Self::Animated(motion) => motion.motion_bounds(bounds),
+        }
+    }
+}
+
+fn corners(bounds: Aabb<Local>) -> [Point3<Local>; 8] {
+    let extremes = [bounds.min, bounds.max];
+    let mut corners = [bounds.min; 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        *corner = Point3::new(
+            extremes[i & 1].x(),
+            extremes[(i >> 1) & 1].y(),
+            extremes[i >> 2].z(),
+        );
+    }
+
+    corners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_animated_transform() {
+        let motion = AnimatedTransform::new(vec![
+            Keyframe {
+                time: 1.0,
+                translation: Vec3::new(2.0, 0.0, 0.0),
+                rotation: Quaternion::from_axis_angle(
+                    Vec3::<World>::new(0.0, 1.0, 0.0),
+                    90f32.to_radians(),
+                ),
+                scale: Vec3::splat(2.0),
+            },
+            Keyframe {
+                time: 0.0,
+                translation: Vec3::splat(0.0),
+                rotation: Quaternion::id(),
+                scale: Vec3::splat(1.0),
+            },
+        ]);
+
+        let p = Point3::new(1.0, 0.0, 0.0);
+        assert!(
+            motion
+                .at(-1.0)
+                .point_to_world(p)
+                .distance(Point3::new(1.0, 0.0, 0.0))
+                < 1e-5
+        );
+        // Scaled by 2, turned to -z and moved by 2
+        let end = motion.at(2.0);
+        assert!(end.point_to_world(p).distance(Point3::new(2.0, 0.0, -2.0)) < 1e-5);
+        assert!(end.point_to_local(Point3::new(2.0, 0.0, -2.0)).distance(p) < 1e-5);
+
+        // Halfway, scaled by 1.5 and turned by 45 degrees
+        let half = motion.at(0.5).point_to_world(p);
+        let expected = Point3::new(1.0, 0.0, 0.0) + 1.5 * Vec3::new(1.0, 0.0, -1.0).normalize();
+        assert!(half.distance(expected) < 1e-5);
+
+        // The corner of the box sweeps out a quarter circle of radius up to 2
+        let bounds = motion.motion_bounds(Aabb::new(Point3::splat(0.0), p));
+        for i in 0..=100 {
+            let q = motion.at(i as f32 / 100.0).point_to_world(p);
+            assert!(bounds.union_point(q) == bounds);
+        }
+    }
+}
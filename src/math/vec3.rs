@@ -42,6 +42,11 @@ impl<S> Vec3<S> {
         self.inner.z()
     }
 
+    pub fn get(self, axis: usize) -> f32 {
+        debug_assert!(axis < 3, "invalid axis {}", axis);
+        self.inner.data[axis]
+    }
+
     pub fn dot(self, other: Self) -> f32 {
         // TODO: Optimize this
         self.inner.x() * other.inner.x() + self.inner.y() * other.inner.y() + self.inner.z() * other.inner.z()
@@ -0,0 +1,177 @@
+// Piecewise-constant distributions, see
+// https://pbr-book.org/3ed-2018/Monte_Carlo_Integration/Sampling_Random_Variables
+// https://pbr-book.org/3ed-2018/Monte_Carlo_Integration/2D_Sampling_with_Multidimensional_Transformations
+
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    func: Vec<f32>,
+    cdf: Vec<f32>,
+    integral: f32,
+}
+
+impl Distribution1D {
+    pub fn new(func: &[f32]) -> Self {
+        assert!(!func.is_empty());
+        let n = func.len();
+
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for (i, f) in func.iter().enumerate() {
+            debug_assert!(*f >= 0.0);
+            cdf.push(cdf[i] + f / n as f32);
+        }
+
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            // Sample uniformly when the function is zero everywhere
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f32 / n as f32
+            };
+        }
+
+        Self {
+            func: func.to_vec(),
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    // Returns the sampled position in [0, 1), its pdf and the index of its segment
+    pub fn sample_continuous(&self, u: f32) -> (f32, f32, usize) {
+        // Last segment whose cdf is <= u, skipping empty segments
+        let offset = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.count() - 1);
+
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            ((u - self.cdf[offset]) / width).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let x = ((offset as f32 + du) / self.count() as f32).min(1.0 - f32::EPSILON);
+        (x, self.pdf(offset), offset)
+    }
+
+    pub fn pdf(&self, offset: usize) -> f32 {
+        if self.integral > 0.0 {
+            self.func[offset] / self.integral
+        } else {
+            1.0
+        }
+    }
+
+    // Index of the segment containing x in [0, 1]
+    pub fn offset(&self, x: f32) -> usize {
+        ((x * self.count() as f32) as usize).min(self.count() - 1)
+    }
+}
+
+// Samples (u, v) in [0, 1)^2 with a density proportional to func, which is
+// stored row by row (v major)
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    conditionals: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f32], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+
+        let conditionals = func
+            .chunks_exact(width)
+            .map(Distribution1D::new)
+            .collect::<Vec<_>>();
+        let marginal = Distribution1D::new(
+            &conditionals
+                .iter()
+                .map(Distribution1D::integral)
+                .collect::<Vec<_>>(),
+        );
+
+        Self {
+            conditionals,
+            marginal,
+        }
+    }
+
+    pub fn sample_continuous(&self, u0: f32, u1: f32) -> ((f32, f32), f32) {
+        let (v, pdf_v, row) = self.marginal.sample_continuous(u1);
+        let (u, pdf_u, _) = self.conditionals[row].sample_continuous(u0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, (u, v): (f32, f32)) -> f32 {
+        let row = self.marginal.offset(v);
+        let conditional = &self.conditionals[row];
+        conditional.pdf(conditional.offset(u)) * self.marginal.pdf(row)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::Sampler;
+
+    #[test]
+    fn test_sample_1d() {
+        let dist = Distribution1D::new(&[0.0, 1.0, 3.0, 0.0]);
+        assert_eq!(dist.integral(), 1.0);
+
+        // Empty segments are never sampled
+        for i in 0..100 {
+            let (x, pdf, offset) = dist.sample_continuous(i as f32 / 100.0);
+            assert!(offset == 1 || offset == 2);
+            assert_eq!(dist.offset(x), offset);
+            assert_eq!(pdf, dist.pdf(offset));
+        }
+
+        assert_eq!(dist.sample_continuous(0.24).2, 1);
+        assert_eq!(dist.sample_continuous(0.26).2, 2);
+        assert_eq!(dist.pdf(2), 3.0);
+    }
+
+    #[test]
+    fn test_zero_function() {
+        let dist = Distribution1D::new(&[0.0; 4]);
+        let (x, pdf, offset) = dist.sample_continuous(0.6);
+        assert!((x - 0.6).abs() < 1e-6);
+        assert_eq!((pdf, offset), (1.0, 2));
+    }
+
+    #[test]
+    fn test_sample_2d_pdf() {
+        let func = [1.0, 2.0, 0.0, 4.0, 0.5, 0.0, 3.0, 1.0, 2.0];
+        let dist = Distribution2D::new(&func, 3, 3);
+
+        let mut integral = 0.0;
+        for i in 0..3 {
+            for j in 0..3 {
+                let uv = ((i as f32 + 0.5) / 3.0, (j as f32 + 0.5) / 3.0);
+                integral += dist.pdf(uv) / 9.0;
+            }
+        }
+        assert!((integral - 1.0).abs() < 1e-5);
+
+        for i in 0..256 {
+            let mut sampler = Sampler::new(0, 0, i, 0);
+            let (uv, pdf) = dist.sample_continuous(sampler.gen_0_1(), sampler.gen_0_1());
+            assert!(pdf > 0.0);
+            assert_eq!(pdf, dist.pdf(uv));
+        }
+    }
+}
@@ -4,7 +4,12 @@ pub fn balance_heuristic_1(f: PdfSet) -> f32 {
     f.hero() / f.sum()
 }
 
-// TODO: Power heuristic
 pub fn balance_heuristic_2(f: PdfSet, g: PdfSet) -> f32 {
     f.hero() / (f + g).sum()
 }
+
+// Power heuristic with an exponent of 2, for two strategies taking one sample
+// each
+pub fn power_heuristic(f: f32, g: f32) -> f32 {
+    f * f / (f * f + g * g)
+}
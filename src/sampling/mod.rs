@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+pub mod distribution;
 pub mod ggx;
 pub mod mis;
 
@@ -30,7 +31,7 @@ pub fn unit_sphere<S>(r1: f32, r2: f32) -> Vec3<S> {
     Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
-fn concentric_disk(r1: f32, r2: f32) -> (f32, f32) {
+pub fn concentric_disk(r1: f32, r2: f32) -> (f32, f32) {
     let x_off = 2.0 * r1 - 1.0;
     let y_off = 2.0 * r2 - 1.0;
 
@@ -47,6 +48,22 @@ fn concentric_disk(r1: f32, r2: f32) -> (f32, f32) {
     (r * theta.cos(), r * theta.sin())
 }
 
+// Uniformly distributed point in a regular polygon inscribed in the unit circle
+pub fn regular_polygon(r1: f32, r2: f32, sides: u32, rotation: f32) -> (f32, f32) {
+    // Pick one of the triangles fanning out from the center and reuse r1
+    let scaled = r1 * sides as f32;
+    let i = (scaled as u32).min(sides - 1);
+    let r1 = scaled - i as f32;
+
+    let wedge = 2.0 * PI / sides as f32;
+    let (sin_a, cos_a) = (rotation + i as f32 * wedge).sin_cos();
+    let (sin_b, cos_b) = (rotation + (i + 1) as f32 * wedge).sin_cos();
+
+    let s = r1.sqrt();
+    let (a, b) = (s * (1.0 - r2), s * r2);
+    (a * cos_a + b * cos_b, a * sin_a + b * sin_b)
+}
+
 pub fn cosine_unit_hemisphere<S>(r1: f32, r2: f32) -> Vec3<S> {
     let (x, y) = concentric_disk(r1, r2);
     let z = (1.0 - x * x - y * y).max(0.0).sqrt();
@@ -26,7 +26,7 @@ impl Sampler {
             self.sample_buffer[self.samples_in_buffer]
         } else if self.samples_in_buffer == 0 {
             // Buffer empty, refill
-            self.sample_buffer = if self.dimension < sobol::NUM_DIMENSIONS {
+            self.sample_buffer = if self.dimension < sobol::NUM_DIMENSION_SETS_4D {
                 sobol::sample_4d(
                     self.index,
                     self.dimension,
@@ -56,7 +56,7 @@ impl Sampler {
 
     pub fn gen_array_index(&mut self, len: usize) -> usize {
         debug_assert!(len > 0);
-        self.gen_range(0.0, len as f32 - 0.5) as usize
+        ((self.gen_0_1() * len as f32) as usize).min(len - 1)
     }
 
     // https://www.graphics.rwth-aachen.de/publication/2/jgt.pdf
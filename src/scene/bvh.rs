@@ -0,0 +1,386 @@
+// Bounding volume hierarchy built with the surface area heuristic, see
+// https://pbr-book.org/3ed-2018/Primitives_and_Intersection_Acceleration/Bounding_Volume_Hierarchies
+use crate::{
+    math::{Aabb, Point3, Ray},
+    shape::{Intersection, Primitive, Shape},
+};
+
+const MAX_PRIMS_IN_LEAF: usize = 4;
+const NUM_BUCKETS: usize = 12;
+// Relative to the cost of a ray-primitive intersection
+const TRAVERSAL_COST: f32 = 0.125;
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    // Primitive indices, ordered so that every leaf references a contiguous range
+    prim_indices: Vec<u32>,
+}
+
+#[derive(Debug)]
+struct Node {
+    bounds: Aabb,
+    // Leaves: index of the first primitive in prim_indices
+    // Interior nodes: index of the second child, the first child directly follows its parent
+    offset: u32,
+    num_prims: u32,
+    axis: u8,
+}
+
+struct BuildPrim {
+    index: u32,
+    bounds: Aabb,
+    centroid: Point3,
+}
+
+impl Bvh {
+    pub fn new(primitives: &[Primitive]) -> Self {
+        let mut build_prims = primitives
+            .iter()
+            .enumerate()
+            .map(|(index, prim)| {
+                let bounds = prim.bounds();
+                BuildPrim {
+                    index: index as u32,
+                    bounds,
+                    centroid: bounds.centroid(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * primitives.len()),
+            prim_indices: Vec::with_capacity(primitives.len()),
+        };
+
+        if !build_prims.is_empty() {
+            bvh.build(&mut build_prims, 0);
+        }
+
+        bvh
+    }
+
+    // Returns the index of the created node
+    fn build(&mut self, prims: &mut [BuildPrim], depth: usize) -> usize {
+        let bounds = prims
+            .iter()
+            .fold(Aabb::empty(), |acc, prim| acc.union(prim.bounds));
+        let centroid_bounds = prims
+            .iter()
+            .fold(Aabb::empty(), |acc, prim| acc.union_point(prim.centroid));
+
+        let node_index = self.nodes.len();
+        let axis = centroid_bounds.max_extent();
+        let is_flat = centroid_bounds.max.get(axis) == centroid_bounds.min.get(axis);
+
+        let split = if prims.len() <= MAX_PRIMS_IN_LEAF || is_flat || depth >= MAX_DEPTH {
+            None
+        } else {
+            sah_split(prims, bounds, centroid_bounds, axis)
+        };
+
+        match split {
+            None => {
+                self.nodes.push(Node {
+                    bounds,
+                    offset: self.prim_indices.len() as u32,
+                    num_prims: prims.len() as u32,
+                    axis: axis as u8,
+                });
+                self.prim_indices
+                    .extend(prims.iter().map(|prim| prim.index));
+            }
+            Some(mid) => {
+                self.nodes.push(Node {
+                    bounds,
+                    offset: 0,
+                    num_prims: 0,
+                    axis: axis as u8,
+                });
+
+                let (left, right) = prims.split_at_mut(mid);
+                self.build(left, depth + 1);
+                let second_child = self.build(right, depth + 1);
+                self.nodes[node_index].offset = second_child as u32;
+            }
+        }
+
+        node_index
+    }
+
+    // Bounds of everything in the hierarchy
+    pub fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map_or_else(Aabb::empty, |root| root.bounds)
+    }
+
+    pub fn intersect<'a>(
+        &self,
+        primitives: &'a [Primitive],
+        ray: &Ray,
+    ) -> Option<(&'a Primitive, Intersection, f32)> {
+        let mut closest = None;
+
+        // Shrinking t_max culls both the nodes and the primitives behind the closest
+        // hit
+        self.traverse(ray.clone(), |ray, prim_index| {
+            let prim = &primitives[prim_index as usize];
+            if let Some((hit, t)) = prim.intersect(ray) {
+                *ray = ray.clone().with_t_max(t);
+                closest = Some((prim, hit, t));
+            }
+
+            false
+        });
+
+        closest
+    }
+
+    // Any-hit query for shadow rays, stops at the first primitive hit within the
+    // ray's range
+    pub fn occluded(&self, primitives: &[Primitive], ray: &Ray) -> bool {
+        let mut occluded = false;
+
+        self.traverse(ray.clone(), |ray, prim_index| {
+            occluded = primitives[prim_index as usize].intersects(ray);
+            occluded
+        });
+
+        occluded
+    }
+
+    // Calls visit for every primitive in the leaves hit by the ray, until it
+    // returns true
+    fn traverse(&self, mut ray: Ray, mut visit: impl FnMut(&mut Ray, u32) -> bool) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let d = ray.d();
+        let inv_d = [1.0 / d.x(), 1.0 / d.y(), 1.0 / d.z()];
+        let dir_is_neg = [d.x() < 0.0, d.y() < 0.0, d.z() < 0.0];
+
+        let mut stack = [0usize; MAX_DEPTH + 1];
+        let mut stack_len = 0;
+        let mut node_index = 0;
+
+        loop {
+            let node = &self.nodes[node_index];
+
+            if node.bounds.hit(&ray, inv_d) {
+                if node.num_prims > 0 {
+                    let start = node.offset as usize;
+                    for &prim_index in &self.prim_indices[start..start + node.num_prims as usize] {
+                        if visit(&mut ray, prim_index) {
+                            return;
+                        }
+                    }
+                } else {
+                    // Visit the nearest child first so that closest hits shrink t_max sooner
+                    let (near, far) = if dir_is_neg[node.axis as usize] {
+                        (node.offset as usize, node_index + 1)
+                    } else {
+                        (node_index + 1, node.offset as usize)
+                    };
+
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    node_index = near;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+
+            stack_len -= 1;
+            node_index = stack[stack_len];
+        }
+    }
+}
+
+// Returns the number of primitives in the first half (after partitioning
+// prims), or None if it is cheaper to create a leaf
+fn sah_split(
+    prims: &mut [BuildPrim],
+    bounds: Aabb,
+    centroid_bounds: Aabb,
+    axis: usize,
+) -> Option<usize> {
+    let bucket_of = |prim: &BuildPrim| {
+        let b = (NUM_BUCKETS as f32 * centroid_bounds.offset(prim.centroid, axis)) as usize;
+        b.min(NUM_BUCKETS - 1)
+    };
+
+    let mut counts = [0usize; NUM_BUCKETS];
+    let mut bucket_bounds = [Aabb::empty(); NUM_BUCKETS];
+    for prim in prims.iter() {
+        let b = bucket_of(prim);
+        counts[b] += 1;
+        bucket_bounds[b] = bucket_bounds[b].union(prim.bounds);
+    }
+
+    // Cost of splitting after each bucket
+    let mut best_cost = f32::INFINITY;
+    let mut best_split = 0;
+    for split in 0..NUM_BUCKETS - 1 {
+        let (left, right) = (0..=split, split + 1..NUM_BUCKETS);
+        let left_bounds = bucket_bounds[left.clone()]
+            .iter()
+            .fold(Aabb::empty(), |acc, b| acc.union(*b));
+        let right_bounds = bucket_bounds[right.clone()]
+            .iter()
+            .fold(Aabb::empty(), |acc, b| acc.union(*b));
+        let left_count = counts[left].iter().sum::<usize>();
+        let right_count = counts[right].iter().sum::<usize>();
+
+        let cost = TRAVERSAL_COST
+            + (left_count as f32 * left_bounds.surface_area()
+                + right_count as f32 * right_bounds.surface_area())
+                / bounds.surface_area();
+
+        if cost < best_cost {
+            best_cost = cost;
+            best_split = split;
+        }
+    }
+
+    let leaf_cost = prims.len() as f32;
+    if best_cost >= leaf_cost {
+        return None;
+    }
+
+    // Partition
+    let mut mid = 0;
+    for i in 0..prims.len() {
+        if bucket_of(&prims[i]) <= best_split {
+            prims.swap(i, mid);
+            mid += 1;
+        }
+    }
+
+    if mid == 0 || mid == prims.len() {
+        // Degenerate split, fall back to splitting in the middle along the axis
+        let mid = prims.len() / 2;
+        prims.select_nth_unstable_by(mid, |a, b| {
+            a.centroid
+                .get(axis)
+                .partial_cmp(&b.centroid.get(axis))
+                .unwrap()
+        });
+        return Some(mid);
+    }
+
+    Some(mid)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        math::Vec3,
+        sampling::Sampler,
+        shape::{Geometry, Mesh, Sphere},
+    };
+
+    fn brute_force<'a>(primitives: &'a [Primitive], ray: &Ray) -> Option<(&'a Primitive, f32)> {
+        let mut closest = None;
+        let mut closest_t = f32::INFINITY;
+
+        for prim in primitives {
+            match prim.intersect(ray) {
+                Some((_, t)) if t < closest_t => {
+                    closest_t = t;
+                    closest = Some((prim, t));
+                }
+                _ => continue,
+            }
+        }
+
+        closest
+    }
+
+    fn random_point(sampler: &mut Sampler, scale: f32) -> Point3 {
+        Point3::new(
+            sampler.gen_range(-scale, scale),
+            sampler.gen_range(-scale, scale),
+            sampler.gen_range(-scale, scale),
+        )
+    }
+
+    fn random_scene(seed: u32, num_spheres: usize, num_triangles: usize) -> Vec<Primitive> {
+        // A sampler only provides a limited number of dimensions, use one per item
+        let mut primitives = Vec::new();
+
+        for i in 0..num_spheres {
+            let mut sampler = Sampler::new(0, 0, i, seed);
+            let sphere = Sphere::new(
+                random_point(&mut sampler, 10.0),
+                sampler.gen_range(0.1, 1.0),
+            );
+            primitives.push(Primitive::new_material(sphere.into(), 0));
+        }
+
+        let positions = (0..3 * num_triangles)
+            .map(|i| random_point(&mut Sampler::new(1, 0, i, seed), 10.0))
+            .collect();
+        let indices = (0..3 * num_triangles as u32).collect();
+        let mesh = Arc::new(Mesh::new(positions, Vec::new(), Vec::new(), indices));
+        primitives.extend(
+            mesh.triangles()
+                .map(|triangle| Primitive::new_material(Geometry::from(triangle), 0)),
+        );
+
+        primitives
+    }
+
+    #[test]
+    fn test_bvh_matches_brute_force() {
+        for seed in 0..4 {
+            let primitives = random_scene(seed, 100, 200);
+            let bvh = Bvh::new(&primitives);
+
+            for i in 0..1000 {
+                let mut sampler = Sampler::new(2, 0, i, seed);
+                let o = random_point(&mut sampler, 12.0);
+                let target = random_point(&mut sampler, 8.0);
+                let ray = Ray::new(o, target - o);
+
+                let expected = brute_force(&primitives, &ray);
+                let actual = bvh.intersect(&primitives, &ray);
+
+                // Shadow ray towards the target, which is occluded if anything lies in between
+                let shadow_ray = Ray::spawn_to(o, target, Vec3::new(0.0, 0.0, 1.0));
+                assert_eq!(
+                    bvh.occluded(&primitives, &shadow_ray),
+                    brute_force(&primitives, &shadow_ray).is_some()
+                );
+
+                match (expected, actual) {
+                    (None, None) => {}
+                    (Some((prim_a, t_a)), Some((prim_b, _, t_b))) => {
+                        assert!(std::ptr::eq(prim_a, prim_b));
+                        assert_eq!(t_a, t_b);
+                    }
+                    (a, b) => panic!(
+                        "brute force hit {:?} but bvh hit {:?}",
+                        a.map(|(_, t)| t),
+                        b.map(|(_, _, t)| t)
+                    ),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let bvh = Bvh::new(&[]);
+        let ray = Ray::new(Point3::splat(0.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(bvh.intersect(&[], &ray).is_none());
+    }
+}
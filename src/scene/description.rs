@@ -0,0 +1,2352 @@
+// Scene description files, written in RON (https://github.com/ron-rs/ron)
+//
+// Example:
+//
+// Scene(
+//     render: (
+//         width: 512,
+//         height: 512,
+//         spp: 100,
+//         filter: Gaussian(),
+//         // Optional, spp becomes the maximum
+//         adaptive: (threshold: 0.01, min_spp: 16, time_limit: 60.0),
+//     ),
+//     camera: (
+//         position: (0.0, 0.0, 0.0),
+//         look_at: (0.0, 0.0, 1.0),
+//         up: (0.0, 1.0, 0.0),
+//         fov: 90.0,
+//         // Depth of field, focus_distance defaults to the distance to look_at
+//         aperture_radius: 0.05,
+//         focus_distance: 3.0,
+//         aperture: Polygon(blades: 6, rotation: 0.0),
+//         // Or trace rays through a lens prescription, which replaces fov and
+//         // the aperture
+//         lens: (path: "lenses/dgauss.50mm.dat", aperture_diameter: 10.0),
+//         // Or one of Orthographic(height: 2.0), Fisheye(fov: 180.0) and
+//         // Equirectangular, which don't take a fov, aperture or lens
+//         projection: Perspective,
+//         // Open and close times, objects with motion are blurred over it
+//         shutter: (0.0, 1.0),
+//     ),
+//     // Equirectangular EXR, +y up
+//     environment: (path: "sky.exr", strength: 1.0),
+//     objects: [
+//         (
+//             shape: Sphere(center: (0.0, 2.3, 3.0), radius: 1.0),
+//             material: Lambertian(albedo: Constant(0.5)),
+//             // Spectra are one of Constant(3.0), Rgb(..), Tabulated(path:
+//             // "spectra/led.csv", scale: 1.0) from measured data,
+//             // Blackbody(temperature: 5500.0, scale: 3.0) and
+//             // Illuminant(name: D65, scale: 3.0) with one of D65, D50, A,
+//             // F2, F7 and F11. Blackbody and Illuminant have the luminance
+//             // of Constant(scale)
+//             emission: Blackbody(temperature: 5500.0, scale: 3.0),
+//             // Keyframes of the object to world transform, interpolated
+//             // over time
+//             motion: [
+//                 (time: 0.0),
+//                 (
+//                     time: 1.0,
+//                     translation: (0.5, 0.0, 0.0),
+//                     rotation: (axis: (0.0, 1.0, 0.0), angle: 90.0),
+//                     scale: (1.0, 1.0, 1.0),
+//                 ),
+//             ],
+//         ),
+//         (
+//             shape: Obj(path: "models/room.obj"),
+//             // Translation, rotation and scale, applied in reverse order
+//             transform: (
+//                 translation: (0.0, -1.0, 0.0),
+//                 scale: (2.0, 2.0, 2.0),
+//             ),
+//             material: Lambertian(albedo: Constant(0.8)),
+//             // Per-group (or per-usemtl) overrides of the default material
+//             materials: {
+//                 "floor": Lambertian(albedo: Rgb(0.2, 0.3, 0.7)),
+//                 // Material colors can also be textures over the uvs, one
+//                 // of Image(path: .., filter: Ewa), Checkerboard(even: ..,
+//                 // odd: .., scale: 8.0), Noise(low: .., high: .., scale:
+//                 // 4.0, octaves: 4) and Gradient(start: .., end: .., axis:
+//                 // V). Images are filtered with Ewa, Trilinear or Bilinear.
+//                 // Spectrum(..) wraps any other spectrum, like measured
+//                 // reflectances
+//                 "rug": Lambertian(albedo: Image(path: "textures/rug.png")),
+//                 // Glass, the ior is one of Bk7, Sf11, FusedSilica,
+//                 // Constant(1.5), Cauchy(a: 1.5, b: 0.004) and
+//                 // Sellmeier(b: (..), c: (..))
+//                 "window": Dielectric(ior: Bk7),
+//                 // Frosted glass, with GGX roughness like Microfacet
+//                 "shade": RoughDielectric(ior: Bk7, roughness: (0.3, 0.3)),
+//                 // Metal, the ior is one of Gold, Silver, Copper, Aluminium,
+//                 // Chromium and Complex(eta: .., k: ..)
+//                 "handle": Conductor(ior: Gold),
+//                 "pan": RoughConductor(ior: Aluminium, roughness: (0.2, 0.2)),
+//                 // Dielectric coating over any other material, optionally
+//                 // rough and absorbing light over its thickness. Smooth
+//                 // coatings can't be lit by light sampling, so are noisier
+//                 "table": Layered(
+//                     base: Lambertian(albedo: Rgb(0.5, 0.3, 0.1)),
+//                     ior: Constant(1.5),
+//                     roughness: (0.05, 0.05),
+//                     thickness: 0.1,
+//                     absorption: Rgb(0.2, 0.5, 0.9),
+//                 ),
+//                 // One material with a few artist-friendly parameters, all
+//                 // optional and in [0, 1] but for the base color and ior
+//                 "chair": Principled(
+//                     base_color: Rgb(0.6, 0.1, 0.1),
+//                     metallic: 0.0,
+//                     roughness: 0.4,
+//                     specular: 0.5,
+//                     sheen: 0.2,
+//                     clearcoat: 1.0,
+//                     clearcoat_roughness: 0.03,
+//                     transmission: 0.0,
+//                     ior: Constant(1.5),
+//                 ),
+//                 // Surface detail from a tangent-space normal map, or
+//                 // Bump(base: .., path: .., strength: 0.01) from heights
+//                 "wall": NormalMap(
+//                     base: Lambertian(albedo: Constant(0.7)),
+//                     path: "textures/bricks_normal.png",
+//                 ),
+//             },
+//         ),
+//         // One copy of a shape from instances, with its own transform and
+//         // material but sharing the geometry (can't be emissive)
+//         (
+//             shape: Instance("tree"),
+//             material: Lambertian(albedo: Rgb(0.2, 0.6, 0.1)),
+//             transform: (translation: (4.0, 0.0, 5.0)),
+//         ),
+//     ],
+//     // Shapes only stored once, however many objects place them
+//     instances: {
+//         "tree": Obj(path: "models/tree.obj"),
+//     },
+// )
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    bsdf::{
+        Bsdf,
+        ConductorBsdf,
+        DielectricBsdf,
+        LambertianBsdf,
+        LayeredBsdf,
+        MicrofacetBsdf,
+        NormalMappedBsdf,
+        PrincipledBsdf,
+        PrincipledParameters,
+        RoughConductorBsdf,
+        RoughDielectricBsdf,
+        SpecularBsdf,
+    },
+    camera::{
+        self,
+        Aperture,
+        Camera,
+        EquirectangularCamera,
+        FisheyeCamera,
+        OrthographicCamera,
+        PerspectiveCamera,
+        RealisticCamera,
+    },
+    filter::{BlackmanHarrisFilter, BoxFilter, Filter, GaussianFilter, MitchellFilter, TentFilter},
+    math::{
+        AnimatedTransform,
+        Camera as CameraCoord,
+        Keyframe,
+        Matrix,
+        ObjectTransform,
+        Point3,
+        Quaternion,
+        Transform,
+        Vec3,
+        World,
+    },
+    scene::{EnvironmentMap, Scene},
+    shape::{Geometry, Instance, InstancedGeometry, Mesh, MeshGroup, Primitive, Sphere},
+    spectrum::{
+        upsample::UpsampleTable,
+        wavelength::{LAMBDA_MAX_NM, LAMBDA_MIN_NM},
+        BlackbodySpectrum,
+        ComplexIor,
+        ConstantSpectrum,
+        Illuminant,
+        Ior,
+        Metal,
+        Spectrum,
+        TabulatedSpectrum,
+    },
+    texture::{
+        CheckerboardTexture,
+        GradientAxis,
+        GradientTexture,
+        ImageFilter,
+        ImageTexture,
+        NoiseTexture,
+        NormalMap,
+        RgbImage,
+        Texture,
+    },
+    tile::AdaptiveSampling,
+};
+
+type Triple = (f32, f32, f32);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "Scene", deny_unknown_fields)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default)]
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub environment: Option<EnvironmentDescription>,
+    pub objects: Vec<ObjectDescription>,
+    #[serde(default)]
+    pub instances: HashMap<String, ShapeDescription>,
+    // Relative paths (e.g. meshes) are resolved against the scene file's directory
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub spp: usize,
+    pub filter: FilterDescription,
+    pub adaptive: Option<AdaptiveDescription>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 512,
+            height: 512,
+            spp: 100,
+            filter: FilterDescription::Gaussian {
+                radius: None,
+                sigma: None,
+            },
+            adaptive: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveDescription {
+    pub threshold: f32,
+    pub min_spp: usize,
+    // In seconds
+    pub time_limit: Option<f32>,
+}
+
+impl Default for AdaptiveDescription {
+    fn default() -> Self {
+        Self {
+            threshold: 0.01,
+            min_spp: 16,
+            time_limit: None,
+        }
+    }
+}
+
+impl AdaptiveDescription {
+    pub fn build(&self) -> AdaptiveSampling {
+        AdaptiveSampling {
+            threshold: self.threshold,
+            // The variance needs at least two samples
+            min_spp: self.min_spp.max(2),
+            time_limit: self
+                .time_limit
+                .map(|seconds| Duration::from_secs_f32(seconds.max(0.0))),
+        }
+    }
+}
+
+// Parameters that are left out use the filter's defaults
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum FilterDescription {
+    Box {
+        #[serde(default)]
+        radius: Option<f32>,
+    },
+    Tent {
+        #[serde(default)]
+        radius: Option<f32>,
+    },
+    Gaussian {
+        #[serde(default)]
+        radius: Option<f32>,
+        #[serde(default)]
+        sigma: Option<f32>,
+    },
+    Mitchell {
+        #[serde(default)]
+        radius: Option<f32>,
+        #[serde(default)]
+        b: Option<f32>,
+        #[serde(default)]
+        c: Option<f32>,
+    },
+    BlackmanHarris {
+        #[serde(default)]
+        radius: Option<f32>,
+    },
+}
+
+impl FilterDescription {
+    pub fn build(&self) -> Filter {
+        match *self {
+            FilterDescription::Box { radius } => {
+                let d = BoxFilter::default();
+                BoxFilter::new(radius.unwrap_or(d.radius)).into()
+            }
+            FilterDescription::Tent { radius } => {
+                let d = TentFilter::default();
+                TentFilter::new(radius.unwrap_or(d.radius)).into()
+            }
+            FilterDescription::Gaussian { radius, sigma } => {
+                let d = GaussianFilter::default();
+                GaussianFilter::new(radius.unwrap_or(d.radius), sigma.unwrap_or(d.sigma)).into()
+            }
+            FilterDescription::Mitchell { radius, b, c } => {
+                let d = MitchellFilter::default();
+                MitchellFilter::new(
+                    radius.unwrap_or(d.radius),
+                    b.unwrap_or(d.b),
+                    c.unwrap_or(d.c),
+                )
+                .into()
+            }
+            FilterDescription::BlackmanHarris { radius } => {
+                let d = BlackmanHarrisFilter::default();
+                BlackmanHarrisFilter::new(radius.unwrap_or(d.radius)).into()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawCameraDescription")]
+pub struct CameraDescription {
+    position: Triple,
+    look_at: Triple,
+    up: Triple,
+    fov: f32,
+    aperture_radius: f32,
+    focus_distance: f32,
+    aperture: ApertureDescription,
+    lens: Option<LensDescription>,
+    projection: ProjectionDescription,
+    shutter: (f32, f32),
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Camera", default, deny_unknown_fields)]
+struct RawCameraDescription {
+    position: Triple,
+    // Defaults to looking down +z
+    look_at: Option<Triple>,
+    up: Triple,
+    // Vertical, in degrees, defaults to 90
+    fov: Option<f32>,
+    // Zero for a pinhole camera
+    aperture_radius: f32,
+    // Defaults to the distance to look_at
+    focus_distance: Option<f32>,
+    aperture: ApertureDescription,
+    lens: Option<LensDescription>,
+    projection: ProjectionDescription,
+    // Open and close times, the same instant (no motion blur) by default
+    shutter: (f32, f32),
+}
+
+impl Default for RawCameraDescription {
+    fn default() -> Self {
+        Self {
+            position: (0.0, 0.0, 0.0),
+            look_at: None,
+            up: (0.0, 1.0, 0.0),
+            fov: None,
+            aperture_radius: 0.0,
+            focus_distance: None,
+            aperture: ApertureDescription::Circle,
+            lens: None,
+            projection: ProjectionDescription::Perspective,
+            shutter: (0.0, 0.0),
+        }
+    }
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        Self::try_from(RawCameraDescription::default()).unwrap()
+    }
+}
+
+impl TryFrom<RawCameraDescription> for CameraDescription {
+    type Error = String;
+
+    fn try_from(raw: RawCameraDescription) -> Result<Self, String> {
+        let (x, y, z) = raw.position;
+        let look_at = raw.look_at.unwrap_or((x, y, z + 1.0));
+        let distance = to_point(raw.position).distance(to_point(look_at));
+
+        if distance == 0.0 {
+            return Err("camera look_at must differ from its position".into());
+        }
+        let fov = raw.fov.unwrap_or(90.0);
+        if fov.is_nan() || fov <= 0.0 || fov >= 180.0 {
+            return Err(format!(
+                "camera fov must be between 0 and 180 degrees, got {}",
+                fov
+            ));
+        }
+        if raw.aperture_radius.is_nan() || raw.aperture_radius < 0.0 {
+            return Err(format!(
+                "camera aperture_radius must not be negative, got {}",
+                raw.aperture_radius
+            ));
+        }
+
+        let focus_distance = match raw.focus_distance {
+            Some(d) if d.is_nan() || d <= 0.0 => {
+                return Err(format!("camera focus_distance must be positive, got {}", d));
+            }
+            Some(d) => d,
+            None if raw.look_at.is_some() => distance,
+            None if raw.aperture_radius > 0.0 || raw.lens.is_some() => {
+                return Err("camera needs a focus_distance or look_at to focus on".into());
+            }
+            None => 1.0,
+        };
+
+        if let ApertureDescription::Polygon { blades, .. } = raw.aperture {
+            if blades < 3 {
+                return Err(format!("aperture needs at least 3 blades, got {}", blades));
+            }
+        }
+
+        if let Some(lens) = &raw.lens {
+            // The lens decides both
+            if raw.fov.is_some() || raw.aperture_radius > 0.0 {
+                return Err("camera with a lens can't have a fov or aperture_radius".into());
+            }
+            if lens.film_diagonal.is_nan() || lens.film_diagonal <= 0.0 {
+                return Err(format!(
+                    "lens film_diagonal must be positive, got {}",
+                    lens.film_diagonal
+                ));
+            }
+            if let Some(d) = lens.aperture_diameter.filter(|d| d.is_nan() || *d <= 0.0) {
+                return Err(format!(
+                    "lens aperture_diameter must be positive, got {}",
+                    d
+                ));
+            }
+        }
+
+        match raw.projection {
+            ProjectionDescription::Perspective => {}
+            _ if raw.fov.is_some() || raw.aperture_radius > 0.0 || raw.lens.is_some() => {
+                return Err(
+                    "camera fov, aperture_radius and lens only apply to the Perspective projection"
+                        .into(),
+                );
+            }
+            ProjectionDescription::Orthographic { height } if height.is_nan() || height <= 0.0 => {
+                return Err(format!(
+                    "orthographic height must be positive, got {}",
+                    height
+                ));
+            }
+            ProjectionDescription::Fisheye { fov } if fov.is_nan() || fov <= 0.0 || fov > 360.0 => {
+                return Err(format!(
+                    "fisheye fov must be between 0 and 360 degrees, got {}",
+                    fov
+                ));
+            }
+            _ => {}
+        }
+
+        let (open, close) = raw.shutter;
+        if !open.is_finite() || !close.is_finite() || open > close {
+            return Err(format!(
+                "camera shutter must open before it closes, got ({}, {})",
+                open, close
+            ));
+        }
+
+        Ok(Self {
+            position: raw.position,
+            look_at,
+            up: raw.up,
+            fov,
+            aperture_radius: raw.aperture_radius,
+            focus_distance,
+            aperture: raw.aperture,
+            lens: raw.lens,
+            projection: raw.projection,
+            shutter: raw.shutter,
+        })
+    }
+}
+
+impl CameraDescription {
+    pub fn shutter(&self) -> (f32, f32) {
+        self.shutter
+    }
+
+    // Relative lens paths are resolved against base_dir
+    pub fn build(&self, aspect_ratio: f32, base_dir: &Path) -> Result<Camera, SceneError> {
+        let (x, y, z) = self.up;
+        let camera_to_world = camera::look_at(
+            to_point(self.position),
+            to_point(self.look_at),
+            Vec3::new(x, y, z),
+        );
+
+        if let Some(lens) = &self.lens {
+            let path = base_dir.join(&lens.path);
+            return lens
+                .build(&path, camera_to_world, self.focus_distance, aspect_ratio)
+                .map_err(|e| SceneError::Lens(path, e));
+        }
+
+        let camera = match self.projection {
+            ProjectionDescription::Perspective => {
+                let camera = PerspectiveCamera::new(camera_to_world, self.fov, aspect_ratio);
+                if self.aperture_radius > 0.0 {
+                    camera
+                        .with_lens(
+                            self.aperture_radius,
+                            self.focus_distance,
+                            self.aperture.build(),
+                        )
+                        .into()
+                } else {
+                    camera.into()
+                }
+            }
+            ProjectionDescription::Orthographic { height } => {
+                OrthographicCamera::new(camera_to_world, height, aspect_ratio).into()
+            }
+            ProjectionDescription::Fisheye { fov } => {
+                FisheyeCamera::new(camera_to_world, fov, aspect_ratio).into()
+            }
+            ProjectionDescription::Equirectangular => {
+                EquirectangularCamera::new(camera_to_world).into()
+            }
+        };
+
+        Ok(camera)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LensDescription {
+    // See camera::parse_prescription for the format
+    pub path: PathBuf,
+    // In millimeters, defaults to 35mm film
+    #[serde(default = "default_film_diagonal")]
+    pub film_diagonal: f32,
+    // In millimeters, stops the lens down from its prescribed aperture
+    #[serde(default)]
+    pub aperture_diameter: Option<f32>,
+}
+
+fn default_film_diagonal() -> f32 {
+    43.27
+}
+
+impl LensDescription {
+    fn build(
+        &self,
+        path: &Path,
+        camera_to_world: Matrix<CameraCoord, World>,
+        focus_distance: f32,
+        aspect_ratio: f32,
+    ) -> Result<Camera, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut elements = camera::parse_prescription(&source)?;
+
+        if let Some(diameter) = self.aperture_diameter {
+            let stop = elements
+                .iter_mut()
+                .find(|element| element.is_stop())
+                .ok_or("the prescription has no aperture stop")?;
+            if diameter * 0.001 / 2.0 > stop.aperture_radius {
+                return Err(format!(
+                    "aperture_diameter {}mm is wider than the aperture stop",
+                    diameter
+                ));
+            }
+            stop.aperture_radius = diameter * 0.001 / 2.0;
+        }
+
+        let camera = RealisticCamera::new(
+            camera_to_world,
+            elements,
+            self.film_diagonal * 0.001,
+            focus_distance,
+            aspect_ratio,
+        )?;
+        Ok(camera.into())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum ProjectionDescription {
+    Perspective,
+    Orthographic {
+        // Of the film in world units, the width follows from the aspect ratio
+        height: f32,
+    },
+    Fisheye {
+        // Across the image circle, in degrees
+        #[serde(default = "default_fisheye_fov")]
+        fov: f32,
+    },
+    // Spans all directions, use a 2:1 aspect ratio
+    Equirectangular,
+}
+
+fn default_fisheye_fov() -> f32 {
+    180.0
+}
+
+// The shape of the bokeh
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum ApertureDescription {
+    Circle,
+    Polygon {
+        blades: u32,
+        // In degrees
+        #[serde(default)]
+        rotation: f32,
+    },
+}
+
+impl ApertureDescription {
+    fn build(&self) -> Aperture {
+        match *self {
+            ApertureDescription::Circle => Aperture::Circle,
+            ApertureDescription::Polygon { blades, rotation } => {
+                Aperture::Polygon { blades, rotation }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentDescription {
+    pub path: PathBuf,
+    // Multiplies the texel values
+    #[serde(default = "default_strength")]
+    pub strength: f32,
+}
+
+fn default_strength() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawObjectDescription")]
+pub struct ObjectDescription {
+    pub shape: ShapeDescription,
+    pub material: Option<MaterialDescription>,
+    pub emission: Option<SpectrumDescription>,
+    pub materials: HashMap<String, MaterialDescription>,
+    pub transform: Option<TransformDescription>,
+    pub motion: Option<MotionDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Object", deny_unknown_fields)]
+struct RawObjectDescription {
+    shape: ShapeDescription,
+    #[serde(default)]
+    material: Option<MaterialDescription>,
+    #[serde(default)]
+    emission: Option<SpectrumDescription>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDescription>,
+    #[serde(default)]
+    transform: Option<TransformDescription>,
+    #[serde(default)]
+    motion: Option<MotionDescription>,
+}
+
+impl TryFrom<RawObjectDescription> for ObjectDescription {
+    type Error = String;
+
+    fn try_from(raw: RawObjectDescription) -> Result<Self, String> {
+        if raw.transform.is_some() && raw.motion.is_some() {
+            return Err("object takes either a transform or motion, not both".into());
+        }
+
+        // Lights are sampled by solid angle in their local space, which only
+        // uniform scaling preserves
+        if raw.emission.is_some() {
+            let scales = raw.transform.iter().map(|t| t.scale);
+            let keyframes = raw.motion.iter().flat_map(|m| &m.keyframes);
+            if let Some(scale) = scales
+                .chain(keyframes.map(|k| k.scale))
+                .find(|s| s.0 != s.1 || s.1 != s.2)
+            {
+                return Err(format!(
+                    "emissive objects can only be scaled uniformly, got scale {:?}",
+                    scale
+                ));
+            }
+        }
+
+        if let ShapeDescription::Instance(name) = &raw.shape {
+            if raw.emission.is_some() {
+                return Err(format!("instance of \"{}\" can't be emissive", name));
+            }
+            if !raw.materials.is_empty() {
+                return Err(format!(
+                    "instance of \"{}\" takes a single material, not per-group materials",
+                    name
+                ));
+            }
+        }
+
+        Ok(Self {
+            shape: raw.shape,
+            material: raw.material,
+            emission: raw.emission,
+            materials: raw.materials,
+            transform: raw.transform,
+            motion: raw.motion,
+        })
+    }
+}
+
+impl ObjectDescription {
+    // Shared by all the primitives of the object
+    fn object_transform(&self) -> Option<ObjectTransform> {
+        match (&self.transform, &self.motion) {
+            (Some(transform), _) => Some(ObjectTransform::Static(Arc::new(transform.build()))),
+            (_, Some(motion)) => Some(ObjectTransform::Animated(Arc::new(motion.build()))),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawTransformDescription")]
+pub struct TransformDescription {
+    translation: Triple,
+    rotation: Option<RotationDescription>,
+    scale: Triple,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Transform", deny_unknown_fields)]
+struct RawTransformDescription {
+    #[serde(default)]
+    translation: Triple,
+    #[serde(default)]
+    rotation: Option<RotationDescription>,
+    #[serde(default = "default_scale")]
+    scale: Triple,
+}
+
+impl TryFrom<RawTransformDescription> for TransformDescription {
+    type Error = String;
+
+    fn try_from(raw: RawTransformDescription) -> Result<Self, String> {
+        validate_placement(raw.scale, &raw.rotation)?;
+
+        Ok(Self {
+            translation: raw.translation,
+            rotation: raw.rotation,
+            scale: raw.scale,
+        })
+    }
+}
+
+impl TransformDescription {
+    fn build(&self) -> Transform {
+        keyframe(0.0, self.translation, &self.rotation, self.scale).transform()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "Vec<KeyframeDescription>")]
+pub struct MotionDescription {
+    keyframes: Vec<KeyframeDescription>,
+}
+
+impl TryFrom<Vec<KeyframeDescription>> for MotionDescription {
+    type Error = String;
+
+    fn try_from(keyframes: Vec<KeyframeDescription>) -> Result<Self, String> {
+        if keyframes.is_empty() {
+            return Err("motion needs at least one keyframe".into());
+        }
+
+        Ok(Self { keyframes })
+    }
+}
+
+impl MotionDescription {
+    pub fn build(&self) -> AnimatedTransform {
+        AnimatedTransform::new(
+            self.keyframes
+                .iter()
+                .map(KeyframeDescription::build)
+                .collect(),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawKeyframeDescription")]
+pub struct KeyframeDescription {
+    time: f32,
+    translation: Triple,
+    rotation: Option<RotationDescription>,
+    scale: Triple,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Keyframe", deny_unknown_fields)]
+struct RawKeyframeDescription {
+    time: f32,
+    #[serde(default)]
+    translation: Triple,
+    #[serde(default)]
+    rotation: Option<RotationDescription>,
+    #[serde(default = "default_scale")]
+    scale: Triple,
+}
+
+fn default_scale() -> Triple {
+    (1.0, 1.0, 1.0)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RotationDescription {
+    axis: Triple,
+    // Counter-clockwise, in degrees
+    angle: f32,
+}
+
+impl TryFrom<RawKeyframeDescription> for KeyframeDescription {
+    type Error = String;
+
+    fn try_from(raw: RawKeyframeDescription) -> Result<Self, String> {
+        if !raw.time.is_finite() {
+            return Err(format!("keyframe time must be finite, got {}", raw.time));
+        }
+
+        validate_placement(raw.scale, &raw.rotation)?;
+
+        Ok(Self {
+            time: raw.time,
+            translation: raw.translation,
+            rotation: raw.rotation,
+            scale: raw.scale,
+        })
+    }
+}
+
+impl KeyframeDescription {
+    fn build(&self) -> Keyframe {
+        keyframe(self.time, self.translation, &self.rotation, self.scale)
+    }
+}
+
+fn validate_placement(scale: Triple, rotation: &Option<RotationDescription>) -> Result<(), String> {
+    let (x, y, z) = scale;
+    if x == 0.0 || y == 0.0 || z == 0.0 || [x, y, z].iter().any(|s| s.is_nan()) {
+        return Err(format!("scale must not be zero, got ({}, {}, {})", x, y, z));
+    }
+
+    if let Some(rotation) = rotation {
+        if to_vec(rotation.axis).len_squared() == 0.0 {
+            return Err("rotation axis must not be zero".into());
+        }
+    }
+
+    Ok(())
+}
+
+fn keyframe(
+    time: f32,
+    translation: Triple,
+    rotation: &Option<RotationDescription>,
+    scale: Triple,
+) -> Keyframe {
+    let rotation = match rotation {
+        Some(rotation) => {
+            Quaternion::from_axis_angle(to_vec(rotation.axis), rotation.angle.to_radians())
+        }
+        None => Quaternion::id(),
+    };
+
+    Keyframe {
+        time,
+        translation: to_vec(translation),
+        rotation,
+        scale: to_vec(scale),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub enum ShapeDescription {
+    Sphere(SphereDescription),
+    Obj { path: PathBuf },
+    // Name of a shape in the scene's instances
+    Instance(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawSphereDescription")]
+pub struct SphereDescription {
+    center: Triple,
+    radius: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Sphere", deny_unknown_fields)]
+struct RawSphereDescription {
+    center: Triple,
+    radius: f32,
+}
+
+impl TryFrom<RawSphereDescription> for SphereDescription {
+    type Error = String;
+
+    fn try_from(raw: RawSphereDescription) -> Result<Self, String> {
+        if raw.radius.is_nan() || raw.radius <= 0.0 {
+            return Err(format!("sphere radius must be positive, got {}", raw.radius));
+        }
+
+        Ok(Self {
+            center: raw.center,
+            radius: raw.radius,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum MaterialDescription {
+    Lambertian { albedo: TextureDescription },
+    Microfacet(MicrofacetDescription),
+    Specular { reflectance: TextureDescription },
+    Dielectric(DielectricDescription),
+    RoughDielectric(RoughDielectricDescription),
+    Conductor(ConductorDescription),
+    RoughConductor(RoughConductorDescription),
+    Layered(LayeredDescription),
+    Principled(PrincipledDescription),
+    NormalMap(NormalMapDescription),
+    Bump(BumpDescription),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "Dielectric", deny_unknown_fields)]
+pub struct DielectricDescription {
+    #[serde(default = "default_dielectric_texture")]
+    reflectance: TextureDescription,
+    #[serde(default = "default_dielectric_texture")]
+    transmittance: TextureDescription,
+    ior: IorDescription,
+}
+
+fn default_dielectric_texture() -> TextureDescription {
+    TextureDescription::Constant(1.0)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawRoughDielectricDescription")]
+pub struct RoughDielectricDescription {
+    reflectance: TextureDescription,
+    transmittance: TextureDescription,
+    ior: IorDescription,
+    roughness: (f32, f32),
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "RoughDielectric", deny_unknown_fields)]
+struct RawRoughDielectricDescription {
+    #[serde(default = "default_dielectric_texture")]
+    reflectance: TextureDescription,
+    #[serde(default = "default_dielectric_texture")]
+    transmittance: TextureDescription,
+    ior: IorDescription,
+    roughness: (f32, f32),
+}
+
+// GGX roughness, which is NaN for zero, negative or infinite values. Smooth
+// surfaces are left to the specular BSDF given as an alternative
+fn check_roughness(name: &str, (x, y): (f32, f32), smooth: &str) -> Result<(), String> {
+    let valid = |r: f32| r > 0.0 && r.is_finite();
+    if !valid(x) || !valid(y) {
+        return Err(format!(
+            "{} roughness must be positive and finite, got ({}, {}), {}",
+            name, x, y, smooth
+        ));
+    }
+    Ok(())
+}
+
+impl TryFrom<RawRoughDielectricDescription> for RoughDielectricDescription {
+    type Error = String;
+
+    fn try_from(raw: RawRoughDielectricDescription) -> Result<Self, String> {
+        check_roughness("dielectric", raw.roughness, "use Dielectric instead")?;
+
+        Ok(Self {
+            reflectance: raw.reflectance,
+            transmittance: raw.transmittance,
+            ior: raw.ior,
+            roughness: raw.roughness,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawIorDescription")]
+pub struct IorDescription(Ior);
+
+#[derive(Deserialize)]
+#[serde(rename = "Ior")]
+enum RawIorDescription {
+    Constant(f32),
+    Cauchy { a: f32, b: f32 },
+    Sellmeier { b: Triple, c: Triple },
+    Bk7,
+    Sf11,
+    FusedSilica,
+}
+
+impl TryFrom<RawIorDescription> for IorDescription {
+    type Error = String;
+
+    fn try_from(raw: RawIorDescription) -> Result<Self, String> {
+        let ior = match raw {
+            RawIorDescription::Constant(n) => Ior::Constant(n),
+            RawIorDescription::Cauchy { a, b } => Ior::Cauchy { a, b },
+            RawIorDescription::Sellmeier { b, c } => Ior::Sellmeier {
+                b: [b.0, b.1, b.2],
+                c: [c.0, c.1, c.2],
+            },
+            RawIorDescription::Bk7 => Ior::BK7,
+            RawIorDescription::Sf11 => Ior::SF11,
+            RawIorDescription::FusedSilica => Ior::FUSED_SILICA,
+        };
+
+        // Catches negative constants as well as Sellmeier poles in the visible
+        // range
+        let lambda_range = LAMBDA_MIN_NM as usize..=LAMBDA_MAX_NM as usize;
+        if let Some(lambda) = lambda_range.step_by(10).find(|&lambda| {
+            let n = ior.evaluate_single(lambda as f32);
+            n.is_nan() || n <= 0.0
+        }) {
+            return Err(format!(
+                "index of refraction must be positive, got {} at {}nm",
+                ior.evaluate_single(lambda as f32),
+                lambda
+            ));
+        }
+
+        Ok(Self(ior))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename = "Conductor", deny_unknown_fields)]
+pub struct ConductorDescription {
+    ior: ComplexIorDescription,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawRoughConductorDescription")]
+pub struct RoughConductorDescription {
+    ior: ComplexIorDescription,
+    roughness: (f32, f32),
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "RoughConductor", deny_unknown_fields)]
+struct RawRoughConductorDescription {
+    ior: ComplexIorDescription,
+    roughness: (f32, f32),
+}
+
+impl TryFrom<RawRoughConductorDescription> for RoughConductorDescription {
+    type Error = String;
+
+    fn try_from(raw: RawRoughConductorDescription) -> Result<Self, String> {
+        check_roughness("conductor", raw.roughness, "use Conductor instead")?;
+
+        Ok(Self {
+            ior: raw.ior,
+            roughness: raw.roughness,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub enum ComplexIorDescription {
+    Gold,
+    Silver,
+    Copper,
+    Aluminium,
+    Chromium,
+    Complex {
+        eta: SpectrumDescription,
+        k: SpectrumDescription,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawLayeredDescription")]
+pub struct LayeredDescription {
+    base: Box<MaterialDescription>,
+    ior: IorDescription,
+    roughness: Option<(f32, f32)>,
+    thickness: f32,
+    absorption: SpectrumDescription,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Layered", deny_unknown_fields)]
+struct RawLayeredDescription {
+    base: Box<MaterialDescription>,
+    ior: IorDescription,
+    #[serde(default)]
+    roughness: Option<(f32, f32)>,
+    #[serde(default)]
+    thickness: f32,
+    #[serde(default = "default_absorption")]
+    absorption: SpectrumDescription,
+}
+
+fn default_absorption() -> SpectrumDescription {
+    SpectrumDescription::Constant(0.0)
+}
+
+impl TryFrom<RawLayeredDescription> for LayeredDescription {
+    type Error = String;
+
+    fn try_from(raw: RawLayeredDescription) -> Result<Self, String> {
+        if let Some(roughness) = raw.roughness {
+            check_roughness("coating", roughness, "leave it out for a smooth coating")?;
+        }
+
+        if raw.thickness.is_nan() || raw.thickness < 0.0 {
+            return Err(format!(
+                "coating thickness must not be negative, got {}",
+                raw.thickness
+            ));
+        }
+
+        Ok(Self {
+            base: raw.base,
+            ior: raw.ior,
+            roughness: raw.roughness,
+            thickness: raw.thickness,
+            absorption: raw.absorption,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawPrincipledDescription")]
+pub struct PrincipledDescription {
+    base_color: TextureDescription,
+    metallic: f32,
+    roughness: f32,
+    specular: f32,
+    sheen: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    transmission: f32,
+    ior: IorDescription,
+}
+
+// Defaults to a grey, somewhat rough plastic
+#[derive(Deserialize)]
+#[serde(rename = "Principled", deny_unknown_fields)]
+struct RawPrincipledDescription {
+    #[serde(default = "default_base_color")]
+    base_color: TextureDescription,
+    #[serde(default)]
+    metallic: f32,
+    #[serde(default = "default_half")]
+    roughness: f32,
+    #[serde(default = "default_half")]
+    specular: f32,
+    #[serde(default)]
+    sheen: f32,
+    #[serde(default)]
+    clearcoat: f32,
+    #[serde(default = "default_clearcoat_roughness")]
+    clearcoat_roughness: f32,
+    #[serde(default)]
+    transmission: f32,
+    #[serde(default = "default_principled_ior")]
+    ior: IorDescription,
+}
+
+fn default_base_color() -> TextureDescription {
+    TextureDescription::Constant(0.8)
+}
+
+fn default_half() -> f32 {
+    0.5
+}
+
+fn default_clearcoat_roughness() -> f32 {
+    0.03
+}
+
+fn default_principled_ior() -> IorDescription {
+    IorDescription(Ior::Constant(1.5))
+}
+
+impl TryFrom<RawPrincipledDescription> for PrincipledDescription {
+    type Error = String;
+
+    fn try_from(raw: RawPrincipledDescription) -> Result<Self, String> {
+        for &(name, value) in &[
+            ("metallic", raw.metallic),
+            ("roughness", raw.roughness),
+            ("specular", raw.specular),
+            ("sheen", raw.sheen),
+            ("clearcoat", raw.clearcoat),
+            ("clearcoat_roughness", raw.clearcoat_roughness),
+            ("transmission", raw.transmission),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("{} must be between 0 and 1, got {}", name, value));
+            }
+        }
+
+        Ok(Self {
+            base_color: raw.base_color,
+            metallic: raw.metallic,
+            roughness: raw.roughness,
+            specular: raw.specular,
+            sheen: raw.sheen,
+            clearcoat: raw.clearcoat,
+            clearcoat_roughness: raw.clearcoat_roughness,
+            transmission: raw.transmission,
+            ior: raw.ior,
+        })
+    }
+}
+
+// Tangent-space normals, linear RGB with z out of the surface
+#[derive(Debug, Deserialize)]
+#[serde(rename = "NormalMap", deny_unknown_fields)]
+pub struct NormalMapDescription {
+    base: Box<MaterialDescription>,
+    path: PathBuf,
+}
+
+// Heights in [0, 1] scaled by strength, in world units
+#[derive(Debug, Deserialize)]
+#[serde(rename = "Bump", deny_unknown_fields)]
+pub struct BumpDescription {
+    base: Box<MaterialDescription>,
+    path: PathBuf,
+    strength: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "RawMicrofacetDescription")]
+pub struct MicrofacetDescription {
+    reflectance: TextureDescription,
+    roughness: (f32, f32),
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Microfacet", deny_unknown_fields)]
+struct RawMicrofacetDescription {
+    reflectance: TextureDescription,
+    roughness: (f32, f32),
+}
+
+impl TryFrom<RawMicrofacetDescription> for MicrofacetDescription {
+    type Error = String;
+
+    fn try_from(raw: RawMicrofacetDescription) -> Result<Self, String> {
+        check_roughness("microfacet", raw.roughness, "use Specular instead")?;
+
+        Ok(Self {
+            reflectance: raw.reflectance,
+            roughness: raw.roughness,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum SpectrumDescription {
+    Constant(f32),
+    // Upsampled with the Jakob et al. table; values above 1 are treated as HDR
+    Rgb(f32, f32, f32),
+    Tabulated(TabulatedDescription),
+    Blackbody(BlackbodyDescription),
+    Illuminant(IlluminantDescription),
+}
+
+// Measured values against wavelengths in nm, from a CSV or SPD file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Tabulated", deny_unknown_fields)]
+pub struct TabulatedDescription {
+    path: PathBuf,
+    #[serde(default = "default_spectrum_scale")]
+    scale: f32,
+}
+
+// Blackbody and Illuminant have a luminance of scale, like Constant(scale)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawBlackbodyDescription")]
+pub struct BlackbodyDescription {
+    temperature: f32,
+    scale: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Blackbody", deny_unknown_fields)]
+struct RawBlackbodyDescription {
+    temperature: f32,
+    #[serde(default = "default_spectrum_scale")]
+    scale: f32,
+}
+
+// Planck's law underflows over the visible range much below this
+const MIN_BLACKBODY_TEMPERATURE: f32 = 100.0;
+
+impl TryFrom<RawBlackbodyDescription> for BlackbodyDescription {
+    type Error = String;
+
+    fn try_from(raw: RawBlackbodyDescription) -> Result<Self, String> {
+        if raw.temperature.is_nan() || raw.temperature < MIN_BLACKBODY_TEMPERATURE {
+            return Err(format!(
+                "blackbody temperature must be at least {} K, got {}",
+                MIN_BLACKBODY_TEMPERATURE, raw.temperature
+            ));
+        }
+
+        Ok(Self {
+            temperature: raw.temperature,
+            scale: raw.scale,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Illuminant", deny_unknown_fields)]
+pub struct IlluminantDescription {
+    name: IlluminantNameDescription,
+    #[serde(default = "default_spectrum_scale")]
+    scale: f32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename = "IlluminantName")]
+pub enum IlluminantNameDescription {
+    D65,
+    D50,
+    A,
+    F2,
+    F7,
+    F11,
+}
+
+fn default_spectrum_scale() -> f32 {
+    1.0
+}
+
+// Spectra varying over a surface with its texture coordinates, or the same
+// everywhere for Constant and Rgb
+#[derive(Debug, Clone, Deserialize)]
+pub enum TextureDescription {
+    Constant(f32),
+    Rgb(f32, f32, f32),
+    // Any other spectrum, such as a measured Tabulated reflectance
+    Spectrum(SpectrumDescription),
+    // Repeats outside of [0, 1]. PNGs are expected to be sRGB and OpenEXR
+    // images linear
+    Image {
+        path: PathBuf,
+        #[serde(default = "default_image_filter")]
+        filter: ImageFilterDescription,
+    },
+    // scale squares along each unit of u and v
+    Checkerboard {
+        even: Box<TextureDescription>,
+        odd: Box<TextureDescription>,
+        #[serde(default = "default_texture_scale")]
+        scale: f32,
+    },
+    // Fractal Perlin noise blending from low to high
+    Noise {
+        low: Box<TextureDescription>,
+        high: Box<TextureDescription>,
+        #[serde(default = "default_texture_scale")]
+        scale: f32,
+        #[serde(default = "default_octaves")]
+        octaves: NonZeroU32,
+    },
+    // From start at 0 to end at 1 along U or V
+    Gradient {
+        start: Box<TextureDescription>,
+        end: Box<TextureDescription>,
+        axis: GradientAxisDescription,
+    },
+}
+
+fn default_texture_scale() -> f32 {
+    1.0
+}
+
+fn default_octaves() -> NonZeroU32 {
+    NonZeroU32::new(4).unwrap()
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename = "GradientAxis")]
+pub enum GradientAxisDescription {
+    U,
+    V,
+}
+
+// How image lookups are filtered over the footprint of a pixel, see
+// texture::ImageFilter
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename = "ImageFilter")]
+pub enum ImageFilterDescription {
+    Bilinear,
+    Trilinear,
+    Ewa,
+}
+
+fn default_image_filter() -> ImageFilterDescription {
+    ImageFilterDescription::Ewa
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Mesh(PathBuf, tobj::LoadError),
+    Environment(PathBuf, exr::error::Error),
+    Lens(PathBuf, String),
+    Texture(PathBuf, String),
+    Spectrum(PathBuf, String),
+    Instance(String, String),
+    Object(usize, String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "failed to read scene file: {}", e),
+            SceneError::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}: {}", line, column, message),
+            SceneError::Mesh(path, e) => {
+                write!(f, "failed to load mesh {}: {}", path.display(), e)
+            }
+            SceneError::Environment(path, e) => {
+                write!(f, "failed to load environment map {}: {}", path.display(), e)
+            }
+            SceneError::Lens(path, e) => {
+                write!(f, "failed to load lens {}: {}", path.display(), e)
+            }
+            SceneError::Texture(path, e) => {
+                write!(f, "failed to load texture {}: {}", path.display(), e)
+            }
+            SceneError::Spectrum(path, e) => {
+                write!(f, "failed to load spectrum {}: {}", path.display(), e)
+            }
+            SceneError::Instance(name, e) => write!(f, "instance \"{}\": {}", name, e),
+            SceneError::Object(index, e) => write!(f, "object {}: {}", index, e),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(e: std::io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for SceneError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        SceneError::Parse {
+            line: e.position.line,
+            column: e.position.col,
+            message: e.code.to_string(),
+        }
+    }
+}
+
+impl SceneDescription {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let mut description = Self::parse(&std::fs::read_to_string(path)?)?;
+        description.base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(description)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, SceneError> {
+        Ok(ron::Options::default()
+            .with_default_extension(
+                ron::extensions::Extensions::IMPLICIT_SOME
+                    | ron::extensions::Extensions::UNWRAP_VARIANT_NEWTYPES,
+            )
+            .from_str(source)?)
+    }
+
+    pub fn build_camera(&self, aspect_ratio: f32) -> Result<Camera, SceneError> {
+        self.camera.build(aspect_ratio, &self.base_dir)
+    }
+
+    pub fn build_scene(&self) -> Result<Scene, SceneError> {
+        let mut builder = SceneBuilder {
+            base_dir: self.base_dir.clone(),
+            ..SceneBuilder::default()
+        };
+
+        let mut instances = HashMap::new();
+        for (name, shape) in &self.instances {
+            let geometry = match shape {
+                ShapeDescription::Sphere(s) => {
+                    vec![Sphere::new(to_point(s.center), s.radius).into()]
+                }
+                ShapeDescription::Obj { path } => self
+                    .load_obj(path)?
+                    .iter()
+                    .flat_map(|group| group.mesh.triangles().map(Geometry::from))
+                    .collect(),
+                ShapeDescription::Instance(_) => {
+                    return Err(SceneError::Instance(
+                        name.clone(),
+                        "instances can't contain other instances".into(),
+                    ))
+                }
+            };
+
+            let primitives = geometry.into_iter().map(Primitive::new).collect();
+            instances.insert(name, Arc::new(InstancedGeometry::new(primitives)));
+        }
+
+        // Objects are numbered from 1 in errors, in the order of the file
+        let unlit = |index: usize, shape: String| {
+            SceneError::Object(
+                index + 1,
+                format!("{} has neither a material nor an emission", shape),
+            )
+        };
+
+        for (index, object) in self.objects.iter().enumerate() {
+            let first_primitive = builder.scene.primitives.len();
+            let material = object
+                .material
+                .as_ref()
+                .map(|m| builder.material(m))
+                .transpose()?;
+            let emission = object
+                .emission
+                .as_ref()
+                .map(|e| builder.spectrum(e))
+                .transpose()?;
+
+            match &object.shape {
+                ShapeDescription::Sphere(s) => {
+                    let sphere = Sphere::new(to_point(s.center), s.radius);
+                    match (material, emission) {
+                        (Some(material), Some(emission)) => builder
+                            .scene
+                            .add_emissive_material(sphere, material, emission),
+                        (Some(material), None) => builder.scene.add_material(sphere, material),
+                        (None, Some(emission)) => builder.scene.add_light(sphere, emission),
+                        (None, None) => return Err(unlit(index, "sphere".into())),
+                    }
+                }
+                ShapeDescription::Obj { path } => {
+                    if material.is_none() && emission.is_none() && object.materials.is_empty() {
+                        return Err(unlit(index, format!("mesh {}", path.display())));
+                    }
+                    for group in self.load_obj(path)? {
+                        let group_material = object
+                            .materials
+                            .get(&group.name)
+                            .or_else(|| {
+                                let name = group.material_name.as_ref()?;
+                                object.materials.get(name)
+                            })
+                            .map(|m| builder.material(m))
+                            .transpose()?
+                            .or_else(|| material.clone());
+                        if group_material.is_none() && emission.is_none() {
+                            let name = format!("group \"{}\" of {}", group.name, path.display());
+                            return Err(unlit(index, name));
+                        }
+
+                        builder
+                            .scene
+                            .add_mesh(&group.mesh, group_material, emission.clone());
+                    }
+                }
+                ShapeDescription::Instance(name) => {
+                    let geometry = instances.get(name).ok_or_else(|| {
+                        SceneError::Instance(name.clone(), "not found in instances".into())
+                    })?;
+                    let material = material
+                        .ok_or_else(|| unlit(index, format!("instance of \"{}\"", name)))?;
+                    builder
+                        .scene
+                        .add_material(Instance::new(geometry.clone()), material);
+                }
+            }
+
+            if let Some(transform) = object.object_transform() {
+                for primitive in &mut builder.scene.primitives[first_primitive..] {
+                    primitive.transform = Some(transform.clone());
+                }
+            }
+        }
+
+        if let Some(env) = &self.environment {
+            let path = self.base_dir.join(&env.path);
+            let table = builder.upsample_table();
+            let map = EnvironmentMap::load(&path, env.strength, table)
+                .map_err(|e| SceneError::Environment(path, e))?;
+            builder.scene.environment = Some(map);
+        }
+
+        builder.scene.build_bvh();
+        Ok(builder.scene)
+    }
+
+    fn load_obj(&self, path: &Path) -> Result<Vec<MeshGroup>, SceneError> {
+        let path = self.base_dir.join(path);
+        Mesh::load_obj(&path).map_err(|e| SceneError::Mesh(path, e))
+    }
+}
+
+#[derive(Default)]
+struct SceneBuilder {
+    scene: Scene,
+    // Only loaded if the scene actually uses RGB spectra
+    upsample_table: Option<UpsampleTable>,
+    // Loaded once however many materials use them
+    images: HashMap<PathBuf, ImageTexture>,
+    base_dir: PathBuf,
+}
+
+impl SceneBuilder {
+    fn upsample_table(&mut self) -> &UpsampleTable {
+        self.upsample_table.get_or_insert_with(UpsampleTable::load)
+    }
+
+    fn spectrum(&mut self, desc: &SpectrumDescription) -> Result<Spectrum, SceneError> {
+        Ok(match desc {
+            SpectrumDescription::Constant(value) => ConstantSpectrum::new(*value).into(),
+            &SpectrumDescription::Rgb(r, g, b) => {
+                let table = self.upsample_table();
+                if r > 1.0 || g > 1.0 || b > 1.0 {
+                    table.get_spectrum_hdr([r, g, b]).into()
+                } else {
+                    table.get_spectrum([r, g, b]).into()
+                }
+            }
+            SpectrumDescription::Tabulated(t) => {
+                let path = self.base_dir.join(&t.path);
+                TabulatedSpectrum::load(&path)
+                    .map_err(|e| SceneError::Spectrum(path, e))?
+                    .scaled(t.scale)
+                    .into()
+            }
+            SpectrumDescription::Blackbody(b) => {
+                BlackbodySpectrum::new(b.temperature).scaled(b.scale).into()
+            }
+            SpectrumDescription::Illuminant(i) => {
+                let illuminant = match i.name {
+                    IlluminantNameDescription::D65 => Illuminant::D65,
+                    IlluminantNameDescription::D50 => Illuminant::D50,
+                    IlluminantNameDescription::A => Illuminant::A,
+                    IlluminantNameDescription::F2 => Illuminant::F2,
+                    IlluminantNameDescription::F7 => Illuminant::F7,
+                    IlluminantNameDescription::F11 => Illuminant::F11,
+                };
+                illuminant.spectrum(i.scale)
+            }
+        })
+    }
+
+    fn texture(&mut self, desc: &TextureDescription) -> Result<Texture, SceneError> {
+        Ok(match desc {
+            TextureDescription::Constant(value) => ConstantSpectrum::new(*value).into(),
+            TextureDescription::Rgb(r, g, b) => {
+                self.spectrum(&SpectrumDescription::Rgb(*r, *g, *b))?.into()
+            }
+            TextureDescription::Spectrum(spectrum) => self.spectrum(spectrum)?.into(),
+            TextureDescription::Image { path, filter } => {
+                let filter = match filter {
+                    ImageFilterDescription::Bilinear => ImageFilter::Bilinear,
+                    ImageFilterDescription::Trilinear => ImageFilter::Trilinear,
+                    ImageFilterDescription::Ewa => ImageFilter::Ewa,
+                };
+                let path = self.base_dir.join(path);
+                if let Some(image) = self.images.get(&path) {
+                    return Ok(image.clone().with_filter(filter).into());
+                }
+
+                let image = ImageTexture::load(&path, self.upsample_table())
+                    .map_err(|e| SceneError::Texture(path.clone(), e))?;
+                self.images.insert(path, image.clone());
+                image.with_filter(filter).into()
+            }
+            TextureDescription::Checkerboard { even, odd, scale } => {
+                CheckerboardTexture::new(self.texture(even)?, self.texture(odd)?, *scale).into()
+            }
+            TextureDescription::Noise {
+                low,
+                high,
+                scale,
+                octaves,
+            } => NoiseTexture::new(
+                self.texture(low)?,
+                self.texture(high)?,
+                *scale,
+                octaves.get(),
+            )
+            .into(),
+            TextureDescription::Gradient { start, end, axis } => {
+                let axis = match axis {
+                    GradientAxisDescription::U => GradientAxis::U,
+                    GradientAxisDescription::V => GradientAxis::V,
+                };
+                GradientTexture::new(self.texture(start)?, self.texture(end)?, axis).into()
+            }
+        })
+    }
+
+    fn complex_ior(&mut self, desc: &ComplexIorDescription) -> Result<ComplexIor, SceneError> {
+        Ok(match desc {
+            ComplexIorDescription::Gold => Metal::Gold.ior(),
+            ComplexIorDescription::Silver => Metal::Silver.ior(),
+            ComplexIorDescription::Copper => Metal::Copper.ior(),
+            ComplexIorDescription::Aluminium => Metal::Aluminium.ior(),
+            ComplexIorDescription::Chromium => Metal::Chromium.ior(),
+            ComplexIorDescription::Complex { eta, k } => {
+                ComplexIor::new(self.spectrum(eta)?, self.spectrum(k)?)
+            }
+        })
+    }
+
+    fn material(&mut self, desc: &MaterialDescription) -> Result<Bsdf, SceneError> {
+        Ok(match desc {
+            MaterialDescription::Lambertian { albedo } => {
+                LambertianBsdf::new(self.texture(albedo)?).into()
+            }
+            MaterialDescription::Microfacet(m) => {
+                MicrofacetBsdf::new(self.texture(&m.reflectance)?, m.roughness.0, m.roughness.1)
+                    .into()
+            }
+            MaterialDescription::Specular { reflectance } => {
+                SpecularBsdf::new(self.texture(reflectance)?).into()
+            }
+            MaterialDescription::Dielectric(d) => DielectricBsdf::new(
+                self.texture(&d.reflectance)?,
+                self.texture(&d.transmittance)?,
+                d.ior.0.clone(),
+            )
+            .into(),
+            MaterialDescription::RoughDielectric(d) => RoughDielectricBsdf::new(
+                self.texture(&d.reflectance)?,
+                self.texture(&d.transmittance)?,
+                d.ior.0.clone(),
+                d.roughness.0,
+                d.roughness.1,
+            )
+            .into(),
+            MaterialDescription::Conductor(c) => {
+                ConductorBsdf::new(self.complex_ior(&c.ior)?).into()
+            }
+            MaterialDescription::RoughConductor(c) => {
+                RoughConductorBsdf::new(self.complex_ior(&c.ior)?, c.roughness.0, c.roughness.1)
+                    .into()
+            }
+            MaterialDescription::Layered(l) => LayeredBsdf::new(
+                self.material(&l.base)?,
+                l.ior.0.clone(),
+                l.roughness,
+                l.thickness,
+                self.spectrum(&l.absorption)?,
+            )
+            .into(),
+            MaterialDescription::Principled(p) => PrincipledBsdf::new(PrincipledParameters {
+                base_color: self.texture(&p.base_color)?,
+                metallic: p.metallic,
+                roughness: p.roughness,
+                specular: p.specular,
+                sheen: p.sheen,
+                clearcoat: p.clearcoat,
+                clearcoat_roughness: p.clearcoat_roughness,
+                transmission: p.transmission,
+                ior: p.ior.0.clone(),
+            })
+            .into(),
+            MaterialDescription::NormalMap(n) => {
+                let map = NormalMap::TangentSpace(self.rgb_image(&n.path)?);
+                NormalMappedBsdf::new(self.material(&n.base)?, map).into()
+            }
+            MaterialDescription::Bump(b) => {
+                let map = NormalMap::Bump {
+                    height: self.rgb_image(&b.path)?,
+                    strength: b.strength,
+                };
+                NormalMappedBsdf::new(self.material(&b.base)?, map).into()
+            }
+        })
+    }
+
+    fn rgb_image(&self, path: &Path) -> Result<RgbImage, SceneError> {
+        let path = self.base_dir.join(path);
+        RgbImage::load(&path).map_err(|e| SceneError::Texture(path, e))
+    }
+}
+
+fn to_point(t: Triple) -> Point3 {
+    Point3::new(t.0, t.1, t.2)
+}
+
+fn to_vec(t: Triple) -> Vec3 {
+    Vec3::new(t.0, t.1, t.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::Ray, shape::Shape};
+
+    const SCENE: &str = r#"
+        Scene(
+            render: (width: 64, height: 32),
+            objects: [
+                (
+                    shape: Sphere(center: (0.0, 2.3, 3.0), radius: 1.0),
+                    material: Lambertian(albedo: Constant(0.5)),
+                    emission: Constant(3.0),
+                ),
+                (
+                    shape: Sphere(center: (0.0, -101.5, 2.0), radius: 100.0),
+                    material: Microfacet(reflectance: Constant(0.8), roughness: (0.3, 0.3)),
+                ),
+            ],
+        )
+    "#;
+
+    #[test]
+    fn test_parse_scene() {
+        let desc = SceneDescription::parse(SCENE).unwrap();
+        assert_eq!(desc.render.width, 64);
+        assert_eq!(desc.render.height, 32);
+        assert_eq!(desc.render.spp, RenderSettings::default().spp);
+
+        let scene = desc.build_scene().unwrap();
+        assert_eq!(scene.primitives.len(), 2);
+        assert_eq!(scene.materials.len(), 2);
+        assert_eq!(scene.lights.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let source = "Scene(\n    objects: [\n        (shape: Cube(size: 1.0)),\n    ],\n)";
+        match SceneDescription::parse(source) {
+            Err(SceneError::Parse { line, .. }) => assert_eq!(line, 3),
+            other => panic!("expected parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_obj_group_materials() {
+        let dir = std::env::temp_dir().join("iris_test_obj_group_materials");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("quads.obj"),
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\ng floor\nf 1 2 3 4\ng wall\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let mut desc = SceneDescription::parse(
+            r#"Scene(objects: [(
+                shape: Obj(path: "quads.obj"),
+                material: Lambertian(albedo: Constant(0.5)),
+                materials: { "floor": Specular(reflectance: Constant(1.0)) },
+            )])"#,
+        )
+        .unwrap();
+        desc.base_dir = dir.clone();
+
+        let scene = desc.build_scene().unwrap();
+        assert_eq!(scene.primitives.len(), 3);
+        assert_eq!(scene.materials.len(), 2);
+        assert!(matches!(scene.materials[0].data, Bsdf::SpecularBsdf(_)));
+        assert!(matches!(scene.materials[1].data, Bsdf::LambertianBsdf(_)));
+
+        // Without a default, groups left without a material are reported
+        let mut desc = SceneDescription::parse(
+            r#"Scene(objects: [(
+                shape: Obj(path: "quads.obj"),
+                materials: { "floor": Specular(reflectance: Constant(1.0)) },
+            )])"#,
+        )
+        .unwrap();
+        desc.base_dir = dir;
+        match desc.build_scene() {
+            Err(SceneError::Object(1, e)) => assert!(e.contains("group \"wall\""), "{}", e),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_invalid_radius() {
+        let source = "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: -1.0))])";
+        assert!(matches!(
+            SceneDescription::parse(source),
+            Err(SceneError::Parse { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_roughness() {
+        for roughness in ["(0.0, 0.3)", "(-0.3, 0.3)", "(0.3, NaN)", "(0.3, -inf)"].iter() {
+            let source = format!(
+                "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), material: \
+                 Microfacet(reflectance: Constant(0.8), roughness: {}))])",
+                roughness
+            );
+            assert!(
+                matches!(
+                    SceneDescription::parse(&source),
+                    Err(SceneError::Parse { line: 1, .. })
+                ),
+                "{}",
+                roughness
+            );
+        }
+    }
+
+    #[test]
+    fn test_dielectric_ior() {
+        let parse = |ior: &str| {
+            let source = format!(
+                "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), material: \
+                 Dielectric(ior: {}))])",
+                ior
+            );
+            SceneDescription::parse(&source)
+        };
+
+        assert!(parse("Sf11").is_ok());
+        assert!(parse("Cauchy(a: 1.5, b: 0.004)").is_ok());
+        assert!(parse("Constant(-1.5)").is_err());
+        // Pole at 500nm
+        assert!(parse("Sellmeier(b: (1.0, 0.0, 0.0), c: (0.25, 0.0, 0.0))").is_err());
+
+        let parse_rough = |roughness: &str| {
+            let source = format!(
+                "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), material: \
+                 RoughDielectric(ior: Bk7, roughness: {}))])",
+                roughness
+            );
+            SceneDescription::parse(&source)
+        };
+        assert!(parse_rough("(0.3, 0.1)").is_ok());
+        for roughness in ["(0.0, 0.1)", "(-0.3, 0.1)", "(0.3, NaN)", "(inf, 0.1)"].iter() {
+            match parse_rough(roughness) {
+                Err(SceneError::Parse { message, .. }) => assert!(message.contains("roughness")),
+                other => panic!("{}: {:?}", roughness, other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_conductor() {
+        let parse = |material: &str| {
+            let source = format!(
+                "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), material: {})])",
+                material
+            );
+            SceneDescription::parse(&source)
+        };
+
+        assert!(parse("Conductor(ior: Gold)").is_ok());
+        assert!(parse("Conductor(ior: Complex(eta: Constant(0.2), k: Constant(3.9)))").is_ok());
+        assert!(parse("RoughConductor(ior: Copper, roughness: (0.2, 0.4))").is_ok());
+        assert!(parse("RoughConductor(ior: Copper, roughness: (0.0, 0.4))").is_err());
+        assert!(parse("RoughConductor(ior: Copper, roughness: (0.2, -0.4))").is_err());
+        assert!(parse("RoughConductor(ior: Copper, roughness: (NaN, 0.4))").is_err());
+        assert!(parse("Conductor(ior: Unobtainium)").is_err());
+    }
+
+    #[test]
+    fn test_layered() {
+        let parse = |material: &str| {
+            let source = format!(
+                "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), material: {})])",
+                material
+            );
+            SceneDescription::parse(&source)
+        };
+
+        assert!(parse("Layered(base: Lambertian(albedo: Constant(0.5)), ior: Bk7)").is_ok());
+        assert!(parse(
+            "Layered(base: Conductor(ior: Copper), ior: Constant(1.5), roughness: (0.1, 0.1), \
+             thickness: 0.1, absorption: Constant(2.0))"
+        )
+        .is_ok());
+        assert!(parse(
+            "Layered(base: Lambertian(albedo: Constant(0.5)), ior: Bk7, roughness: (0.0, 0.1))"
+        )
+        .is_err());
+        assert!(parse(
+            "Layered(base: Lambertian(albedo: Constant(0.5)), ior: Bk7, roughness: (-0.1, inf))"
+        )
+        .is_err());
+        assert!(parse(
+            "Layered(base: Lambertian(albedo: Constant(0.5)), ior: Bk7, thickness: -1.0)"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_principled() {
+        let parse = |material: &str| {
+            let source = format!(
+                "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), material: {})])",
+                material
+            );
+            SceneDescription::parse(&source)
+        };
+
+        assert!(parse("Principled()").is_ok());
+        assert!(parse(
+            "Principled(base_color: Rgb(0.9, 0.6, 0.2), metallic: 1.0, sheen: 0.5, ior: Bk7, \
+             clearcoat: 0.5, transmission: 0.5)"
+        )
+        .is_ok());
+        assert!(parse("Principled(roughness: 1.5)").is_err());
+        assert!(parse("Principled(metallic: -0.1)").is_err());
+    }
+
+    #[test]
+    fn test_textures() {
+        let parse = |albedo: &str| {
+            let source = format!(
+                "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), material: \
+                 Lambertian(albedo: {}))])",
+                albedo
+            );
+            SceneDescription::parse(&source)?.build_scene()
+        };
+
+        assert!(parse("Checkerboard(even: Constant(0.9), odd: Constant(0.1), scale: 8.0)").is_ok());
+        assert!(parse(
+            "Noise(low: Constant(0.2), high: Checkerboard(even: Constant(0.9), odd: \
+             Constant(0.1)), octaves: 2)"
+        )
+        .is_ok());
+        assert!(parse("Gradient(start: Constant(0.0), end: Constant(1.0), axis: U)").is_ok());
+        assert!(parse("Noise(low: Constant(0.2), high: Constant(0.8), octaves: 0)").is_err());
+        assert!(matches!(
+            parse("Image(path: \"missing.png\")"),
+            Err(SceneError::Texture(..))
+        ));
+        assert!(matches!(
+            parse("Image(path: \"missing.png\", filter: Trilinear)"),
+            Err(SceneError::Texture(..))
+        ));
+        assert!(matches!(
+            parse("Image(path: \"missing.png\", filter: Box)"),
+            Err(SceneError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_spectra() {
+        let parse = |emission: &str| {
+            let source = format!(
+                "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), material: \
+                 Lambertian(albedo: Constant(0.5)), emission: {})])",
+                emission
+            );
+            SceneDescription::parse(&source)?.build_scene()
+        };
+
+        assert!(parse("Blackbody(temperature: 2700.0)").is_ok());
+        assert!(parse("Blackbody(temperature: 6500.0, scale: 10.0)").is_ok());
+        assert!(parse("Illuminant(name: F11, scale: 2.0)").is_ok());
+        assert!(matches!(
+            parse("Blackbody(temperature: 10.0)"),
+            Err(SceneError::Parse { .. })
+        ));
+        assert!(matches!(
+            parse("Illuminant(name: F1)"),
+            Err(SceneError::Parse { .. })
+        ));
+        assert!(matches!(
+            parse("Tabulated(path: \"missing.csv\")"),
+            Err(SceneError::Spectrum(..))
+        ));
+
+        // Measured reflectances
+        let source = "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), material: \
+                      Lambertian(albedo: Spectrum(Tabulated(path: \"missing.csv\"))))])";
+        assert!(matches!(
+            SceneDescription::parse(source).and_then(|d| d.build_scene()),
+            Err(SceneError::Spectrum(..))
+        ));
+    }
+
+    #[test]
+    fn test_normal_maps() {
+        let parse = |material: &str| {
+            let source = format!(
+                "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), material: {})])",
+                material
+            );
+            SceneDescription::parse(&source)?.build_scene()
+        };
+
+        assert!(matches!(
+            parse("NormalMap(base: Lambertian(albedo: Constant(0.5)), path: \"missing.png\")"),
+            Err(SceneError::Texture(..))
+        ));
+        assert!(matches!(
+            parse(
+                "Bump(base: Lambertian(albedo: Constant(0.5)), path: \"missing.png\", strength: \
+                 0.1)"
+            ),
+            Err(SceneError::Texture(..))
+        ));
+        assert!(matches!(
+            parse("Bump(base: Lambertian(albedo: Constant(0.5)), path: \"missing.png\")"),
+            Err(SceneError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn test_lens_camera() {
+        let source = r#"Scene(
+            camera: (focus_distance: 2.0, lens: (path: "lenses/dgauss.50mm.dat")),
+            objects: [],
+        )"#;
+        let mut desc = SceneDescription::parse(source).unwrap();
+        desc.base_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("scenes");
+        assert!(matches!(
+            desc.build_camera(1.0),
+            Ok(Camera::RealisticCamera(_))
+        ));
+
+        desc.base_dir = PathBuf::from("nowhere");
+        assert!(matches!(desc.build_camera(1.0), Err(SceneError::Lens(..))));
+
+        // The lens decides the field of view, and needs something to focus on
+        let source = r#"Scene(
+            camera: (fov: 60.0, focus_distance: 2.0, lens: (path: "a")),
+            objects: [],
+        )"#;
+        assert!(SceneDescription::parse(source).is_err());
+        let source = r#"Scene(camera: (lens: (path: "a")), objects: [])"#;
+        assert!(SceneDescription::parse(source).is_err());
+    }
+
+    #[test]
+    fn test_camera_projection() {
+        let source = "Scene(camera: (projection: Fisheye()), objects: [])";
+        let desc = SceneDescription::parse(source).unwrap();
+        assert!(matches!(
+            desc.build_camera(1.0),
+            Ok(Camera::FisheyeCamera(_))
+        ));
+
+        let source = "Scene(camera: (projection: Equirectangular, fov: 60.0), objects: [])";
+        assert!(SceneDescription::parse(source).is_err());
+        let source = "Scene(camera: (projection: Orthographic(height: 0.0)), objects: [])";
+        assert!(SceneDescription::parse(source).is_err());
+    }
+
+    #[test]
+    fn test_motion() {
+        let source = r#"Scene(
+            camera: (shutter: (0.0, 1.0)),
+            objects: [(
+                shape: Sphere(center: (0, 0, 0), radius: 1.0),
+                material: Lambertian(albedo: Constant(0.5)),
+                motion: [(time: 1.0, translation: (2.0, 0.0, 0.0)), (time: 0.0)],
+            )],
+        )"#;
+        let desc = SceneDescription::parse(source).unwrap();
+        assert_eq!(desc.camera.shutter(), (0.0, 1.0));
+        let scene = desc.build_scene().unwrap();
+        let bounds = scene.primitives[0].bounds();
+        assert!(bounds.min.x() <= -1.0 && bounds.max.x() >= 3.0);
+
+        for source in &[
+            "Scene(camera: (shutter: (1.0, 0.0)), objects: [])",
+            "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), motion: [])])",
+            "Scene(objects: [(
+                shape: Sphere(center: (0, 0, 0), radius: 1.0),
+                motion: [(time: 0.0, scale: (1.0, 0.0, 1.0))],
+            )])",
+        ] {
+            assert!(SceneDescription::parse(source).is_err());
+        }
+    }
+
+    #[test]
+    fn test_instances() {
+        let source = r#"Scene(
+            objects: [
+                (
+                    shape: Instance("ball"),
+                    material: Lambertian(albedo: Constant(0.5)),
+                    transform: (translation: (0.0, 0.0, 5.0)),
+                ),
+                (
+                    shape: Instance("ball"),
+                    material: Specular(reflectance: Constant(1.0)),
+                    transform: (translation: (0.0, 0.0, -5.0), scale: (2.0, 2.0, 2.0)),
+                ),
+            ],
+            instances: {"ball": Sphere(center: (0, 0, 0), radius: 1.0)},
+        )"#;
+        let scene = SceneDescription::parse(source)
+            .unwrap()
+            .build_scene()
+            .unwrap();
+        assert_eq!(scene.primitives.len(), 2);
+
+        let (prim, hit) = scene
+            .intersection(&Ray::new(Point3::splat(0.0), Vec3::new(0.0, 0.0, 1.0)))
+            .unwrap();
+        assert!(matches!(
+            prim.get_material(&scene.materials),
+            Some(Bsdf::LambertianBsdf(_))
+        ));
+        assert!(hit.point.distance(Point3::new(0.0, 0.0, 4.0)) < 1e-4);
+
+        // The second copy is twice as big
+        let (prim, hit) = scene
+            .intersection(&Ray::new(Point3::splat(0.0), Vec3::new(0.0, 0.0, -1.0)))
+            .unwrap();
+        assert!(matches!(
+            prim.get_material(&scene.materials),
+            Some(Bsdf::SpecularBsdf(_))
+        ));
+        assert!(hit.point.distance(Point3::new(0.0, 0.0, -3.0)) < 1e-4);
+        assert!((hit.normal.z() - 1.0).abs() < 1e-4);
+
+        let source = r#"Scene(objects: [(shape: Instance("missing"))])"#;
+        assert!(matches!(
+            SceneDescription::parse(source).unwrap().build_scene(),
+            Err(SceneError::Instance(..))
+        ));
+        let source = r#"Scene(objects: [(shape: Instance("a"), emission: Constant(1.0))])"#;
+        assert!(SceneDescription::parse(source).is_err());
+    }
+
+    #[test]
+    fn test_unlit_objects() {
+        let build = |object: &str| {
+            let source = format!(
+                r#"Scene(
+                    objects: [
+                        (shape: Sphere(center: (0, 0, 0), radius: 1.0), emission: Constant(1.0)),
+                        {},
+                    ],
+                    instances: {{"ball": Sphere(center: (0, 0, 0), radius: 1.0)}},
+                )"#,
+                object
+            );
+            SceneDescription::parse(&source).unwrap().build_scene()
+        };
+
+        // Objects that would never be seen are reported rather than dropped
+        for object in [
+            "(shape: Sphere(center: (0, 0, 3), radius: 1.0))",
+            r#"(shape: Instance("ball"), transform: (translation: (0.0, 0.0, 3.0)))"#,
+            r#"(shape: Obj(path: "missing.obj"))"#,
+        ]
+        .iter()
+        {
+            match build(object) {
+                Err(SceneError::Object(2, e)) => {
+                    assert!(e.contains("neither a material nor an emission"), "{}", e)
+                }
+                other => panic!("{}: {:?}", object, other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_scaled_emitters() {
+        let parse = |placement: &str| {
+            let source = format!(
+                "Scene(objects: [(shape: Sphere(center: (0, 0, 0), radius: 1.0), emission: \
+                 Constant(1.0), {})])",
+                placement
+            );
+            SceneDescription::parse(&source)
+        };
+
+        assert!(parse("transform: (scale: (2.0, 2.0, 2.0))").is_ok());
+        assert!(parse("motion: [(time: 0.0), (time: 1.0, scale: (3.0, 3.0, 3.0))]").is_ok());
+        assert!(matches!(
+            parse("transform: (scale: (1.0, 2.0, 1.0))"),
+            Err(SceneError::Parse { .. })
+        ));
+        assert!(matches!(
+            parse("motion: [(time: 0.0), (time: 1.0, scale: (1.0, 1.0, 3.0))]"),
+            Err(SceneError::Parse { .. })
+        ));
+    }
+}
@@ -0,0 +1,219 @@
+use std::{f32::consts::PI, path::Path};
+
+use crate::{
+    math::Vec3,
+    sampling::{distribution::Distribution2D, Sampler},
+    spectrum::{
+        upsample::UpsampleTable,
+        SampleableSpectrum,
+        SpectralSample,
+        UpsampledHdrSpectrum,
+        Wavelength,
+    },
+};
+
+// Equirectangular environment map, with +y up. u follows phi = atan2(z, x) and
+// v = 0 at the top of the image
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    texels: Vec<UpsampledHdrSpectrum>,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    // rgb is stored row by row, starting from the top of the image
+    pub fn new(
+        rgb: &[[f32; 3]],
+        width: usize,
+        height: usize,
+        strength: f32,
+        table: &UpsampleTable,
+    ) -> Self {
+        assert_eq!(rgb.len(), width * height);
+
+        let rgb = rgb
+            .iter()
+            .map(|texel| {
+                texel.map(|c| {
+                    if c.is_finite() {
+                        c.max(0.0) * strength
+                    } else {
+                        0.0
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let texels = rgb
+            .iter()
+            .map(|&texel| table.get_spectrum_hdr(texel))
+            .collect();
+
+        // Weight by sin(theta) to account for the stretching of the rows near the
+        // poles
+        let func = rgb
+            .chunks_exact(width)
+            .enumerate()
+            .flat_map(|(y, row)| {
+                let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+                row.iter().map(move |texel| luminance(*texel) * sin_theta)
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            width,
+            height,
+            texels,
+            distribution: Distribution2D::new(&func, width, height),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        strength: f32,
+        table: &UpsampleTable,
+    ) -> exr::error::Result<Self> {
+        struct Pixels {
+            width: usize,
+            rgb: Vec<[f32; 3]>,
+        }
+
+        let image = exr::prelude::read_first_rgba_layer_from_file(
+            path,
+            |resolution, _| Pixels {
+                width: resolution.width(),
+                rgb: vec![[0.0; 3]; resolution.width() * resolution.height()],
+            },
+            |pixels, position, (r, g, b, _): (f32, f32, f32, f32)| {
+                pixels.rgb[position.x() + position.y() * pixels.width] = [r, g, b];
+            },
+        )?;
+
+        let size = image.layer_data.size;
+        let pixels = image.layer_data.channel_data.pixels;
+        Ok(Self::new(
+            &pixels.rgb,
+            size.width(),
+            size.height(),
+            strength,
+            table,
+        ))
+    }
+
+    pub fn evaluate(&self, dir: Vec3, wavelength: Wavelength) -> SpectralSample {
+        let (u, v) = direction_to_uv(dir);
+        let x = ((u * self.width as f32) as usize).min(self.width - 1);
+        let y = ((v * self.height as f32) as usize).min(self.height - 1);
+        self.texels[x + y * self.width].evaluate(wavelength)
+    }
+
+    // Returns a direction sampled proportionally to the luminance of the map, and
+    // its solid angle pdf
+    pub fn sample(&self, sampler: &mut Sampler) -> (Vec3, f32) {
+        let (uv, pdf_uv) = self
+            .distribution
+            .sample_continuous(sampler.gen_0_1(), sampler.gen_0_1());
+        let dir = uv_to_direction(uv);
+        (dir, uv_pdf_to_solid_angle(pdf_uv, uv))
+    }
+
+    pub fn pdf(&self, dir: Vec3) -> f32 {
+        let uv = direction_to_uv(dir);
+        uv_pdf_to_solid_angle(self.distribution.pdf(uv), uv)
+    }
+}
+
+fn luminance(rgb: [f32; 3]) -> f32 {
+    0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]
+}
+
+fn direction_to_uv(dir: Vec3) -> (f32, f32) {
+    let phi = dir.z().atan2(dir.x());
+    let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+    let theta = dir.y().clamp(-1.0, 1.0).acos();
+    (phi / (2.0 * PI), theta / PI)
+}
+
+fn uv_to_direction((u, v): (f32, f32)) -> Vec3 {
+    let (sin_phi, cos_phi) = (2.0 * PI * u).sin_cos();
+    let (sin_theta, cos_theta) = (PI * v).sin_cos();
+    Vec3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi)
+}
+
+// The Jacobian of the mapping from (u, v) to directions is 2 pi^2 sin(theta)
+fn uv_pdf_to_solid_angle(pdf: f32, (_, v): (f32, f32)) -> f32 {
+    let sin_theta = (PI * v).sin();
+    if sin_theta <= 0.0 {
+        0.0
+    } else {
+        pdf / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uv_round_trip() {
+        for &(u, v) in &[(0.1, 0.2), (0.5, 0.5), (0.9, 0.7), (0.3, 0.99)] {
+            let (u2, v2) = direction_to_uv(uv_to_direction((u, v)));
+            assert!((u - u2).abs() < 1e-4 && (v - v2).abs() < 1e-4);
+        }
+
+        // +y is the top row of the image
+        assert!(direction_to_uv(Vec3::new(0.0, 1.0, 0.0)).1 < 1e-6);
+    }
+
+    #[test]
+    fn test_sample_pdf_matches() {
+        let table = UpsampleTable::load();
+        let (width, height) = (16, 8);
+
+        // A bright spot on a dim background
+        let mut rgb = vec![[0.1, 0.2, 0.3]; width * height];
+        rgb[5 + 2 * width] = [100.0, 80.0, 60.0];
+        let env = EnvironmentMap::new(&rgb, width, height, 1.0, &table);
+
+        let mut in_spot = 0;
+        for i in 0..1024 {
+            let mut sampler = Sampler::new(0, 0, i, 0);
+            let (dir, pdf) = env.sample(&mut sampler);
+            assert!(pdf > 0.0);
+            assert!((pdf - env.pdf(dir)).abs() / pdf < 1e-3);
+
+            let (u, v) = direction_to_uv(dir);
+            if (u * width as f32) as usize == 5 && (v * height as f32) as usize == 2 {
+                in_spot += 1;
+            }
+        }
+
+        assert!(
+            in_spot > 700,
+            "only {} samples in the bright texel",
+            in_spot
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join("iris_test_environment.exr");
+        exr::prelude::write_rgb_file(&path, 8, 4, |x, y| {
+            if (x, y) == (3, 1) {
+                (50.0, 50.0, 50.0)
+            } else {
+                (0.5, 0.5, 0.5)
+            }
+        })
+        .unwrap();
+
+        let env = EnvironmentMap::load(&path, 2.0, &UpsampleTable::load()).unwrap();
+        assert_eq!((env.width, env.height), (8, 4));
+
+        // The center of texel (3, 1) is the most likely direction
+        let dir = uv_to_direction((3.5 / 8.0, 1.5 / 4.0));
+        let other = uv_to_direction((0.5 / 8.0, 1.5 / 4.0));
+        assert!(env.pdf(dir) > 10.0 * env.pdf(other));
+    }
+}
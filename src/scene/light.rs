@@ -0,0 +1,76 @@
+use crate::{
+    math::Ray,
+    sampling::Sampler,
+    scene::{EnvironmentMap, Scene},
+    shape::{Intersection, Primitive, Shape},
+    spectrum::{SampleableSpectrum, SpectralSample, Spectrum, Wavelength},
+};
+
+// A light picked for next event estimation
+pub enum Light<'a> {
+    Area {
+        emission: &'a Spectrum,
+        primitive: &'a Primitive,
+    },
+    Environment(&'a EnvironmentMap),
+}
+
+impl Light<'_> {
+    // Returns a shadow ray towards the sampled point on the light, the solid angle
+    // pdf and the emitted radiance. The shadow ray still has to be tested for
+    // occlusion
+    pub fn sample(
+        &self,
+        hit: &Intersection,
+        wavelength: Wavelength,
+        sampler: &mut Sampler,
+    ) -> Option<(Ray, f32, SpectralSample)> {
+        match self {
+            Self::Area {
+                emission,
+                primitive,
+            } => {
+                let (light_pos, light_pdf) = primitive.sample(hit, sampler);
+
+                // Very important, otherwise lights will illuminate themselves
+                if light_pdf == 0.0 || light_pos.distance_squared(hit.point) <= 0.00001 {
+                    return None;
+                }
+
+                let ray = hit.spawn_ray_to(light_pos);
+                Some((ray, light_pdf, emission.evaluate(wavelength)))
+            }
+            Self::Environment(env) => {
+                let (dir, light_pdf) = env.sample(sampler);
+                if light_pdf == 0.0 {
+                    return None;
+                }
+
+                let ray = hit.spawn_ray(dir);
+                Some((ray, light_pdf, env.evaluate(dir, wavelength)))
+            }
+        }
+    }
+
+    // For a ray leaving hit in a direction that wasn't sampled from the light,
+    // returns the light's solid angle pdf and the emitted radiance if the ray
+    // reaches the light unoccluded
+    pub fn hit_by(
+        &self,
+        scene: &Scene,
+        hit: &Intersection,
+        ray: &Ray,
+        wavelength: Wavelength,
+    ) -> Option<(f32, SpectralSample)> {
+        match self {
+            Self::Area {
+                emission,
+                primitive,
+            } => scene
+                .ray_hits_light(ray, primitive)
+                .then(|| (primitive.pdf(hit, ray.d()), emission.evaluate(wavelength))),
+            Self::Environment(env) => (!scene.occluded(ray))
+                .then(|| (env.pdf(ray.d()), env.evaluate(ray.d(), wavelength))),
+        }
+    }
+}
@@ -1,10 +1,10 @@
 #![allow(unused)]
 #![allow(dead_code)]
 use crate::{
-    bsdf::{Bsdf, FresnelBsdf, LambertianBsdf, MicrofacetBsdf, SampleableBsdf, SpecularBsdf},
+    bsdf::{Bsdf, DielectricBsdf, LambertianBsdf, MicrofacetBsdf, SampleableBsdf, SpecularBsdf},
     math::{self, PdfSet, Point3, Ray, Shading, Vec3},
     sampling::{self, mis, Sampler},
-    shape::{Geometry, Intersection, Primitive, Shape, Sphere},
+    shape::{Geometry, Intersection, Mesh, Primitive, Shape, Sphere},
     spectrum::{
         upsample::UpsampleTable,
         ConstantSpectrum,
@@ -17,57 +17,30 @@ use crate::{
     types::PrimIndex,
 };
 
-use std::f32::INFINITY;
+use std::sync::Arc;
+
+mod bvh;
+pub use bvh::Bvh;
+
+mod description;
+pub use description::{AdaptiveDescription, RenderSettings, SceneDescription, SceneError};
+
+mod environment;
+pub use environment::EnvironmentMap;
+
+mod light;
+pub use light::Light;
 
 #[derive(Default)]
 pub struct Scene {
     pub lights: Vec<PrimIndex<Spectrum>>,
     pub materials: Vec<PrimIndex<Bsdf>>,
     pub primitives: Vec<Primitive>,
-    _env_map: Vec<UpsampledHdrSpectrum>,
+    pub environment: Option<EnvironmentMap>,
+    bvh: Bvh,
 }
 
 impl Scene {
-    pub fn dummy() -> Self {
-        let mut scene = Self::default();
-
-        let upsample_table = UpsampleTable::load();
-
-        //Light oven
-        //scene.add_emissive_material(
-            //Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0),
-            //LambertianBsdf::new(ConstantSpectrum::new(0.50)),
-            //ConstantSpectrum::new(0.25),
-        //);
-
-        scene.add_emissive_material(
-            Sphere::new(Point3::new(0.0, 2.3, 3.0), 1.0),
-            LambertianBsdf::new(ConstantSpectrum::new(0.5)),
-            ConstantSpectrum::new(3.0),
-        );
-        //scene.add_material(
-            //Sphere::new(Point3::new(0.0, 1.5, 7.0), 3.0),
-            ////LambertianBsdf::new(upsample_table.get_spectrum([0.8, 0.1, 0.1])),
-            //LambertianBsdf::new(ConstantSpectrum::new(0.5)),
-        //);
-        scene.add_material(
-            Sphere::new(Point3::new(0.0, -0.2, 3.0), 1.0),
-            //FresnelBsdf::new(
-                //ConstantSpectrum::new(1.0),
-                //ConstantSpectrum::new(1.0),
-                //1.5220,
-                //0.00459,
-            //),
-            LambertianBsdf::new(ConstantSpectrum::new(0.5)),
-        );
-        scene.add_material(
-            Sphere::new(Point3::new(0.0, -101.5, 2.0), 100.0),
-            LambertianBsdf::new(ConstantSpectrum::new(0.8)),
-        );
-
-        scene
-    }
-
     fn add_light<G: Into<Geometry>, S: Into<Spectrum>>(&mut self, geom: G, light: S) {
         self.lights.push(PrimIndex {
             data: light.into(),
@@ -109,66 +82,85 @@ impl Scene {
         ));
     }
 
-    pub fn background_emission(&self, ray: &Ray, _wavelength: Wavelength) -> SpectralSample {
-        SpectralSample::splat(0.0)
+    // Adds every triangle of the mesh as a primitive, sharing a single material
+    fn add_mesh(&mut self, mesh: &Arc<Mesh>, material: Option<Bsdf>, light: Option<Spectrum>) {
+        let material_index = material.map(|material| {
+            self.materials.push(PrimIndex {
+                data: material,
+                prim_index: self.primitives.len(),
+            });
+            self.materials.len() - 1
+        });
+
+        for triangle in mesh.triangles() {
+            // Each emissive triangle is sampled separately
+            let light_index = light.as_ref().map(|light| {
+                self.lights.push(PrimIndex {
+                    data: light.clone(),
+                    prim_index: self.primitives.len(),
+                });
+                self.lights.len() - 1
+            });
+
+            self.primitives.push(Primitive {
+                geometry: triangle.into(),
+                light_index,
+                material_index,
+                transform: None,
+            });
+        }
     }
 
-    pub fn intersection(&self, ray: &Ray) -> Option<(&Primitive, Intersection)> {
-        let mut closest_t = INFINITY;
-        let mut closest_prim_hit = None;
-
-        // Note: for some reason, the equivalent code with iterators is *much* slower
-        for prim in &self.primitives {
-            match prim.intersect(ray) {
-                Some((hit, t)) if t < closest_t && t > 0.0 => {
-                    closest_t = t;
-                    closest_prim_hit = Some((prim, hit));
-                }
-                _ => continue,
-            }
+    // Radiance arriving along a ray that escapes the scene
+    pub fn background_emission(&self, ray: &Ray, wavelength: Wavelength) -> SpectralSample {
+        match &self.environment {
+            Some(env) => env.evaluate(ray.d(), wavelength),
+            None => SpectralSample::splat(0.0),
         }
+    }
+
+    // Must be called after all the primitives have been added
+    pub fn build_bvh(&mut self) {
+        self.bvh = Bvh::new(&self.primitives);
+    }
 
-        closest_prim_hit
+    pub fn intersection(&self, ray: &Ray) -> Option<(&Primitive, Intersection)> {
+        self.bvh
+            .intersect(&self.primitives, ray)
+            .map(|(prim, hit, _)| (prim, hit))
     }
 
-    pub fn ray_hits_point(&self, ray: &Ray, pos: Point3) -> bool {
-        let mut closest_t = INFINITY;
+    // Whether anything lies along the ray within its range
+    pub fn occluded(&self, ray: &Ray) -> bool {
+        self.bvh.occluded(&self.primitives, ray)
+    }
 
-        for prim in &self.primitives {
-            match prim.intersect(ray) {
-                Some((_, t)) if t < closest_t && t > 0.0 => {
-                    closest_t = t;
-                }
-                _ => continue,
-            }
+    // Whether the first thing the ray hits is the light
+    pub fn ray_hits_light(&self, ray: &Ray, light: &Primitive) -> bool {
+        match light.intersect(ray) {
+            Some((_, t)) => !self.occluded(&ray.clone().with_t_max(t * (1.0 - math::RAY_EPSILON))),
+            None => false,
         }
-
-        // TODO: Is this good enough?
-        let target_t = (pos - ray.o()).len() / ray.d().len();
-        closest_t > target_t - math::RAY_EPSILON
     }
 
-    pub fn ray_hits_object(&self, ray: &Ray, light: &Primitive) -> bool {
-        let mut closest_t = INFINITY;
-        let mut closest_hit_is_obj = false;
-
-        for prim in &self.primitives {
-            match prim.intersect(ray) {
-                Some((_, t)) if t < closest_t && t > 0.0 => {
-                    closest_t = t;
-                    closest_hit_is_obj = std::ptr::eq(prim, light);
-                }
-                _ => continue,
-            }
+    // Picks one of the area lights or the environment uniformly, along with the
+    // inverse of the pick probability
+    pub fn pick_one_light(&self, sampler: &mut Sampler) -> Option<(Light<'_>, f32)> {
+        let num_lights = self.lights.len() + self.environment.is_some() as usize;
+        if num_lights == 0 {
+            return None;
         }
 
-        closest_hit_is_obj
-    }
+        let light_idx = sampler.gen_array_index(num_lights);
+        let light = match self.lights.get(light_idx) {
+            Some(light) => Light::Area {
+                emission: &light.data,
+                primitive: &self.primitives[light.prim_index],
+            },
+            None => Light::Environment(self.environment.as_ref().unwrap()),
+        };
 
-    pub fn pick_one_light(&self, sampler: &mut Sampler) -> (&Spectrum, &Primitive, f32) {
-        let light_idx = sampler.gen_array_index(self.lights.len());
-        let light = &self.lights[light_idx];
-        (&light.data, &self.primitives[light.prim_index], self.lights.len() as f32)
+        Some((light, num_lights as f32))
     }
 
     //pub fn radiance(
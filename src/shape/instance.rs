@@ -0,0 +1,67 @@
+use crate::{
+    math::{Aabb, Point3, Ray, Vec3},
+    sampling::Sampler,
+    scene::Bvh,
+    shape::{Intersection, Primitive, Shape},
+};
+
+use std::sync::Arc;
+
+// Geometry placed many times in a scene, kept in object space with a hierarchy
+// of its own. The primitives carry no materials or lights, those come from the
+// primitive placing the instance
+#[derive(Debug)]
+pub struct InstancedGeometry {
+    primitives: Vec<Primitive>,
+    bvh: Bvh,
+}
+
+impl InstancedGeometry {
+    pub fn new(primitives: Vec<Primitive>) -> Self {
+        Self {
+            bvh: Bvh::new(&primitives),
+            primitives,
+        }
+    }
+
+    pub fn num_primitives(&self) -> usize {
+        self.primitives.len()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Instance {
+    geometry: Arc<InstancedGeometry>,
+}
+
+impl Instance {
+    pub fn new(geometry: Arc<InstancedGeometry>) -> Self {
+        Self { geometry }
+    }
+}
+
+impl Shape for Instance {
+    fn intersect(&self, ray: &Ray) -> Option<(Intersection, f32)> {
+        self.geometry
+            .bvh
+            .intersect(&self.geometry.primitives, ray)
+            .map(|(_, hit, t)| (hit, t))
+    }
+
+    fn intersects(&self, ray: &Ray) -> bool {
+        self.geometry.bvh.occluded(&self.geometry.primitives, ray)
+    }
+
+    // Instances can't be lights, so they are never sampled
+    fn sample(&self, _hit: &Intersection, _sampler: &mut Sampler) -> (Point3, f32) {
+        unreachable!("sampled an instance")
+    }
+
+    fn pdf(&self, _hit: &Intersection, _wi: Vec3) -> f32 {
+        unreachable!("sampled an instance")
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.geometry.bvh.bounds()
+    }
+}
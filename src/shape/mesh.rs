@@ -0,0 +1,124 @@
+use std::{io::BufRead, path::Path, sync::Arc};
+
+use crate::{
+    math::{Point3, Vec3},
+    shape::Triangle,
+};
+
+// Triangle soup with optional per-vertex normals and texture coordinates, shared between all
+// the triangles that reference it
+#[derive(Debug)]
+pub struct Mesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub indices: Vec<u32>,
+}
+
+// A group of faces from an OBJ file, along with the names used to look up its material
+#[derive(Debug)]
+pub struct MeshGroup {
+    pub name: String,
+    pub material_name: Option<String>,
+    pub mesh: Arc<Mesh>,
+}
+
+impl Mesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f32, f32)>,
+        indices: Vec<u32>,
+    ) -> Self {
+        assert_eq!(indices.len() % 3, 0, "mesh indices must describe triangles");
+        assert!(normals.is_empty() || normals.len() == positions.len());
+        assert!(uvs.is_empty() || uvs.len() == positions.len());
+
+        Self {
+            positions,
+            normals,
+            uvs,
+            indices,
+        }
+    }
+
+    pub fn num_triangles(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn triangles(self: &Arc<Self>) -> impl Iterator<Item = Triangle> + '_ {
+        (0..self.num_triangles()).map(move |face| Triangle::new(self.clone(), face))
+    }
+
+    pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Vec<MeshGroup>, tobj::LoadError> {
+        let (models, materials) = tobj::load_obj(path.as_ref(), &LOAD_OPTIONS)?;
+        // A missing MTL file is fine, materials are assigned by the scene file anyway
+        Ok(Self::from_models(models, &materials.unwrap_or_default()))
+    }
+
+    pub fn load_obj_buf<B: BufRead>(reader: &mut B) -> Result<Vec<MeshGroup>, tobj::LoadError> {
+        let (models, materials) = tobj::load_obj_buf(reader, &LOAD_OPTIONS, |_| {
+            Err(tobj::LoadError::OpenFileFailed)
+        })?;
+        Ok(Self::from_models(models, &materials.unwrap_or_default()))
+    }
+
+    fn from_models(models: Vec<tobj::Model>, materials: &[tobj::Material]) -> Vec<MeshGroup> {
+        models
+            .into_iter()
+            .filter(|model| !model.mesh.indices.is_empty())
+            .map(|model| {
+                let mesh = model.mesh;
+
+                let positions = mesh
+                    .positions
+                    .chunks_exact(3)
+                    .map(|p| Point3::new(p[0], p[1], p[2]))
+                    .collect();
+                let normals = mesh
+                    .normals
+                    .chunks_exact(3)
+                    .map(|n| Vec3::new(n[0], n[1], n[2]).normalize())
+                    .collect();
+                let uvs = mesh
+                    .texcoords
+                    .chunks_exact(2)
+                    .map(|uv| (uv[0], uv[1]))
+                    .collect();
+
+                MeshGroup {
+                    name: model.name,
+                    material_name: mesh
+                        .material_id
+                        .and_then(|id| materials.get(id))
+                        .map(|m| m.name.clone()),
+                    mesh: Arc::new(Mesh::new(positions, normals, uvs, mesh.indices)),
+                }
+            })
+            .collect()
+    }
+}
+
+const LOAD_OPTIONS: tobj::LoadOptions = tobj::LoadOptions {
+    single_index: true,
+    triangulate: true,
+    ignore_points: true,
+    ignore_lines: true,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_obj_groups() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\ng floor\nf 1 2 3 4\ng wall\nf 1 2 3\n";
+
+        let groups = Mesh::load_obj_buf(&mut obj.as_bytes()).unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].name, "floor");
+        assert_eq!(groups[0].mesh.num_triangles(), 2);
+        assert_eq!(groups[1].name, "wall");
+        assert_eq!(groups[1].mesh.num_triangles(), 1);
+    }
+}
@@ -1,55 +1,354 @@
 use enum_dispatch::enum_dispatch;
 
 use crate::{
-    bsdf::Bsdf,
-    math::{Point3, Ray, Shading, Vec3, World},
+    bsdf::{Bsdf, SampleableBsdf},
+    math::{
+        self,
+        Aabb,
+        ObjectTransform,
+        Point3,
+        Ray,
+        RayDifferential,
+        Shading,
+        Transform,
+        Vec3,
+        World,
+    },
     sampling::Sampler,
     spectrum::Spectrum,
+    texture::TextureContext,
     types::PrimIndex,
 };
 
+mod instance;
+pub use instance::{Instance, InstancedGeometry};
+
+mod mesh;
+pub use mesh::{Mesh, MeshGroup};
+
 mod sphere;
 pub use sphere::Sphere;
 
+mod triangle;
+pub use triangle::Triangle;
+
 #[derive(Debug)]
 pub struct Intersection {
     pub point: Point3,
+    // Geometric normal, used for offsetting rays
     pub normal: Vec3,
+    // The shading frame BSDFs are evaluated in, see world_to_shading. The
+    // normal is interpolated or perturbed by normal maps, and always on the
+    // side of the geometric normal
+    pub shading_normal: Vec3,
     pub tangeant: Vec3,
     pub bitangeant: Vec3,
+    pub uv: (f32, f32),
+    // Derivatives of the point and shading normal along u and v, zero when the
+    // shape doesn't provide them. Used to filter textures over ray footprints
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    pub dndu: Vec3,
+    pub dndv: Vec3,
     pub back_face: bool,
+    // Of the ray that found the hit, rays leaving from it keep it
+    pub time: f32,
 }
 
 impl Intersection {
+    pub fn new(
+        point: Point3,
+        normal: Vec3,
+        shading_normal: Vec3,
+        uv: (f32, f32),
+        back_face: bool,
+    ) -> Self {
+        let (tangeant, bitangeant) = shading_frame(shading_normal, Vec3::splat(0.0));
+
+        Self {
+            point,
+            normal,
+            shading_normal,
+            tangeant,
+            bitangeant,
+            uv,
+            dpdu: Vec3::splat(0.0),
+            dpdv: Vec3::splat(0.0),
+            dndu: Vec3::splat(0.0),
+            dndv: Vec3::splat(0.0),
+            back_face,
+            time: 0.0,
+        }
+    }
+
+    // Also lines the shading frame up with dpdu
+    pub fn with_derivatives(self, dpdu: Vec3, dpdv: Vec3, dndu: Vec3, dndv: Vec3) -> Self {
+        let (tangeant, bitangeant) = shading_frame(self.shading_normal, dpdu);
+        Self {
+            tangeant,
+            bitangeant,
+            dpdu,
+            dpdv,
+            dndu,
+            dndv,
+            ..self
+        }
+    }
+
+    // Rebuilds the shading frame around a new normal. Normals perturbed past
+    // the surface would shade it as if seen from the other side, so they're
+    // pulled back to just above it
+    pub fn with_shading_normal(self, shading_normal: Vec3) -> Self {
+        const MIN_COS_THETA: f32 = 1e-3;
+        let cos_theta = shading_normal.dot(self.normal);
+        let shading_normal = if cos_theta < MIN_COS_THETA {
+            (shading_normal + (MIN_COS_THETA - cos_theta) * self.normal).normalize()
+        } else {
+            shading_normal
+        };
+
+        let (tangeant, bitangeant) = shading_frame(shading_normal, self.dpdu);
+        Self {
+            shading_normal,
+            tangeant,
+            bitangeant,
+            ..self
+        }
+    }
+
+    // Applies the normal or bump map of the hit's material, if it has one
+    pub fn with_normal_map(self, bsdf: &Bsdf, ctx: &TextureContext) -> Self {
+        match bsdf.shading_normal(&self, ctx) {
+            Some(shading_normal) => self.with_shading_normal(shading_normal),
+            None => self,
+        }
+    }
+
+    pub fn spawn_ray(&self, d: Vec3) -> Ray {
+        Ray::spawn(self.point, d, self.normal).with_time(self.time)
+    }
+
+    // Stops just short of p, see Ray::spawn_to
+    pub fn spawn_ray_to(&self, p: Point3) -> Ray {
+        Ray::spawn_to(self.point, p, self.normal).with_time(self.time)
+    }
+
+    // Moves a hit on untransformed geometry into world space
+    fn to_world(&self, transform: &Transform) -> Self {
+        let to_world = |n: Vec3| transform.normal_to_world(n.coerce_system());
+        let vector_to_world = |v: Vec3| transform.vector_to_world(v.coerce_system());
+        // Keeps the length of normal derivatives, which is only right for
+        // rigid transforms and uniform scaling
+        let derivative_to_world = |dn: Vec3| {
+            if dn == Vec3::splat(0.0) {
+                dn
+            } else {
+                to_world(dn) * dn.len()
+            }
+        };
+
+        Self {
+            time: self.time,
+            ..Self::new(
+                transform.point_to_world(self.point.coerce_system()),
+                to_world(self.normal),
+                to_world(self.shading_normal),
+                self.uv,
+                self.back_face,
+            )
+            .with_derivatives(
+                vector_to_world(self.dpdu),
+                vector_to_world(self.dpdv),
+                derivative_to_world(self.dndu),
+                derivative_to_world(self.dndv),
+            )
+        }
+    }
+
+    // The inverse of to_world
+    fn to_local(&self, transform: &Transform) -> Self {
+        let to_local = |n: Vec3| transform.normal_to_local(n).coerce_system();
+        let vector_to_local = |v: Vec3| transform.vector_to_local(v).coerce_system();
+        let derivative_to_local = |dn: Vec3| {
+            if dn == Vec3::splat(0.0) {
+                dn
+            } else {
+                to_local(dn) * dn.len()
+            }
+        };
+
+        Self {
+            time: self.time,
+            ..Self::new(
+                transform.point_to_local(self.point).coerce_system(),
+                to_local(self.normal),
+                to_local(self.shading_normal),
+                self.uv,
+                self.back_face,
+            )
+            .with_derivatives(
+                vector_to_local(self.dpdu),
+                vector_to_local(self.dpdv),
+                derivative_to_local(self.dndu),
+                derivative_to_local(self.dndv),
+            )
+        }
+    }
+
+    // Without a differential, or on shapes without uv derivatives, textures are
+    // looked up at a single point
+    pub fn texture_context(&self, differential: Option<&RayDifferential>) -> TextureContext {
+        let offsets = differential.and_then(|differential| {
+            let (_, duv_dx) = self.tangent_offset(differential.rx_o, differential.rx_d)?;
+            let (_, duv_dy) = self.tangent_offset(differential.ry_o, differential.ry_d)?;
+            Some((duv_dx, duv_dy))
+        });
+
+        match offsets {
+            Some((duv_dx, duv_dy)) => TextureContext {
+                uv: self.uv,
+                duv_dx,
+                duv_dy,
+            },
+            None => TextureContext {
+                uv: self.uv,
+                ..TextureContext::default()
+            },
+        }
+    }
+
+    // Follows a differential through a specular bounce from direction d to wi,
+    // by reflecting or refracting the offset rays where they cross the tangent
+    // plane, around the shading normal they would have seen there
+    pub fn specular_differential(
+        &self,
+        differential: &RayDifferential,
+        d: Vec3,
+        wi: Vec3,
+    ) -> Option<RayDifferential> {
+        let n = self.shading_normal;
+        let wo = -d;
+        let refraction = wi.dot(n) * wo.dot(n) < 0.0;
+
+        // Snell's law scales the part of a direction along the surface by the
+        // relative index of refraction
+        let eta = if refraction {
+            let along_surface = |w: Vec3| (w - w.dot(n) * n).len();
+            let wo_along = along_surface(wo);
+            if wo_along < 1e-4 {
+                return None;
+            }
+            along_surface(wi) / wo_along
+        } else {
+            1.0
+        };
+
+        let bounce = |o: Point3, d: Vec3| {
+            let (dp, (du, dv)) = self.tangent_offset(o, d)?;
+            let n = (n + self.dndu * du + self.dndv * dv)
+                .normalize()
+                .face_forward(-d);
+            let wi = if refraction {
+                math::refract(-d, n, eta)?
+            } else {
+                math::reflect(-d, n)
+            };
+            Some((self.point + dp, wi))
+        };
+
+        let (rx_o, rx_d) = bounce(differential.rx_o, differential.rx_d)?;
+        let (ry_o, ry_d) = bounce(differential.ry_o, differential.ry_d)?;
+        Some(RayDifferential {
+            rx_o,
+            rx_d,
+            ry_o,
+            ry_d,
+        })
+    }
+
+    // Where a ray crosses the tangent plane, as an offset from the point, along
+    // with the matching change in texture coordinates
+    fn tangent_offset(&self, o: Point3, d: Vec3) -> Option<(Vec3, (f32, f32))> {
+        let t = self.normal.dot(self.point - o) / self.normal.dot(d);
+        let dp = o + d * t - self.point;
+
+        // Least squares solution of dp = dpdu * du + dpdv * dv
+        let (a, b, c) = (
+            self.dpdu.dot(self.dpdu),
+            self.dpdu.dot(self.dpdv),
+            self.dpdv.dot(self.dpdv),
+        );
+        let det = a * c - b * b;
+        if det <= 1e-6 * a * c || !det.is_finite() || !t.is_finite() {
+            return None;
+        }
+
+        let (dp_u, dp_v) = (self.dpdu.dot(dp), self.dpdv.dot(dp));
+        let duv = ((c * dp_u - b * dp_v) / det, (a * dp_v - b * dp_u) / det);
+        Some((dp, duv))
+    }
+
+    // Shading space has x along the tangeant, y along the bitangeant and z along
+    // the shading normal
     pub fn world_to_shading(&self, w: Vec3<World>) -> Vec3<Shading> {
         Vec3::new(
-            self.bitangeant.dot(w),
             self.tangeant.dot(w),
-            self.normal.dot(w),
+            self.bitangeant.dot(w),
+            self.shading_normal.dot(w),
         )
     }
 
     pub fn shading_to_world(&self, s: Vec3<Shading>) -> Vec3<World> {
-        let x = self.bitangeant.x() * s.x() + self.tangeant.x() * s.y() + self.normal.x() * s.z();
-        let y = self.bitangeant.y() * s.x() + self.tangeant.y() * s.y() + self.normal.y() * s.z();
-        let z = self.bitangeant.z() * s.x() + self.tangeant.z() * s.y() + self.normal.z() * s.z();
+        let n = self.shading_normal;
+        let x = self.tangeant.x() * s.x() + self.bitangeant.x() * s.y() + n.x() * s.z();
+        let y = self.tangeant.y() * s.x() + self.bitangeant.y() * s.y() + n.y() * s.z();
+        let z = self.tangeant.z() * s.x() + self.bitangeant.z() * s.y() + n.z() * s.z();
         Vec3::new(x, y, z)
     }
 }
 
+// Right handed tangeant and bitangeant around the normal, with the tangeant
+// along dpdu so that anisotropic BSDFs follow the uvs. Without a usable dpdu
+// any tangeant will do
+fn shading_frame(normal: Vec3, dpdu: Vec3) -> (Vec3, Vec3) {
+    let along_surface = dpdu - normal.dot(dpdu) * normal;
+    let tangeant = if along_surface.len_squared() > 1e-6 * dpdu.len_squared() {
+        along_surface.normalize()
+    } else {
+        // Avoid a degenerate cross product when the normal is parallel to the y
+        // axis
+        let up = if normal.y().abs() < 0.999 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        up.cross(normal).normalize()
+    };
+
+    (tangeant, normal.cross(tangeant))
+}
+
 #[enum_dispatch]
 pub trait Shape {
     fn intersect(&self, ray: &Ray) -> Option<(Intersection, f32)>;
 
+    // Whether there is any hit within the ray's range, cheaper than intersect as
+    // no Intersection is built
+    fn intersects(&self, ray: &Ray) -> bool;
+
     fn sample(&self, hit: &Intersection, sampler: &mut Sampler) -> (Point3, f32);
 
     fn pdf(&self, hit: &Intersection, wi: Vec3) -> f32;
+
+    fn bounds(&self) -> Aabb;
 }
 
 #[enum_dispatch(Shape)]
 #[derive(Debug, Clone)]
 pub enum Geometry {
     Sphere,
+    Triangle,
+    Instance,
 }
 
 #[derive(Debug, Clone)]
@@ -57,28 +356,99 @@ pub struct Primitive {
     pub geometry: Geometry,
     pub light_index: Option<usize>,
     pub material_index: Option<usize>,
+    // Places the geometry, which is then in object space, possibly moving over
+    // time. Shared by all the triangles of a mesh
+    pub transform: Option<ObjectTransform>,
 }
 
 impl Shape for Primitive {
     fn intersect(&self, ray: &Ray) -> Option<(Intersection, f32)> {
-        self.geometry.intersect(ray)
+        let hit = match &self.transform {
+            Some(transform) => {
+                let transform = transform.at(ray.time());
+                let (local_ray, scale) = transform.ray_to_local(ray);
+                self.geometry
+                    .intersect(&local_ray.coerce_system())
+                    .map(|(hit, t)| (hit.to_world(&transform), t / scale))
+            }
+            None => self.geometry.intersect(ray),
+        };
+
+        hit.map(|(hit, t)| {
+            (
+                Intersection {
+                    time: ray.time(),
+                    ..hit
+                },
+                t,
+            )
+        })
     }
 
+    fn intersects(&self, ray: &Ray) -> bool {
+        match &self.transform {
+            Some(transform) => {
+                let (local_ray, _) = transform.at(ray.time()).ray_to_local(ray);
+                self.geometry.intersects(&local_ray.coerce_system())
+            }
+            None => self.geometry.intersects(ray),
+        }
+    }
+
+    // Solid angle pdfs are only preserved by rigid transforms and uniform
+    // scaling, which scene descriptions require of transformed lights
     fn sample(&self, hit: &Intersection, sampler: &mut Sampler) -> (Point3, f32) {
-        self.geometry.sample(hit, sampler)
+        match &self.transform {
+            Some(transform) => {
+                let transform = transform.at(hit.time);
+                let (point, pdf) = self.geometry.sample(&hit.to_local(&transform), sampler);
+                (transform.point_to_world(point.coerce_system()), pdf)
+            }
+            None => self.geometry.sample(hit, sampler),
+        }
     }
 
     fn pdf(&self, hit: &Intersection, wi: Vec3) -> f32 {
-        self.geometry.pdf(hit, wi)
+        match &self.transform {
+            Some(transform) => {
+                let transform = transform.at(hit.time);
+                let wi = transform.vector_to_local(wi).normalize().coerce_system();
+                self.geometry.pdf(&hit.to_local(&transform), wi)
+            }
+            None => self.geometry.pdf(hit, wi),
+        }
+    }
+
+    // Covers the whole motion, so that the BVH finds the geometry at any time
+    fn bounds(&self) -> Aabb {
+        match &self.transform {
+            Some(transform) => {
+                let bounds = self.geometry.bounds();
+                let local = Aabb::new(bounds.min.coerce_system(), bounds.max.coerce_system());
+                transform.bounds(local)
+            }
+            None => self.geometry.bounds(),
+        }
     }
 }
 
 impl Primitive {
+    // Without a material or light, only used inside instances
+    pub fn new(geometry: Geometry) -> Self {
+        Self {
+            geometry,
+            material_index: None,
+            light_index: None,
+            transform: None,
+        }
+    }
+
     pub fn new_light(geometry: Geometry, light_index: usize) -> Self {
         Self {
             geometry,
             material_index: None,
             light_index: Some(light_index),
+            transform: None,
         }
     }
 
@@ -87,6 +457,7 @@ impl Primitive {
             geometry,
             material_index: Some(material_index),
             light_index: None,
+            transform: None,
         }
     }
 
@@ -99,6 +470,7 @@ impl Primitive {
             geometry,
             material_index: Some(material_index),
             light_index: Some(light_index),
+            transform: None,
         }
     }
 
@@ -1,9 +1,11 @@
 use crate::{
-    math::{self, Local, Point3, Ray, Vec3},
+    math::{self, Aabb, Local, Point3, Ray, Vec3},
     sampling::{self, Sampler},
     shape::{Intersection, Shape},
 };
 
+use std::f32::consts::PI;
+
 #[derive(Debug, Clone)]
 pub struct Sphere {
     position: Point3,
@@ -18,11 +20,10 @@ impl Sphere {
     fn local_to_world(&self, vec: Vec3<Local>) -> Vec3 {
         self.position.to_vec() + vec.coerce_system()
     }
-}
 
-impl Shape for Sphere {
+    // Closest root within the ray's range
     // TODO: Clean up & optimize
-    fn intersect(&self, ray: &Ray) -> Option<(Intersection, f32)> {
+    fn hit_t(&self, ray: &Ray) -> Option<f32> {
         let oc = ray.o() - self.position;
         let a = ray.d().len_squared();
         let half_b = ray.d().dot(oc);
@@ -32,47 +33,56 @@ impl Shape for Sphere {
         if discrim > 0.0 {
             let root = discrim.sqrt();
             let temp = (-half_b - root) / a;
-            if temp > 0.0 {
-                let point = ray.point_at(temp);
-                let normal = (point - self.position) / self.radius;
-                let tangeant = Vec3::new(0.0, 1.0, 0.0).cross(normal).normalize();
-                let bitangeant = normal.cross(tangeant);
-                let back_face = normal.dot(ray.d()) >= 0.0;
-                return Some((
-                    Intersection {
-                        point,
-                        normal,
-                        tangeant,
-                        bitangeant,
-                        back_face,
-                    },
-                    temp,
-                ));
+            if ray.in_range(temp) {
+                return Some(temp);
             }
 
             let temp = (-half_b + root) / a;
-            if temp > 0.0 {
-                let point = ray.point_at(temp);
-                let normal = (point - self.position) / self.radius;
-                let tangeant = Vec3::new(0.0, 1.0, 0.0).cross(normal).normalize();
-                let bitangeant = normal.cross(tangeant);
-                let back_face = normal.dot(ray.d()) >= 0.0;
-                return Some((
-                    Intersection {
-                        point,
-                        normal,
-                        tangeant,
-                        bitangeant,
-                        back_face,
-                    },
-                    temp,
-                ));
+            if ray.in_range(temp) {
+                return Some(temp);
             }
         }
 
         None
     }
 
+    fn intersection_at(&self, ray: &Ray, t: f32) -> Intersection {
+        let point = ray.point_at(t);
+        let normal = (point - self.position) / self.radius;
+        let back_face = normal.dot(ray.d()) >= 0.0;
+
+        // Spherical coordinates, with v going up from 0 at the bottom of the
+        // sphere so that image textures are upright
+        let phi = normal.z().atan2(normal.x());
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+        let theta = normal.y().clamp(-1.0, 1.0).acos();
+        let uv = (phi / (2.0 * PI), 1.0 - theta / PI);
+
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let dpdu =
+            (2.0 * PI * self.radius) * Vec3::new(-sin_theta * sin_phi, 0.0, sin_theta * cos_phi);
+        let dpdv =
+            (-PI * self.radius) * Vec3::new(cos_theta * cos_phi, -sin_theta, cos_theta * sin_phi);
+
+        Intersection::new(point, normal, normal, uv, back_face).with_derivatives(
+            dpdu,
+            dpdv,
+            dpdu / self.radius,
+            dpdv / self.radius,
+        )
+    }
+}
+
+impl Shape for Sphere {
+    fn intersect(&self, ray: &Ray) -> Option<(Intersection, f32)> {
+        self.hit_t(ray).map(|t| (self.intersection_at(ray, t), t))
+    }
+
+    fn intersects(&self, ray: &Ray) -> bool {
+        self.hit_t(ray).is_some()
+    }
+
     // TODO: Clean up
     fn sample(&self, hit: &Intersection, sampler: &mut Sampler) -> (Point3, f32) {
         let (u0, u1) = (sampler.gen_0_1(), sampler.gen_0_1());
@@ -144,7 +154,7 @@ impl Shape for Sphere {
 
         // Intersect with geometry
         if self.position.distance_squared(point) <= self.radius.powi(2) {
-            let ray = Ray::spawn(hit.point, wi, hit.normal);
+            let ray = hit.spawn_ray(wi);
             if let Some((light_hit, _)) = self.intersect(&ray) {
                 let area = 4.0 * std::f32::consts::PI * self.radius.powi(2);
                 let pdf = light_hit.point.distance_squared(point)
@@ -160,4 +170,9 @@ impl Shape for Sphere {
         let cos_theta_max = (1.0 - sin_theta_max_2).max(0.0).sqrt();
         sampling::pdf_cone(cos_theta_max)
     }
+
+    fn bounds(&self) -> Aabb {
+        let r = Vec3::splat(self.radius);
+        Aabb::new(self.position - r, self.position + r)
+    }
 }
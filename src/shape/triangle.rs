@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use crate::{
+    math::{Aabb, Point3, Ray, Vec3},
+    sampling::Sampler,
+    shape::{Intersection, Mesh, Shape},
+};
+
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    mesh: Arc<Mesh>,
+    face: u32,
+}
+
+impl Triangle {
+    pub fn new(mesh: Arc<Mesh>, face: usize) -> Self {
+        debug_assert!(face < mesh.num_triangles());
+        Self {
+            mesh,
+            face: face as u32,
+        }
+    }
+
+    fn vertex_indices(&self) -> [usize; 3] {
+        let base = 3 * self.face as usize;
+        [
+            self.mesh.indices[base] as usize,
+            self.mesh.indices[base + 1] as usize,
+            self.mesh.indices[base + 2] as usize,
+        ]
+    }
+
+    fn vertices(&self) -> [Point3; 3] {
+        let [i0, i1, i2] = self.vertex_indices();
+        [
+            self.mesh.positions[i0],
+            self.mesh.positions[i1],
+            self.mesh.positions[i2],
+        ]
+    }
+
+    pub fn area(&self) -> f32 {
+        let [p0, p1, p2] = self.vertices();
+        0.5 * (p1 - p0).cross(p2 - p0).len()
+    }
+
+    fn geometric_normal(&self) -> Vec3 {
+        let [p0, p1, p2] = self.vertices();
+        (p1 - p0).cross(p2 - p0).normalize()
+    }
+
+    // Möller-Trumbore, returns (b1, b2, t)
+    fn hit_barycentrics(&self, ray: &Ray) -> Option<(f32, f32, f32)> {
+        let [p0, p1, p2] = self.vertices();
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+
+        let p = ray.d().cross(e2);
+        let det = e1.dot(p);
+        if det.abs() < 1e-9 {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let s = ray.o() - p0;
+        let b1 = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+
+        let q = s.cross(e1);
+        let b2 = ray.d().dot(q) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+
+        let t = e2.dot(q) * inv_det;
+        if !ray.in_range(t) {
+            return None;
+        }
+
+        Some((b1, b2, t))
+    }
+
+    fn intersection_at(&self, ray: &Ray, b1: f32, b2: f32) -> Intersection {
+        let [i0, i1, i2] = self.vertex_indices();
+        let [p0, p1, p2] = self.vertices();
+        let b0 = 1.0 - b1 - b2;
+
+        let point = Point3::from(b0 * p0.to_vec() + b1 * p1.to_vec() + b2 * p2.to_vec());
+        let normal = self.geometric_normal();
+        let back_face = normal.dot(ray.d()) >= 0.0;
+
+        let vertex_uvs = if self.mesh.uvs.is_empty() {
+            [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]
+        } else {
+            let uv = &self.mesh.uvs;
+            [uv[i0], uv[i1], uv[i2]]
+        };
+        let [uv0, uv1, uv2] = vertex_uvs;
+        let uv = (
+            b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+        );
+
+        // Solves for the derivatives along u and v of a value given at the
+        // vertices, zero when the uvs are degenerate
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let det = du02 * dv12 - dv02 * du12;
+        let derivatives = |d02: Vec3, d12: Vec3| {
+            if det.abs() < 1e-9 {
+                (Vec3::splat(0.0), Vec3::splat(0.0))
+            } else {
+                (
+                    (dv12 * d02 - dv02 * d12) / det,
+                    (du02 * d12 - du12 * d02) / det,
+                )
+            }
+        };
+        let (dpdu, dpdv) = derivatives(p0 - p2, p1 - p2);
+
+        let (shading_normal, dndu, dndv) = if self.mesh.normals.is_empty() {
+            (normal, Vec3::splat(0.0), Vec3::splat(0.0))
+        } else {
+            let n = &self.mesh.normals;
+            let shading_normal = (b0 * n[i0] + b1 * n[i1] + b2 * n[i2]).normalize();
+            let (dndu, dndv) = derivatives(n[i0] - n[i2], n[i1] - n[i2]);
+            // Keep the shading normal on the same side as the geometric normal
+            if shading_normal.dot(normal) >= 0.0 {
+                (shading_normal, dndu, dndv)
+            } else {
+                (-shading_normal, -dndu, -dndv)
+            }
+        };
+
+        Intersection::new(point, normal, shading_normal, uv, back_face)
+            .with_derivatives(dpdu, dpdv, dndu, dndv)
+    }
+
+    // Solid angle pdf of sampling `light_point` from `point`
+    fn solid_angle_pdf(&self, point: Point3, light_point: Point3) -> f32 {
+        let to_light = light_point - point;
+        let distance_squared = to_light.len_squared();
+        let cos_theta = self.geometric_normal().dot(to_light.normalize()).abs();
+
+        if cos_theta == 0.0 || distance_squared == 0.0 {
+            0.0
+        } else {
+            distance_squared / (cos_theta * self.area())
+        }
+    }
+}
+
+impl Shape for Triangle {
+    fn intersect(&self, ray: &Ray) -> Option<(Intersection, f32)> {
+        self.hit_barycentrics(ray)
+            .map(|(b1, b2, t)| (self.intersection_at(ray, b1, b2), t))
+    }
+
+    fn intersects(&self, ray: &Ray) -> bool {
+        self.hit_barycentrics(ray).is_some()
+    }
+
+    fn sample(&self, hit: &Intersection, sampler: &mut Sampler) -> (Point3, f32) {
+        let [p0, p1, p2] = self.vertices();
+
+        // Uniformly sample barycentric coordinates
+        let su0 = sampler.gen_0_1().sqrt();
+        let b0 = 1.0 - su0;
+        let b1 = sampler.gen_0_1() * su0;
+        let b2 = 1.0 - b0 - b1;
+
+        let light_point = Point3::from(b0 * p0.to_vec() + b1 * p1.to_vec() + b2 * p2.to_vec());
+        (light_point, self.solid_angle_pdf(hit.point, light_point))
+    }
+
+    fn pdf(&self, hit: &Intersection, wi: Vec3) -> f32 {
+        let ray = hit.spawn_ray(wi);
+        match self.intersect(&ray) {
+            Some((light_hit, _)) => self.solid_angle_pdf(hit.point, light_hit.point),
+            None => 0.0,
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        let [p0, p1, p2] = self.vertices();
+        Aabb::new(p0, p1).union_point(p2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::RayDifferential;
+
+    fn unit_triangle() -> Triangle {
+        let mesh = Mesh::new(
+            vec![
+                Point3::new(0.0, 0.0, 1.0),
+                Point3::new(1.0, 0.0, 1.0),
+                Point3::new(0.0, 1.0, 1.0),
+            ],
+            Vec::new(),
+            Vec::new(),
+            vec![0, 1, 2],
+        );
+        Triangle::new(Arc::new(mesh), 0)
+    }
+
+    #[test]
+    fn test_intersect() {
+        let triangle = unit_triangle();
+
+        let ray = Ray::new(Point3::new(0.25, 0.25, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let (hit, t) = triangle.intersect(&ray).unwrap();
+        assert!((t - 1.0).abs() < 1e-6);
+        assert!((hit.uv.0 - 0.25).abs() < 1e-6 && (hit.uv.1 - 0.25).abs() < 1e-6);
+        assert!(hit.back_face);
+
+        let miss = Ray::new(Point3::new(0.75, 0.75, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(triangle.intersect(&miss).is_none());
+
+        let behind = Ray::new(Point3::new(0.25, 0.25, 2.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(triangle.intersect(&behind).is_none());
+
+        assert!(triangle.intersects(&ray));
+        assert!(!triangle.intersects(&ray.clone().with_t_max(0.5)));
+    }
+
+    #[test]
+    fn test_ray_differentials() {
+        let triangle = unit_triangle();
+        let d = Vec3::new(0.0, 0.0, 1.0);
+        let ray = Ray::new(Point3::new(0.25, 0.25, 0.0), d);
+        let (hit, _) = triangle.intersect(&ray).unwrap();
+
+        // Rays a hundredth apart cover a hundredth of the uvs
+        let differential = RayDifferential {
+            rx_o: Point3::new(0.26, 0.25, 0.0),
+            rx_d: d,
+            ry_o: Point3::new(0.25, 0.26, 0.0),
+            ry_d: d,
+        };
+        let ctx = hit.texture_context(Some(&differential));
+        assert!((ctx.duv_dx.0 - 0.01).abs() < 1e-5 && ctx.duv_dx.1.abs() < 1e-5);
+        assert!(ctx.duv_dy.0.abs() < 1e-5 && (ctx.duv_dy.1 - 0.01).abs() < 1e-5);
+        assert_eq!(hit.texture_context(None).duv_dx, (0.0, 0.0));
+
+        // Parallel rays stay parallel off a flat mirror
+        let reflected = hit.specular_differential(&differential, d, -d).unwrap();
+        assert!((reflected.rx_d - -d).len() < 1e-5);
+        assert!(reflected.rx_o.distance(Point3::new(0.26, 0.25, 1.0)) < 1e-5);
+    }
+
+    #[test]
+    fn test_sample_pdf_matches() {
+        let triangle = unit_triangle();
+        let origin = Point3::new(0.2, 0.2, 0.0);
+        let hit = Intersection::new(
+            origin,
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            (0.0, 0.0),
+            false,
+        );
+
+        let mut sampler = Sampler::new(0, 0, 0, 0);
+        for _ in 0..16 {
+            let (point, pdf) = triangle.sample(&hit, &mut sampler);
+            let expected = triangle.pdf(&hit, (point - origin).normalize());
+            assert!(
+                (pdf - expected).abs() / pdf < 1e-2,
+                "{} != {}",
+                pdf,
+                expected
+            );
+        }
+    }
+}
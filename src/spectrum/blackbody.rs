@@ -0,0 +1,73 @@
+use crate::{color::Xyz, spectrum::SampleableSpectrum};
+
+// Second radiation constant hc/k in m K, as used by the CIE since ITS-90
+const C2: f64 = 1.4388e-2;
+
+// Light emitted by an ideal black body at a temperature in Kelvin, from
+// Planck's law. Scaled to the luminance of ConstantSpectrum::new(1.0), so that
+// the temperature only sets the color
+#[derive(Debug, Clone)]
+pub struct BlackbodySpectrum {
+    temperature: f32,
+    // Planck's law at the brightest visible wavelength, which keeps the
+    // exponentials of cold bodies in range
+    peak: f64,
+    scale: f32,
+}
+
+impl BlackbodySpectrum {
+    pub fn new(temperature: f32) -> Self {
+        assert!(temperature > 0.0, "blackbody temperature must be positive");
+
+        // Wien's displacement law, clamped to the range we render
+        let peak_nm = (2.897_772e6 / temperature).clamp(360.0, 830.0);
+        let mut spectrum = Self {
+            temperature,
+            peak: planck(peak_nm, temperature),
+            scale: 1.0,
+        };
+        spectrum.scale = 1.0 / Xyz::from_spectrum(&spectrum).to_array()[1];
+        spectrum
+    }
+
+    pub fn scaled(self, factor: f32) -> Self {
+        Self {
+            scale: self.scale * factor,
+            ..self
+        }
+    }
+}
+
+impl SampleableSpectrum for BlackbodySpectrum {
+    fn evaluate_single(&self, wavelength_nm: f32) -> f32 {
+        (planck(wavelength_nm, self.temperature) / self.peak) as f32 * self.scale
+    }
+}
+
+// Spectral radiance up to the constant factor 2hc²
+fn planck(wavelength_nm: f32, temperature: f32) -> f64 {
+    let lambda = wavelength_nm as f64 * 1e-9;
+    1.0 / (lambda.powi(5) * ((C2 / (lambda * temperature as f64)).exp() - 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blackbody() {
+        // Normalized to the luminance of a constant 1
+        for &temperature in &[100.0, 1000.0, 2856.0, 6500.0, 20000.0] {
+            let luminance = Xyz::from_spectrum(&BlackbodySpectrum::new(temperature)).to_array()[1];
+            assert!((luminance - 1.0).abs() < 1e-3);
+        }
+
+        // Hotter is bluer
+        let warm = BlackbodySpectrum::new(3000.0);
+        let cool = BlackbodySpectrum::new(10000.0);
+        assert!(warm.evaluate_single(450.0) < warm.evaluate_single(650.0));
+        assert!(cool.evaluate_single(450.0) > cool.evaluate_single(650.0));
+        let bright = warm.clone().scaled(2.0);
+        assert!((bright.evaluate_single(550.0) - 2.0 * warm.evaluate_single(550.0)).abs() < 1e-5);
+    }
+}
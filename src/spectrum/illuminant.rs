@@ -0,0 +1,184 @@
+use crate::{
+    color::Xyz,
+    spectrum::{BlackbodySpectrum, Spectrum, TabulatedSpectrum},
+};
+
+// CIE standard illuminants. Of the fluorescent F series, only the three the CIE
+// recommends are included: F2 (cool white), F7 (broadband daylight) and F11
+// (narrow triband)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Illuminant {
+    // Daylight, noon in western Europe
+    D65,
+    // Horizon daylight, used for print
+    D50,
+    // Incandescent tungsten
+    A,
+    F2,
+    F7,
+    F11,
+}
+
+impl Illuminant {
+    // Scaled to a luminance, that of ConstantSpectrum::new(luminance)
+    pub fn spectrum(self, luminance: f32) -> Spectrum {
+        let table = match self {
+            // Correlated color temperatures with the updated c2 of ITS-90
+            Self::D65 => daylight(6504.0),
+            Self::D50 => daylight(5003.0),
+            // Defined as a black body
+            Self::A => return BlackbodySpectrum::new(2856.0).scaled(luminance).into(),
+            Self::F2 => fluorescent(F2),
+            Self::F7 => fluorescent(F7),
+            Self::F11 => fluorescent(F11),
+        };
+
+        let unscaled = Xyz::from_spectrum(&table).to_array()[1];
+        table.scaled(luminance / unscaled).into()
+    }
+}
+
+// CIE daylight at a correlated color temperature in Kelvin, from the S0, S1 and
+// S2 basis functions
+fn daylight(cct: f32) -> TabulatedSpectrum {
+    let t = 1e3 / cct;
+    let x = if cct <= 7000.0 {
+        -4.6070 * t.powi(3) + 2.9678 * t.powi(2) + 0.09911 * t + 0.244063
+    } else {
+        -2.0064 * t.powi(3) + 1.9018 * t.powi(2) + 0.24748 * t + 0.237040
+    };
+    let y = -3.0 * x * x + 2.87 * x - 0.275;
+
+    let m = 0.0241 + 0.2562 * x - 0.7341 * y;
+    let m1 = (-1.3515 - 1.7703 * x + 5.9114 * y) / m;
+    let m2 = (0.0300 - 31.4424 * x + 30.0717 * y) / m;
+
+    TabulatedSpectrum::new(
+        DAYLIGHT
+            .iter()
+            .enumerate()
+            .map(|(i, &(s0, s1, s2))| (300.0 + 10.0 * i as f32, s0 + m1 * s1 + m2 * s2))
+            .collect(),
+    )
+}
+
+fn fluorescent(table: &[f32]) -> TabulatedSpectrum {
+    TabulatedSpectrum::new(
+        table
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| (380.0 + 5.0 * i as f32, value))
+            .collect(),
+    )
+}
+
+// (S0, S1, S2) from 300 to 830 nm in steps of 10 nm, from CIE 15:2004
+const DAYLIGHT: &[(f32, f32, f32)] = &[
+    (0.04, 0.02, 0.0),
+    (6.0, 4.5, 2.0),
+    (29.6, 22.4, 4.0),
+    (55.3, 42.0, 8.5),
+    (57.3, 40.6, 7.8),
+    (61.8, 41.6, 6.7),
+    (61.5, 38.0, 5.3),
+    (68.8, 42.4, 6.1),
+    (63.4, 38.5, 3.0),
+    (65.8, 35.0, 1.2),
+    (94.8, 43.4, -1.1),
+    (104.8, 46.3, -0.5),
+    (105.9, 43.9, -0.7),
+    (96.8, 37.1, -1.2),
+    (113.9, 36.7, -2.6),
+    (125.6, 35.9, -2.9),
+    (125.5, 32.6, -2.8),
+    (121.3, 27.9, -2.6),
+    (121.3, 24.3, -2.6),
+    (113.5, 20.1, -1.8),
+    (113.1, 16.2, -1.5),
+    (110.8, 13.2, -1.3),
+    (106.5, 8.6, -1.2),
+    (108.8, 6.1, -1.0),
+    (105.3, 4.2, -0.5),
+    (104.4, 1.9, -0.3),
+    (100.0, 0.0, 0.0),
+    (96.0, -1.6, 0.2),
+    (95.1, -3.5, 0.5),
+    (89.1, -3.5, 2.1),
+    (90.5, -5.8, 3.2),
+    (90.3, -7.2, 4.1),
+    (88.4, -8.6, 4.7),
+    (84.0, -9.5, 5.1),
+    (85.1, -10.9, 6.7),
+    (81.9, -10.7, 7.3),
+    (82.6, -12.0, 8.6),
+    (84.9, -14.0, 9.8),
+    (81.3, -13.6, 10.2),
+    (71.9, -12.0, 8.3),
+    (74.3, -13.3, 9.6),
+    (76.4, -12.9, 8.5),
+    (63.3, -10.6, 7.0),
+    (71.7, -11.6, 7.6),
+    (77.0, -12.2, 8.0),
+    (65.2, -10.2, 6.7),
+    (47.7, -7.8, 5.2),
+    (68.6, -11.2, 7.4),
+    (65.0, -10.4, 6.8),
+    (66.0, -10.6, 7.0),
+    (61.0, -9.7, 6.4),
+    (53.3, -8.3, 5.5),
+    (58.9, -9.3, 6.1),
+    (61.9, -9.8, 6.5),
+];
+
+// Relative spectral power from 380 to 780 nm in steps of 5 nm, from CIE
+// 15:2004
+const F2: &[f32] = &[
+    1.18, 1.48, 1.84, 2.15, 3.44, 15.69, 3.85, 3.74, 4.19, 4.62, 5.06, 34.98, 11.81, 6.27, 6.63,
+    6.93, 7.19, 7.4, 7.54, 7.62, 7.65, 7.62, 7.62, 7.45, 7.28, 7.15, 7.05, 7.04, 7.16, 7.47, 8.04,
+    8.88, 10.01, 24.88, 16.64, 14.59, 16.16, 17.56, 18.62, 21.47, 22.79, 19.29, 18.66, 17.73,
+    16.54, 15.21, 13.8, 12.36, 10.95, 9.65, 8.4, 7.32, 6.31, 5.43, 4.68, 4.02, 3.45, 2.96, 2.55,
+    2.19, 1.89, 1.64, 1.53, 1.27, 1.1, 0.99, 0.88, 0.76, 0.68, 0.61, 0.56, 0.54, 0.51, 0.47, 0.47,
+    0.43, 0.46, 0.47, 0.4, 0.33, 0.27,
+];
+
+const F7: &[f32] = &[
+    2.56, 3.18, 3.84, 4.53, 6.15, 19.37, 7.37, 7.05, 7.71, 8.41, 9.15, 44.14, 17.52, 11.35, 12.0,
+    12.58, 13.08, 13.45, 13.71, 13.88, 13.95, 13.93, 13.82, 13.64, 13.43, 13.25, 13.08, 12.93,
+    12.78, 12.6, 12.44, 12.33, 12.26, 29.52, 17.05, 12.44, 12.58, 12.72, 12.83, 15.46, 16.75,
+    12.83, 12.67, 12.45, 12.19, 11.89, 11.6, 11.35, 11.12, 10.95, 10.76, 10.42, 10.11, 10.04,
+    10.02, 10.11, 9.87, 8.65, 7.27, 6.44, 5.83, 5.41, 5.04, 4.57, 4.12, 3.77, 3.46, 3.08, 2.73,
+    2.47, 2.25, 1.96, 1.54, 1.64, 1.36, 1.07, 0.87, 0.82, 0.62, 0.54, 0.45,
+];
+
+const F11: &[f32] = &[
+    0.91, 0.63, 0.46, 0.37, 1.29, 12.68, 1.59, 1.79, 2.46, 3.33, 4.49, 33.94, 12.13, 6.95, 7.19,
+    7.12, 6.72, 6.13, 5.46, 4.79, 5.66, 14.29, 14.96, 8.97, 4.72, 2.33, 1.47, 1.1, 0.89, 0.83,
+    1.18, 4.9, 39.59, 72.84, 32.61, 7.52, 2.83, 1.96, 1.67, 4.43, 11.28, 14.76, 12.73, 9.74, 7.33,
+    9.72, 55.27, 42.58, 13.18, 13.16, 12.26, 5.11, 2.07, 2.34, 3.58, 3.01, 2.48, 2.14, 1.54, 1.33,
+    1.46, 1.94, 2.0, 1.2, 1.35, 4.1, 5.58, 2.51, 0.57, 0.27, 0.23, 0.21, 0.24, 0.24, 0.2, 0.24,
+    0.32, 0.26, 0.16, 0.12, 0.09,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chromaticities() {
+        // White points for the CIE 1964 10° observer, which iris renders with
+        for &(illuminant, (x, y)) in &[
+            (Illuminant::D65, (0.31382, 0.33100)),
+            (Illuminant::D50, (0.34773, 0.35952)),
+            (Illuminant::A, (0.45117, 0.40594)),
+            (Illuminant::F2, (0.37928, 0.36723)),
+            (Illuminant::F7, (0.31565, 0.32951)),
+            (Illuminant::F11, (0.38543, 0.37110)),
+        ] {
+            let [cx, cy, cz] = Xyz::from_spectrum(&illuminant.spectrum(1.0)).to_array();
+            let sum = cx + cy + cz;
+            assert!((cx / sum - x).abs() < 1e-3, "{:?}", illuminant);
+            assert!((cy / sum - y).abs() < 1e-3, "{:?}", illuminant);
+            assert!((cy - 1.0).abs() < 1e-3, "{:?}", illuminant);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{
+    math,
+    spectrum::{SampleableSpectrum, SpectralSample, Spectrum, Wavelength},
+};
+
+// Index of refraction of a dielectric as a function of wavelength, formulas
+// take the wavelength in micrometers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ior {
+    Constant(f32),
+    // n = a + b / λ²
+    Cauchy { a: f32, b: f32 },
+    // n² = 1 + Σ b_i λ² / (λ² - c_i)
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Ior {
+    // Coefficients from https://refractiveindex.info
+    pub const BK7: Self = Self::Sellmeier {
+        b: [1.039_612, 0.231_792_34, 1.010_469_4],
+        c: [0.006_000_699, 0.020_017_914, 103.560_65],
+    };
+    pub const SF11: Self = Self::Sellmeier {
+        b: [1.737_597, 0.313_747_35, 1.898_781],
+        c: [0.013_188_707, 0.062_306_814, 155.236_3],
+    };
+    pub const FUSED_SILICA: Self = Self::Sellmeier {
+        b: [0.696_166_3, 0.407_942_6, 0.897_479_4],
+        c: [0.004_679_148, 0.013_512_063, 97.934_003],
+    };
+
+    pub fn evaluate_single(&self, wavelength_nm: f32) -> f32 {
+        let lambda = wavelength_nm * 1e-3;
+        match self {
+            Self::Constant(n) => *n,
+            Self::Cauchy { a, b } => a + b / (lambda * lambda),
+            Self::Sellmeier { b, c } => {
+                let l2 = lambda * lambda;
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f32>();
+                n2.sqrt()
+            }
+        }
+    }
+
+    pub fn evaluate(&self, wavelength: Wavelength) -> SpectralSample {
+        match self {
+            Self::Constant(n) => SpectralSample::splat(*n),
+            _ => SpectralSample::from_function(wavelength, |lambda| self.evaluate_single(lambda)),
+        }
+    }
+}
+
+// Complex index of refraction η + ik of a conductor, k being how strongly the
+// metal absorbs light
+#[derive(Debug, Clone)]
+pub struct ComplexIor {
+    eta: Spectrum,
+    k: Spectrum,
+}
+
+impl ComplexIor {
+    pub fn new<E: Into<Spectrum>, K: Into<Spectrum>>(eta: E, k: K) -> Self {
+        Self {
+            eta: eta.into(),
+            k: k.into(),
+        }
+    }
+
+    // Fresnel reflectance of light arriving from air
+    pub fn fresnel(&self, cos_theta_i: f32, wavelength: Wavelength) -> SpectralSample {
+        SpectralSample::from_function(wavelength, |lambda| {
+            math::fresnel_conductor(
+                cos_theta_i,
+                self.eta.evaluate_single(lambda),
+                self.k.evaluate_single(lambda),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sellmeier() {
+        // Reference values at the sodium D line
+        assert!((Ior::BK7.evaluate_single(587.6) - 1.5168).abs() < 1e-4);
+        assert!((Ior::SF11.evaluate_single(587.6) - 1.7847).abs() < 1e-4);
+        assert!((Ior::FUSED_SILICA.evaluate_single(587.6) - 1.4585).abs() < 1e-4);
+
+        // Normal dispersion, blue bends more than red
+        assert!(Ior::SF11.evaluate_single(450.0) > Ior::SF11.evaluate_single(650.0));
+    }
+}
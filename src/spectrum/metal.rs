@@ -0,0 +1,137 @@
+use crate::spectrum::{ComplexIor, TabulatedSpectrum};
+
+// Measured metals, for conductors that don't need their own data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metal {
+    Gold,
+    Silver,
+    Copper,
+    Aluminium,
+    Chromium,
+}
+
+impl Metal {
+    pub fn ior(self) -> ComplexIor {
+        let table = match self {
+            Self::Gold => GOLD,
+            Self::Silver => SILVER,
+            Self::Copper => COPPER,
+            Self::Aluminium => ALUMINIUM,
+            Self::Chromium => CHROMIUM,
+        };
+
+        let eta = table
+            .iter()
+            .map(|&(lambda, eta, _)| (lambda, eta))
+            .collect();
+        let k = table.iter().map(|&(lambda, _, k)| (lambda, k)).collect();
+        ComplexIor::new(TabulatedSpectrum::new(eta), TabulatedSpectrum::new(k))
+    }
+}
+
+// (wavelength in nm, η, k), rounded from https://refractiveindex.info. Johnson
+// and Christy for everything but aluminium, which is Rakić
+const GOLD: &[(f32, f32, f32)] = &[
+    (350.0, 1.50, 1.87),
+    (380.0, 1.46, 1.93),
+    (410.0, 1.46, 1.96),
+    (450.0, 1.38, 1.91),
+    (470.0, 1.31, 1.85),
+    (500.0, 1.04, 1.83),
+    (520.0, 0.62, 2.08),
+    (550.0, 0.43, 2.46),
+    (580.0, 0.29, 2.86),
+    (620.0, 0.21, 3.27),
+    (660.0, 0.14, 3.70),
+    (700.0, 0.13, 4.10),
+    (760.0, 0.14, 4.54),
+    (820.0, 0.16, 5.08),
+    (890.0, 0.17, 5.66),
+];
+
+const SILVER: &[(f32, f32, f32)] = &[
+    (350.0, 0.24, 1.02),
+    (370.0, 0.14, 1.55),
+    (400.0, 0.05, 2.07),
+    (450.0, 0.04, 2.66),
+    (500.0, 0.05, 3.09),
+    (550.0, 0.06, 3.59),
+    (600.0, 0.06, 4.00),
+    (650.0, 0.05, 4.48),
+    (700.0, 0.04, 4.84),
+    (750.0, 0.03, 5.24),
+    (800.0, 0.04, 5.48),
+    (890.0, 0.04, 6.09),
+];
+
+const COPPER: &[(f32, f32, f32)] = &[
+    (350.0, 1.27, 1.95),
+    (400.0, 1.18, 2.21),
+    (450.0, 1.15, 2.45),
+    (500.0, 1.12, 2.60),
+    (550.0, 1.00, 2.58),
+    (575.0, 0.50, 2.80),
+    (600.0, 0.25, 3.40),
+    (650.0, 0.21, 3.67),
+    (700.0, 0.21, 4.18),
+    (750.0, 0.24, 4.60),
+    (800.0, 0.26, 5.01),
+    (890.0, 0.30, 5.68),
+];
+
+const ALUMINIUM: &[(f32, f32, f32)] = &[
+    (350.0, 0.37, 4.24),
+    (400.0, 0.49, 4.86),
+    (450.0, 0.62, 5.47),
+    (500.0, 0.77, 6.08),
+    (550.0, 0.96, 6.69),
+    (600.0, 1.20, 7.26),
+    (650.0, 1.47, 7.79),
+    (700.0, 1.83, 8.31),
+    (750.0, 2.40, 8.62),
+    (800.0, 2.80, 8.45),
+    (890.0, 2.30, 8.20),
+];
+
+const CHROMIUM: &[(f32, f32, f32)] = &[
+    (350.0, 1.50, 2.80),
+    (400.0, 1.90, 2.95),
+    (450.0, 2.30, 3.10),
+    (500.0, 2.75, 3.30),
+    (550.0, 3.00, 3.33),
+    (600.0, 3.17, 3.30),
+    (650.0, 3.30, 3.30),
+    (700.0, 3.60, 3.45),
+    (750.0, 3.75, 3.55),
+    (800.0, 3.84, 3.60),
+    (890.0, 3.90, 3.70),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::Wavelength;
+
+    #[test]
+    fn test_metal_colors() {
+        // Reflectance at normal incidence of blue, green and red light, the
+        // lanes are at 450, 567.5 and 685nm
+        let reflectance = |metal: Metal| {
+            let r = metal.ior().fresnel(1.0, Wavelength::new(450.0));
+            (r.x(), r.y(), r.z())
+        };
+
+        // Gold and copper are warm, silver and aluminium bright and close to
+        // white, chromium a darker grey
+        let (blue, _, red) = reflectance(Metal::Gold);
+        assert!(red > 0.9 && blue < 0.5);
+        let (blue, _, red) = reflectance(Metal::Copper);
+        assert!(red > 0.9 && blue < 0.65);
+        for metal in [Metal::Silver, Metal::Aluminium] {
+            let (blue, green, red) = reflectance(metal);
+            assert!(blue > 0.85 && green > 0.85 && red > 0.85);
+        }
+        let (blue, _, red) = reflectance(Metal::Chromium);
+        assert!((red - blue).abs() < 0.1);
+    }
+}
@@ -1,14 +1,24 @@
 use enum_dispatch::enum_dispatch;
 
+pub mod blackbody;
 pub mod constant;
+pub mod illuminant;
+pub mod ior;
+pub mod metal;
 pub mod sample;
+pub mod tabulated;
 pub mod upsample;
 pub mod wavelength;
 
 pub use sample::SpectralSample;
 pub use wavelength::Wavelength;
 
+pub use blackbody::BlackbodySpectrum;
 pub use constant::ConstantSpectrum;
+pub use illuminant::Illuminant;
+pub use ior::{ComplexIor, Ior};
+pub use metal::Metal;
+pub use tabulated::TabulatedSpectrum;
 pub use upsample::{UpsampledHdrSpectrum, UpsampledSpectrum};
 
 #[enum_dispatch]
@@ -22,9 +32,13 @@ pub trait SampleableSpectrum {
 
 #[enum_dispatch(SampleableSpectrum)]
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Spectrum {
     UpsampledSpectrum,
+    UpsampledHdrSpectrum,
     ConstantSpectrum,
+    TabulatedSpectrum,
+    BlackbodySpectrum,
 }
 
 impl Default for Spectrum {
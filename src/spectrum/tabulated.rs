@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use crate::spectrum::SampleableSpectrum;
+
+// Piecewise linear spectrum through measured (wavelength in nm, value) pairs,
+// held constant past either end of the table
+#[derive(Debug, Clone)]
+pub struct TabulatedSpectrum {
+    samples: Vec<(f32, f32)>,
+}
+
+impl TabulatedSpectrum {
+    pub fn new(samples: Vec<(f32, f32)>) -> Self {
+        assert!(!samples.is_empty());
+        assert!(
+            samples.windows(2).all(|pair| pair[0].0 < pair[1].0),
+            "tabulated wavelengths must be increasing"
+        );
+        Self { samples }
+    }
+
+    // Reads CSV or SPD files of wavelength and value pairs, separated by commas
+    // or whitespace. Lines can hold several pairs, # starts a comment, and a
+    // header line before the data is skipped
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(&source)
+    }
+
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut samples: Vec<(f32, f32)> = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let fields = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|field| !field.is_empty())
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<_>, _>>();
+
+            let numbers = match fields {
+                Ok(numbers) => numbers,
+                Err(_) if samples.is_empty() => continue,
+                Err(e) => return Err(format!("line {}: {}", i + 1, e)),
+            };
+            if numbers.len() % 2 != 0 {
+                return Err(format!(
+                    "line {}: expected wavelength and value pairs",
+                    i + 1
+                ));
+            }
+
+            for pair in numbers.chunks(2) {
+                let (lambda, value) = (pair[0], pair[1]);
+                if !lambda.is_finite() || !value.is_finite() {
+                    return Err(format!("line {}: values must be finite", i + 1));
+                }
+                if matches!(samples.last(), Some(&(last, _)) if last >= lambda) {
+                    return Err(format!("line {}: wavelengths must be increasing", i + 1));
+                }
+                samples.push((lambda, value));
+            }
+        }
+
+        if samples.is_empty() {
+            return Err("no samples".into());
+        }
+        Ok(Self { samples })
+    }
+
+    pub fn scaled(mut self, factor: f32) -> Self {
+        for (_, value) in &mut self.samples {
+            *value *= factor;
+        }
+        self
+    }
+}
+
+impl SampleableSpectrum for TabulatedSpectrum {
+    fn evaluate_single(&self, wavelength_nm: f32) -> f32 {
+        let i = self
+            .samples
+            .partition_point(|&(lambda, _)| lambda < wavelength_nm);
+        if i == 0 {
+            return self.samples[0].1;
+        } else if i == self.samples.len() {
+            return self.samples[i - 1].1;
+        }
+
+        let (lambda_0, value_0) = self.samples[i - 1];
+        let (lambda_1, value_1) = self.samples[i];
+        let t = (wavelength_nm - lambda_0) / (lambda_1 - lambda_0);
+        value_0 + t * (value_1 - value_0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let csv = "wavelength,reflectance\n400,0.1\n500, 0.5 # peak\n\n600,0.2\n";
+        let spectrum = TabulatedSpectrum::parse(csv).unwrap();
+        assert_eq!(spectrum.evaluate_single(450.0), 0.3);
+        assert_eq!(spectrum.evaluate_single(700.0), 0.2);
+
+        // SPD files, with pairs on one line or several
+        let spd = "# Measured\n400 1.0 450 2.0\n500\t3.0\n";
+        let spectrum = TabulatedSpectrum::parse(spd).unwrap();
+        assert_eq!(spectrum.evaluate_single(475.0), 2.5);
+
+        assert!(TabulatedSpectrum::parse("").is_err());
+        assert!(TabulatedSpectrum::parse("400,0.1\n400,0.2").is_err());
+        assert!(TabulatedSpectrum::parse("400,0.1\n500").is_err());
+        assert!(TabulatedSpectrum::parse("400,0.1\n500,high").is_err());
+    }
+}
@@ -42,16 +42,23 @@ impl SampleableSpectrum for UpsampledHdrSpectrum {
 }
 
 impl UpsampleTable {
-    #[allow(unused)]
     pub fn get_spectrum_hdr(&self, rgb: [f32; 3]) -> UpsampledHdrSpectrum {
-        let max = rgb[0].max(rgb[1]).max(rgb[2]).max(1.0);
+        let max = rgb[0].max(rgb[1]).max(rgb[2]);
+
+        // The table lookup divides by the largest component
+        if max <= 0.0 {
+            return UpsampledHdrSpectrum {
+                coefficients: [0.0; 3],
+                hdr_coefficient: 0.0,
+            };
+        }
 
+        let max = max.max(1.0);
         UpsampledHdrSpectrum {
             coefficients: self
                 .get_spectrum([rgb[0] / max, rgb[1] / max, rgb[2] / max])
                 .coefficients,
-            hdr_coefficient: max.min(50.0), /* Remove this hack once we importance sample the
-                                             * env map */
+            hdr_coefficient: max,
         }
     }
 
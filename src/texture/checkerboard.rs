@@ -0,0 +1,72 @@
+use crate::{
+    spectrum::{SpectralSample, Wavelength},
+    texture::{self, SampleableTexture, Texture, TextureContext},
+};
+
+// Alternates between two textures, with scale squares along each unit of u and
+// v
+#[derive(Debug, Clone)]
+pub struct CheckerboardTexture {
+    even: Box<Texture>,
+    odd: Box<Texture>,
+    scale: f32,
+}
+
+impl CheckerboardTexture {
+    pub fn new(even: Texture, odd: Texture, scale: f32) -> Self {
+        Self {
+            even: Box::new(even),
+            odd: Box::new(odd),
+            scale,
+        }
+    }
+}
+
+impl SampleableTexture for CheckerboardTexture {
+    // Box filtered over the footprint, in closed form, see pbrt's
+    // Checkerboard2DTexture
+    fn evaluate(&self, ctx: &TextureContext, wavelength: Wavelength) -> SpectralSample {
+        let (u, v) = ctx.uv;
+        let (s, t) = (u * self.scale, v * self.scale);
+        let ds = self.scale * ctx.duv_dx.0.abs().max(ctx.duv_dy.0.abs());
+        let dt = self.scale * ctx.duv_dx.1.abs().max(ctx.duv_dy.1.abs());
+
+        // Within a single square
+        if (s - ds).floor() == (s + ds).floor() && (t - dt).floor() == (t + dt).floor() {
+            let square = s.floor() + t.floor();
+            return if square.rem_euclid(2.0) == 0.0 {
+                self.even.evaluate(ctx, wavelength)
+            } else {
+                self.odd.evaluate(ctx, wavelength)
+            };
+        }
+
+        // Fraction of the footprint over odd squares, past a few squares it's
+        // as good as a half
+        let odd_fraction = |x: f32, dx: f32| {
+            if dx == 0.0 {
+                x.floor().rem_euclid(2.0)
+            } else {
+                (odd_integral(x + dx) - odd_integral(x - dx)) / (2.0 * dx)
+            }
+        };
+        let odd = if ds > 1.0 || dt > 1.0 {
+            0.5
+        } else {
+            let (s_odd, t_odd) = (odd_fraction(s, ds), odd_fraction(t, dt));
+            s_odd + t_odd - 2.0 * s_odd * t_odd
+        };
+        texture::lerp(
+            odd,
+            self.even.evaluate(ctx, wavelength),
+            self.odd.evaluate(ctx, wavelength),
+        )
+    }
+}
+
+// Integral from 0 to x of the function that's 1 over odd squares and 0 over
+// even ones
+fn odd_integral(x: f32) -> f32 {
+    let half = x / 2.0;
+    half.floor() + 2.0 * (half - half.floor() - 0.5).max(0.0)
+}
@@ -0,0 +1,43 @@
+use crate::{
+    spectrum::{SpectralSample, Wavelength},
+    texture::{self, SampleableTexture, Texture, TextureContext},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientAxis {
+    U,
+    V,
+}
+
+// Blends linearly from start at 0 to end at 1 along one texture coordinate,
+// clamping outside of that
+#[derive(Debug, Clone)]
+pub struct GradientTexture {
+    start: Box<Texture>,
+    end: Box<Texture>,
+    axis: GradientAxis,
+}
+
+impl GradientTexture {
+    pub fn new(start: Texture, end: Texture, axis: GradientAxis) -> Self {
+        Self {
+            start: Box::new(start),
+            end: Box::new(end),
+            axis,
+        }
+    }
+}
+
+impl SampleableTexture for GradientTexture {
+    fn evaluate(&self, ctx: &TextureContext, wavelength: Wavelength) -> SpectralSample {
+        let t = match self.axis {
+            GradientAxis::U => ctx.uv.0,
+            GradientAxis::V => ctx.uv.1,
+        };
+        texture::lerp(
+            t.clamp(0.0, 1.0),
+            self.start.evaluate(ctx, wavelength),
+            self.end.evaluate(ctx, wavelength),
+        )
+    }
+}
@@ -0,0 +1,543 @@
+use std::{fmt, fs::File, io::BufReader, path::Path, sync::Arc};
+
+use crate::{
+    spectrum::{
+        upsample::UpsampleTable,
+        SampleableSpectrum,
+        SpectralSample,
+        UpsampledHdrSpectrum,
+        Wavelength,
+    },
+    texture::{self, SampleableTexture, TextureContext},
+};
+
+// Lookups over a footprint wider than a texel blend in smaller, prefiltered
+// copies of the image so that distant surfaces don't alias
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFilter {
+    // Only ever reads the full resolution image
+    Bilinear,
+    // Blends the two levels closest to the footprint's width
+    Trilinear,
+    // Elliptical weighted average, Heckbert's filter over the footprint's
+    // ellipse, which keeps textures sharp at grazing angles
+    Ewa,
+}
+
+// Ellipses are widened to at most this ratio of their axes, past which EWA
+// would loop over too many texels
+const MAX_ANISOTROPY: f32 = 8.0;
+
+// Half the widest texel bounding box EWA loops over. On the levels it picks the
+// minor axis is at most two texels, and so the major one at most this
+const MAX_EWA_EXTENT: f32 = 2.0 * MAX_ANISOTROPY + 1.0;
+
+// Texels are upsampled to spectra when loading, then filtered and repeated
+// outside of [0, 1]. v goes up the image, like OBJ texture coordinates
+#[derive(Clone)]
+pub struct ImageTexture {
+    // The MIP pyramid, from the full resolution image down to a single texel,
+    // shared by all the materials using the image
+    levels: Arc<[MipLevel]>,
+    filter: ImageFilter,
+}
+
+struct MipLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<UpsampledHdrSpectrum>,
+}
+
+impl ImageTexture {
+    // rgb is linear and stored row by row, starting from the top of the image
+    pub fn new(rgb: &[[f32; 3]], width: usize, height: usize, table: &UpsampleTable) -> Self {
+        assert_eq!(rgb.len(), width * height);
+        assert!(width > 0 && height > 0);
+
+        // Levels are averaged in RGB, as upsampled spectra don't blend linearly
+        let mut rgb: Vec<[f32; 3]> = rgb
+            .iter()
+            .map(|texel| texel.map(|c| if c.is_finite() { c.max(0.0) } else { 0.0 }))
+            .collect();
+        let (mut width, mut height) = (width, height);
+        let mut levels = Vec::new();
+        loop {
+            levels.push(MipLevel {
+                width,
+                height,
+                texels: rgb
+                    .iter()
+                    .map(|&texel| table.get_spectrum_hdr(texel))
+                    .collect(),
+            });
+            if width == 1 && height == 1 {
+                break;
+            }
+
+            let (next, next_width, next_height) = downsample(&rgb, width, height);
+            rgb = next;
+            width = next_width;
+            height = next_height;
+        }
+
+        Self {
+            levels: levels.into(),
+            filter: ImageFilter::Ewa,
+        }
+    }
+
+    pub fn with_filter(self, filter: ImageFilter) -> Self {
+        Self { filter, ..self }
+    }
+
+    // OpenEXR images are expected to be linear, PNGs to be sRGB
+    pub fn load<P: AsRef<Path>>(path: P, table: &UpsampleTable) -> Result<Self, String> {
+        let (rgb, width, height) = load_rgb(path.as_ref(), true)?;
+        Ok(Self::new(&rgb, width, height, table))
+    }
+
+    // Lookups past the smallest level read its single texel
+    fn level(&self, level: usize) -> &MipLevel {
+        &self.levels[level.min(self.levels.len() - 1)]
+    }
+
+    // Width of the footprint in (s, t), as a fraction of the image, and the
+    // level that makes it about a texel wide. Level 0 is the full image
+    fn level_for_width(&self, width: f32) -> f32 {
+        (self.levels.len() - 1) as f32 + width.max(1e-8).log2()
+    }
+
+    fn trilinear(&self, st: (f32, f32), width: f32, wavelength: Wavelength) -> SpectralSample {
+        let level = self.level_for_width(width).max(0.0);
+        let lower = level.floor();
+        let t = level - lower;
+
+        let lower = self.level(lower as usize).bilinear(st, wavelength);
+        if t == 0.0 {
+            return lower;
+        }
+        let upper = self
+            .level(level.floor() as usize + 1)
+            .bilinear(st, wavelength);
+        texture::lerp(t, lower, upper)
+    }
+
+    fn ewa(
+        &self,
+        st: (f32, f32),
+        mut major: (f32, f32),
+        mut minor: (f32, f32),
+        wavelength: Wavelength,
+    ) -> SpectralSample {
+        let len = |(s, t): (f32, f32)| (s * s + t * t).sqrt();
+        if len(major) < len(minor) {
+            std::mem::swap(&mut major, &mut minor);
+        }
+
+        // Overly eccentric ellipses are widened, trading sharpness for speed
+        let (major_len, mut minor_len) = (len(major), len(minor));
+        if minor_len * MAX_ANISOTROPY < major_len && minor_len > 0.0 {
+            let scale = major_len / (minor_len * MAX_ANISOTROPY);
+            minor = (minor.0 * scale, minor.1 * scale);
+            minor_len *= scale;
+        }
+        if minor_len == 0.0 {
+            return self.level(0).bilinear(st, wavelength);
+        }
+
+        // The minor axis is about a texel wide on the chosen levels. Past the
+        // smallest one the footprint covers the whole image
+        let level = self.level_for_width(minor_len).max(0.0);
+        if level >= (self.levels.len() - 1) as f32 {
+            return self.levels[self.levels.len() - 1].texel(0, 0, wavelength);
+        }
+        let lower = level.floor();
+        let t = level - lower;
+
+        let lower = self.level(lower as usize).ewa(st, major, minor, wavelength);
+        if t == 0.0 {
+            return lower;
+        }
+        let upper = self
+            .level(level.floor() as usize + 1)
+            .ewa(st, major, minor, wavelength);
+        texture::lerp(t, lower, upper)
+    }
+}
+
+impl SampleableTexture for ImageTexture {
+    fn evaluate(&self, ctx: &TextureContext, wavelength: Wavelength) -> SpectralSample {
+        // (s, t) has t going down the image, as the texels are stored
+        let st = (ctx.uv.0, 1.0 - ctx.uv.1);
+        let dst_dx = (ctx.duv_dx.0, -ctx.duv_dx.1);
+        let dst_dy = (ctx.duv_dy.0, -ctx.duv_dy.1);
+
+        match self.filter {
+            ImageFilter::Bilinear => self.level(0).bilinear(st, wavelength),
+            ImageFilter::Trilinear => {
+                let width = 2.0
+                    * dst_dx
+                        .0
+                        .abs()
+                        .max(dst_dx.1.abs())
+                        .max(dst_dy.0.abs())
+                        .max(dst_dy.1.abs());
+                self.trilinear(st, width, wavelength)
+            }
+            ImageFilter::Ewa => self.ewa(st, dst_dx, dst_dy, wavelength),
+        }
+    }
+}
+
+impl MipLevel {
+    fn texel(&self, x: i64, y: i64, wavelength: Wavelength) -> SpectralSample {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        self.texels[x + y * self.width].evaluate(wavelength)
+    }
+
+    fn bilinear(&self, (s, t): (f32, f32), wavelength: Wavelength) -> SpectralSample {
+        let x = s * self.width as f32 - 0.5;
+        let y = t * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top =
+            self.texel(x0, y0, wavelength) * (1.0 - tx) + self.texel(x0 + 1, y0, wavelength) * tx;
+        let bottom = self.texel(x0, y0 + 1, wavelength) * (1.0 - tx)
+            + self.texel(x0 + 1, y0 + 1, wavelength) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    // Gaussian weighted texels within the ellipse with the given axes, see
+    // pbrt's MIPMap::EWA
+    fn ewa(
+        &self,
+        (s, t): (f32, f32),
+        major: (f32, f32),
+        minor: (f32, f32),
+        wavelength: Wavelength,
+    ) -> SpectralSample {
+        // In texels
+        let (width, height) = (self.width as f32, self.height as f32);
+        let (x, y) = (s * width - 0.5, t * height - 0.5);
+        let major = (major.0 * width, major.1 * height);
+        let minor = (minor.0 * width, minor.1 * height);
+
+        // The ellipse as A s^2 + B s t + C t^2 < 1, grown by a texel so that it
+        // always covers some
+        let mut a = major.1 * major.1 + minor.1 * minor.1 + 1.0;
+        let mut b = -2.0 * (major.0 * major.1 + minor.0 * minor.1);
+        let mut c = major.0 * major.0 + minor.0 * minor.0 + 1.0;
+        let inv_f = 1.0 / (a * c - b * b * 0.25);
+        a *= inv_f;
+        b *= inv_f;
+        c *= inv_f;
+
+        // Its bounding box, bounded in case of rounding in the level choice
+        let det = 4.0 * a * c - b * b;
+        let s_extent = (2.0 * (det * c).sqrt() / det).min(MAX_EWA_EXTENT);
+        let t_extent = (2.0 * (det * a).sqrt() / det).min(MAX_EWA_EXTENT);
+        let (x0, x1) = ((x - s_extent).ceil() as i64, (x + s_extent).floor() as i64);
+        let (y0, y1) = ((y - t_extent).ceil() as i64, (y + t_extent).floor() as i64);
+
+        let mut sum = SpectralSample::splat(0.0);
+        let mut total_weight = 0.0;
+        for texel_y in y0..=y1 {
+            let dy = texel_y as f32 - y;
+            for texel_x in x0..=x1 {
+                let dx = texel_x as f32 - x;
+                let r2 = a * dx * dx + b * dx * dy + c * dy * dy;
+                if r2 < 1.0 {
+                    let weight = (-2.0 * r2).exp() - (-2.0f32).exp();
+                    sum += self.texel(texel_x, texel_y, wavelength) * weight;
+                    total_weight += weight;
+                }
+            }
+        }
+
+        if total_weight > 0.0 {
+            sum / total_weight
+        } else {
+            self.bilinear((s, t), wavelength)
+        }
+    }
+}
+
+// Halves the image, rounding up, by averaging blocks of 2x2 texels. The last
+// row and column are repeated for odd sizes
+fn downsample(rgb: &[[f32; 3]], width: usize, height: usize) -> (Vec<[f32; 3]>, usize, usize) {
+    let (half_width, half_height) = (width.div_ceil(2), height.div_ceil(2));
+    let mut half = Vec::with_capacity(half_width * half_height);
+    for y in 0..half_height {
+        for x in 0..half_width {
+            let mut texel = [0.0; 3];
+            for &(dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+                let source =
+                    rgb[(2 * x + dx).min(width - 1) + (2 * y + dy).min(height - 1) * width];
+                for (texel, source) in texel.iter_mut().zip(source.iter()) {
+                    *texel += 0.25 * source;
+                }
+            }
+            half.push(texel);
+        }
+    }
+
+    (half, half_width, half_height)
+}
+
+// Texels as they are stored, for images holding data rather than colors, like
+// normal and bump maps. Lookups repeat and have v going up like ImageTexture
+#[derive(Clone)]
+pub struct RgbImage {
+    width: usize,
+    height: usize,
+    texels: Arc<[[f32; 3]]>,
+}
+
+impl RgbImage {
+    // Unlike ImageTexture::load, PNGs aren't decoded from sRGB
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let (texels, width, height) = load_rgb(path.as_ref(), false)?;
+        Ok(Self {
+            width,
+            height,
+            texels: texels.into(),
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Filtered bilinearly
+    pub fn lookup(&self, (u, v): (f32, f32)) -> [f32; 3] {
+        let x = u * self.width as f32 - 0.5;
+        let y = (1.0 - v) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let mut rgb = [0.0; 3];
+        for &(dx, dy, weight) in [
+            (0, 0, (1.0 - tx) * (1.0 - ty)),
+            (1, 0, tx * (1.0 - ty)),
+            (0, 1, (1.0 - tx) * ty),
+            (1, 1, tx * ty),
+        ]
+        .iter()
+        {
+            let x = (x0 + dx).rem_euclid(self.width as i64) as usize;
+            let y = (y0 + dy).rem_euclid(self.height as i64) as usize;
+            for (c, texel) in rgb.iter_mut().zip(self.texels[x + y * self.width].iter()) {
+                *c += weight * texel;
+            }
+        }
+
+        rgb
+    }
+}
+
+impl fmt::Debug for RgbImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RgbImage")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish()
+    }
+}
+
+// The texels would drown out the rest of a material
+impl fmt::Debug for ImageTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageTexture")
+            .field("width", &self.levels[0].width)
+            .field("height", &self.levels[0].height)
+            .field("levels", &self.levels.len())
+            .field("filter", &self.filter)
+            .finish()
+    }
+}
+
+// Linear RGB texels row by row from the top, along with the width and height.
+// PNGs are decoded from sRGB when srgb is set
+fn load_rgb(path: &Path, srgb: bool) -> Result<(Vec<[f32; 3]>, usize, usize), String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    let (rgb, width, height) = match extension.as_deref() {
+        Some("exr") => load_exr(path).map_err(|e| e.to_string())?,
+        Some("png") => load_png(path, srgb)?,
+        _ => return Err("unsupported image format, expected .exr or .png".into()),
+    };
+
+    if width == 0 || height == 0 {
+        return Err("image is empty".into());
+    }
+    Ok((rgb, width, height))
+}
+
+fn load_exr(path: &Path) -> exr::error::Result<(Vec<[f32; 3]>, usize, usize)> {
+    struct Pixels {
+        width: usize,
+        rgb: Vec<[f32; 3]>,
+    }
+
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| Pixels {
+            width: resolution.width(),
+            rgb: vec![[0.0; 3]; resolution.width() * resolution.height()],
+        },
+        |pixels, position, (r, g, b, _): (f32, f32, f32, f32)| {
+            pixels.rgb[position.x() + position.y() * pixels.width] = [r, g, b];
+        },
+    )?;
+
+    let size = image.layer_data.size;
+    let pixels = image.layer_data.channel_data.pixels;
+    Ok((pixels.rgb, size.width(), size.height()))
+}
+
+fn load_png(path: &Path, srgb: bool) -> Result<(Vec<[f32; 3]>, usize, usize), String> {
+    let decode = |c: f32| if srgb { srgb_to_linear(c) } else { c };
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    // Palettes and grayscale below 8 bits become 8 bit samples
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+
+    let samples: Vec<f32> = match info.bit_depth {
+        png::BitDepth::Eight => buffer[..info.buffer_size()]
+            .iter()
+            .map(|&s| decode(s as f32 / 255.0))
+            .collect(),
+        png::BitDepth::Sixteen => buffer[..info.buffer_size()]
+            .chunks_exact(2)
+            .map(|s| decode(u16::from_be_bytes([s[0], s[1]]) as f32 / 65535.0))
+            .collect(),
+        depth => return Err(format!("unsupported bit depth {:?}", depth)),
+    };
+
+    // Alpha is ignored
+    let channels = info.color_type.samples();
+    let rgb = samples
+        .chunks_exact(channels)
+        .map(|s| match info.color_type {
+            png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha => [s[0]; 3],
+            _ => [s[0], s[1], s[2]],
+        })
+        .collect();
+    Ok((rgb, info.width as usize, info.height as usize))
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_png() {
+        // A black and white 2x1 image, stored as 8 bit grayscale
+        let path = std::env::temp_dir().join("iris_test_texture.png");
+        let mut encoder = png::Encoder::new(File::create(&path).unwrap(), 2, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0, 255]).unwrap();
+        writer.finish().unwrap();
+
+        let texture = ImageTexture::load(&path, &UpsampleTable::load()).unwrap();
+        assert_eq!((texture.levels[0].width, texture.levels[0].height), (2, 1));
+
+        // Halfway between texel centers is a blend of the two, and u = 0 is
+        // halfway between the last and first texels as the image repeats
+        let value = |u: f32| {
+            let ctx = TextureContext {
+                uv: (u, 0.5),
+                ..TextureContext::default()
+            };
+            texture.evaluate(&ctx, Wavelength::new(550.0)).hero()
+        };
+        let (black, white) = (value(0.25), value(0.75));
+        assert!(black < white);
+        assert!((value(0.5) - 0.5 * (black + white)).abs() < 1e-4);
+        assert!((value(0.0) - 0.5 * (black + white)).abs() < 1e-4);
+        assert!((value(1.25) - black).abs() < 1e-6);
+
+        assert!(ImageTexture::load("texture.jpg", &UpsampleTable::load()).is_err());
+    }
+
+    #[test]
+    fn test_mip_filtering() {
+        // Black and white stripes, one texel wide
+        let rgb: Vec<_> = (0..8 * 6).map(|i| [(i % 2) as f32 * 0.8; 3]).collect();
+        let texture = ImageTexture::new(&rgb, 8, 6, &UpsampleTable::load());
+        let sizes: Vec<_> = texture
+            .levels
+            .iter()
+            .map(|level| (level.width, level.height))
+            .collect();
+        assert_eq!(sizes, [(8, 6), (4, 3), (2, 2), (1, 1)]);
+
+        // Lookups over a footprint of the given width, at the center of a
+        // black or white texel
+        let wavelength = Wavelength::new(550.0);
+        let lookup = |texture: &ImageTexture, texel: f32, footprint: f32| {
+            let ctx = TextureContext {
+                uv: ((texel + 0.5) / 8.0, 0.5),
+                duv_dx: (footprint, 0.0),
+                duv_dy: (0.0, footprint),
+            };
+            texture.evaluate(&ctx, wavelength).hero()
+        };
+        let white = lookup(&texture, 1.0, 0.0);
+        let grey = texture.levels[3].texel(0, 0, wavelength).hero();
+        assert!(white > 0.0);
+
+        // Points see the stripes and the whole image its average, unless the
+        // footprint is ignored
+        for &filter in [
+            ImageFilter::Bilinear,
+            ImageFilter::Trilinear,
+            ImageFilter::Ewa,
+        ]
+        .iter()
+        {
+            let texture = texture.clone().with_filter(filter);
+            assert_eq!(lookup(&texture, 0.0, 0.0), 0.0);
+            assert_eq!(lookup(&texture, 1.0, 0.0), white);
+
+            let wide = lookup(&texture, 0.0, 1.0);
+            if filter == ImageFilter::Bilinear {
+                assert_eq!(wide, 0.0);
+            } else {
+                assert!((wide - grey).abs() < 1e-4, "{:?} {} {}", filter, wide, grey);
+            }
+        }
+
+        // A few texels blur the stripes together
+        let blurred = lookup(&texture, 0.0, 0.25);
+        assert!(blurred > 0.0 && blurred <= white);
+
+        // Huge or degenerate footprints read the smallest level right away
+        let texture = texture.with_filter(ImageFilter::Ewa);
+        assert_eq!(lookup(&texture, 0.0, 1000.0), grey);
+        assert_eq!(lookup(&texture, 0.0, f32::INFINITY), grey);
+    }
+}
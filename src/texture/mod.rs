@@ -0,0 +1,61 @@
+use enum_dispatch::enum_dispatch;
+
+use crate::spectrum::{ConstantSpectrum, SampleableSpectrum, SpectralSample, Spectrum, Wavelength};
+
+mod checkerboard;
+pub use checkerboard::CheckerboardTexture;
+
+mod gradient;
+pub use gradient::{GradientAxis, GradientTexture};
+
+mod image;
+pub use image::{ImageFilter, ImageTexture, RgbImage};
+
+mod noise;
+pub use noise::NoiseTexture;
+
+mod normal_map;
+pub use normal_map::NormalMap;
+
+// Where on a surface a texture is looked up. The derivatives are the change in
+// uv to the neighbouring pixels, the footprint textures are filtered over, and
+// are zero for a lookup at a single point
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextureContext {
+    pub uv: (f32, f32),
+    pub duv_dx: (f32, f32),
+    pub duv_dy: (f32, f32),
+}
+
+#[enum_dispatch]
+pub trait SampleableTexture {
+    fn evaluate(&self, ctx: &TextureContext, wavelength: Wavelength) -> SpectralSample;
+}
+
+// Spectra that vary over a surface, for the colors of BSDFs
+#[enum_dispatch(SampleableTexture)]
+#[derive(Debug, Clone)]
+pub enum Texture {
+    Spectrum,
+    ImageTexture,
+    CheckerboardTexture,
+    NoiseTexture,
+    GradientTexture,
+}
+
+// The same everywhere
+impl SampleableTexture for Spectrum {
+    fn evaluate(&self, _ctx: &TextureContext, wavelength: Wavelength) -> SpectralSample {
+        SampleableSpectrum::evaluate(self, wavelength)
+    }
+}
+
+impl From<ConstantSpectrum> for Texture {
+    fn from(spectrum: ConstantSpectrum) -> Self {
+        Spectrum::from(spectrum).into()
+    }
+}
+
+fn lerp(t: f32, a: SpectralSample, b: SpectralSample) -> SpectralSample {
+    a * (1.0 - t) + b * t
+}
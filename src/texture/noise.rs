@@ -0,0 +1,118 @@
+use crate::{
+    spectrum::{SpectralSample, Wavelength},
+    texture::{self, SampleableTexture, Texture, TextureContext},
+};
+
+use std::f32::consts::{PI, SQRT_2};
+
+// Blends between two textures with fractal Perlin noise over the texture
+// coordinates. Each octave adds noise at twice the frequency and half the
+// amplitude of the last
+#[derive(Debug, Clone)]
+pub struct NoiseTexture {
+    low: Box<Texture>,
+    high: Box<Texture>,
+    scale: f32,
+    octaves: u32,
+}
+
+impl NoiseTexture {
+    pub fn new(low: Texture, high: Texture, scale: f32, octaves: u32) -> Self {
+        assert!(octaves > 0);
+        Self {
+            low: Box::new(low),
+            high: Box::new(high),
+            scale,
+            octaves,
+        }
+    }
+}
+
+impl SampleableTexture for NoiseTexture {
+    fn evaluate(&self, ctx: &TextureContext, wavelength: Wavelength) -> SpectralSample {
+        let (u, v) = ctx.uv;
+        let len = |(du, dv): (f32, f32)| (du * du + dv * dv).sqrt();
+        let footprint = self.scale * len(ctx.duv_dx).max(len(ctx.duv_dy));
+        let t = 0.5 + 0.5 * fbm(u * self.scale, v * self.scale, self.octaves, footprint);
+        texture::lerp(
+            t.clamp(0.0, 1.0),
+            self.low.evaluate(ctx, wavelength),
+            self.high.evaluate(ctx, wavelength),
+        )
+    }
+}
+
+// In [-1, 1]. Octaves too fine for the footprint would alias, they're faded
+// out to their average of zero instead
+fn fbm(x: f32, y: f32, octaves: u32, footprint: f32) -> f32 {
+    // Octaves from this one on, which can be fractional, vary too quickly for
+    // the footprint
+    let max_octaves = if footprint > 0.0 {
+        -1.0 - footprint.log2()
+    } else {
+        f32::INFINITY
+    };
+
+    let (mut sum, mut total) = (0.0, 0.0);
+    let (mut amplitude, mut frequency) = (1.0, 1.0);
+    for octave in 0..octaves {
+        let fade_out = (max_octaves - octave as f32).clamp(0.0, 1.0);
+        sum += fade_out * amplitude * perlin(x * frequency, y * frequency);
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    sum / total
+}
+
+// Gradient noise, scaled from its range of ±sqrt(2) / 2 to [-1, 1]
+fn perlin(x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as i32, y0 as i32);
+
+    let corner = |i: i32, j: i32| {
+        let (gx, gy) = gradient(x0.wrapping_add(i), y0.wrapping_add(j));
+        gx * (fx - i as f32) + gy * (fy - j as f32)
+    };
+
+    let (sx, sy) = (fade(fx), fade(fy));
+    let bottom = corner(0, 0) + sx * (corner(1, 0) - corner(0, 0));
+    let top = corner(0, 1) + sx * (corner(1, 1) - corner(0, 1));
+    SQRT_2 * (bottom + sy * (top - bottom))
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+// One of eight unit directions, picked by hashing the lattice point
+fn gradient(x: i32, y: i32) -> (f32, f32) {
+    let mut hash = (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0x5bd1_e995);
+    hash ^= hash >> 15;
+
+    let angle = (hash & 7) as f32 * PI / 4.0;
+    (angle.cos(), angle.sin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perlin() {
+        // Zero on the lattice, continuous and within range in between
+        assert_eq!(perlin(3.0, -2.0), 0.0);
+        let mut previous = perlin(0.0, 0.3);
+        for i in 1..1000 {
+            let x = i as f32 * 0.01;
+            let value = perlin(x, 0.3);
+            assert!(value.abs() <= 1.0);
+            assert!((value - previous).abs() < 0.05);
+            previous = value;
+        }
+    }
+}
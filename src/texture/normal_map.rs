@@ -0,0 +1,114 @@
+use crate::{
+    math::Vec3,
+    shape::Intersection,
+    texture::{RgbImage, TextureContext},
+};
+
+// Detail the geometry doesn't have, added by perturbing the shading normal
+#[derive(Debug, Clone)]
+pub enum NormalMap {
+    // Normals in tangent space, encoded as RGB in [0, 1], with x along u, y
+    // along v and z out of the surface
+    TangentSpace(RgbImage),
+    // Heights in [0, 1], the average of the channels, that move the surface out
+    // along its normal by up to strength
+    Bump { height: RgbImage, strength: f32 },
+}
+
+impl NormalMap {
+    // None when the surface has no uv derivatives to orient the map with
+    pub fn shading_normal(&self, hit: &Intersection, ctx: &TextureContext) -> Option<Vec3> {
+        let (n, dpdu, dpdv) = (hit.shading_normal, hit.dpdu, hit.dpdv);
+        let orientation = dpdu.cross(dpdv).dot(n);
+        if orientation == 0.0 || !orientation.is_finite() {
+            return None;
+        }
+
+        let normal = match self {
+            Self::TangentSpace(image) => {
+                let [x, y, z] = image.lookup(ctx.uv).map(|c| 2.0 * c - 1.0);
+                let tangent = (dpdu - n.dot(dpdu) * n).normalize();
+                // Mirrored uvs flip the bitangent
+                let bitangent = n.cross(tangent) * orientation.signum();
+                x * tangent + y * bitangent + z * n
+            }
+            Self::Bump { height, strength } => {
+                let displacement = |uv| {
+                    let [r, g, b] = height.lookup(uv);
+                    strength * (r + g + b) / 3.0
+                };
+
+                // Finite differences over the footprint, and at least a texel
+                let (u, v) = ctx.uv;
+                let du = (0.5 * (ctx.duv_dx.0.abs() + ctx.duv_dy.0.abs()))
+                    .max(1.0 / height.width() as f32);
+                let dv = (0.5 * (ctx.duv_dx.1.abs() + ctx.duv_dy.1.abs()))
+                    .max(1.0 / height.height() as f32);
+                let d = displacement((u, v));
+                let dd_du = (displacement((u + du, v)) - d) / du;
+                let dd_dv = (displacement((u, v + dv)) - d) / dv;
+
+                // Derivatives of the displaced surface p + d * n
+                let dpdu = dpdu + dd_du * n + d * hit.dndu;
+                let dpdv = dpdv + dd_dv * n + d * hit.dndv;
+                dpdu.cross(dpdv) * orientation.signum()
+            }
+        };
+
+        if normal.len_squared() > 0.0 && normal.len_squared().is_finite() {
+            Some(normal.normalize())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Point3;
+
+    #[test]
+    fn test_bump_map() {
+        // Heights rising along u, 0, 0.5 and 1 over three texels
+        let path = std::env::temp_dir().join("iris_test_bump.png");
+        let mut encoder = png::Encoder::new(std::fs::File::create(&path).unwrap(), 3, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0, 128, 255]).unwrap();
+        writer.finish().unwrap();
+
+        // A flat surface facing up, with u along x and v along z
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let hit = Intersection::new(Point3::new(0.0, 0.0, 0.0), up, up, (0.5, 0.5), false)
+            .with_derivatives(
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, -1.0),
+                Vec3::splat(0.0),
+                Vec3::splat(0.0),
+            );
+        let ctx = TextureContext {
+            uv: hit.uv,
+            ..TextureContext::default()
+        };
+
+        // Rising along +x tilts the normal towards -x, and more so when the
+        // bumps are stronger
+        let bump = |strength| {
+            NormalMap::Bump {
+                height: RgbImage::load(&path).unwrap(),
+                strength,
+            }
+            .shading_normal(&hit, &ctx)
+            .unwrap()
+        };
+        let (weak, strong) = (bump(0.1), bump(1.0));
+        assert!(weak.x() < 0.0 && strong.x() < weak.x());
+        assert!(weak.y() > 0.0 && weak.z().abs() < 1e-4);
+        assert!((weak.len() - 1.0).abs() < 1e-4);
+
+        // Flat heights leave the normal as it is
+        assert!((bump(0.0) - up).len() < 1e-6);
+    }
+}
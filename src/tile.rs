@@ -1,9 +1,13 @@
-use std::cmp::{Ord, Ordering};
+use std::{
+    cmp::{Ord, Ordering},
+    time::Duration,
+};
 
 use crate::{
+    camera::Projection,
     color::Xyz,
+    film::FilmBlock,
     integrator::Integrator,
-    math::{Point3, Ray, Vec3},
     sampling::Sampler,
     spectrum::Wavelength,
     Render,
@@ -11,8 +15,61 @@ use crate::{
 
 const MAX_TILE_WIDTH: usize = 64;
 const MAX_TILE_HEIGHT: usize = 64;
-// const SAMPLE_CHUNK_SIZE: usize = 5000;
-const SEED: u32 = 123_456_789;
+
+// Keeps the relative error of very dark pixels from blowing up
+const ERROR_EPSILON: f32 = 1e-3;
+
+// Instead of taking render.spp samples everywhere, pixels stop taking samples
+// once the relative standard error of their mean drops below the threshold.
+// render.spp becomes the maximum
+#[derive(Debug, Clone)]
+pub struct AdaptiveSampling {
+    pub threshold: f32,
+    // Taken by every pixel before its error is trusted
+    pub min_spp: usize,
+    // Once it runs out, tiles are not refined any further
+    pub time_limit: Option<Duration>,
+}
+
+// Running mean and variance of the samples taken in a pixel (before they are
+// filtered), see Welford's algorithm
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PixelStats {
+    pub count: usize,
+    mean: [f32; 3],
+    m2: [f32; 3],
+}
+
+impl PixelStats {
+    pub fn add(&mut self, xyz: Xyz) {
+        self.count += 1;
+        for (c, &value) in xyz.to_array().iter().enumerate() {
+            let delta = value - self.mean[c];
+            self.mean[c] += delta / self.count as f32;
+            self.m2[c] += delta * (value - self.mean[c]);
+        }
+    }
+
+    // Standard error of the mean relative to the mean, for the worst of X, Y and
+    // Z
+    pub fn relative_error(&self) -> f32 {
+        if self.count < 2 {
+            return f32::INFINITY;
+        }
+
+        let n = self.count as f32;
+        (0..3)
+            .map(|c| {
+                let variance = self.m2[c] / (n - 1.0);
+                (variance / n).sqrt() / (self.mean[c].abs() + ERROR_EPSILON)
+            })
+            .fold(0.0, f32::max)
+    }
+
+    pub fn converged(&self, adaptive: &AdaptiveSampling) -> bool {
+        self.count >= adaptive.min_spp && self.relative_error() <= adaptive.threshold
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct TileData {
@@ -23,34 +80,17 @@ pub struct TileData {
     pub pixel_y: usize,
     pub distance_from_center: f32,
     pub remaining_samples: usize,
-    pub accum_buffer: Vec<Xyz>,
-    pub temp_buffer: Vec<(f32, f32, f32)>,
+    // Largest relative error of the pixels that have not converged yet
+    pub error: f32,
+    pixels: Vec<PixelStats>,
 }
 
 // TODO: This code is very messy and I am not particularly happy with it
 
 impl TileData {
     pub fn new(render: &Render, idx: usize) -> Option<Self> {
-        let tile_width = (render.width / 4).min(MAX_TILE_WIDTH);
-        let tile_height = (render.height / 4).min(MAX_TILE_HEIGHT);
-
-        let num_horiz_tiles = (render.width + tile_width - 1) / tile_width;
-        let num_vert_tiles = (render.height + tile_height - 1) / tile_height;
-        let num_tiles = num_vert_tiles * num_horiz_tiles;
-
-        if idx >= num_tiles {
-            return None;
-        }
-
-        let this_tile_width = render.width - ((num_horiz_tiles.max(1) - 1) * tile_width);
-        let this_tile_height = render.height - ((num_vert_tiles.max(1) - 1) * tile_height);
-        let this_tile_pixels = this_tile_height * this_tile_width;
-
-        let tile_x = idx % num_horiz_tiles;
-        let tile_y = idx / num_horiz_tiles;
-
-        let pixel_start_x = tile_x * tile_width;
-        let pixel_start_y = tile_y * tile_height;
+        let (pixel_start_x, pixel_start_y, this_tile_width, this_tile_height) =
+            tile_bounds(render.width, render.height, idx)?;
 
         let pixel_center_x = (pixel_start_x + this_tile_width / 2) as i32;
         let pixel_center_y = (pixel_start_y + this_tile_height / 2) as i32;
@@ -67,102 +107,198 @@ impl TileData {
             pixel_x: pixel_start_x,
             pixel_y: pixel_start_y,
             remaining_samples: render.spp,
-            accum_buffer: vec![Xyz::new(0.0, 0.0, 0.0); this_tile_pixels],
-            temp_buffer: vec![(0.0, 0.0, 0.0); this_tile_pixels],
+            error: f32::INFINITY,
+            pixels: vec![PixelStats::default(); this_tile_width * this_tile_height],
         })
     }
 
-    pub fn render(mut self, render: &Render) -> Self {
-        // use time::Instant;
-        // let start_time = Instant::now();
-
-        // The below is needed for progressive rendering
-        while self.remaining_samples > 0
-        //&& Instant::now()
-        //.saturating_duration_since(start_time)
-        //.as_secs_f32()
-        //<= 0.1
-        {
-            // let new_remaining_samples =
-            // self.remaining_samples.saturating_sub(SAMPLE_CHUNK_SIZE);
-            let new_remaining_samples = 0;
-            let samples_this_iter = self.remaining_samples - new_remaining_samples;
-            let weight = render.spp as f32 / ((render.spp - new_remaining_samples) as f32);
-
-            for (i, (accumulator, pixel)) in self
-                .accum_buffer
-                .iter_mut()
-                .zip(self.temp_buffer.iter_mut())
-                .enumerate()
-            {
-                let xyz = get_pixel_color(
-                    self.pixel_x + i % self.width,
-                    self.pixel_y + i / self.width,
-                    samples_this_iter,
-                    render.spp - self.remaining_samples,
-                    render,
-                );
+    pub fn samples_taken(&self, render: &Render) -> usize {
+        render.spp - self.remaining_samples
+    }
 
-                *accumulator += xyz;
+    // Samples actually taken, which is less than samples_taken() * pixels when
+    // some pixels converged early
+    pub fn pixel_samples_taken(&self) -> usize {
+        self.pixels.iter().map(|stats| stats.count).sum()
+    }
 
-                let rgb = (*accumulator * weight).to_rgb_hdr();
-                *pixel = (rgb.0.max(0.0), rgb.1.max(0.0), rgb.2.max(0.0));
-            }
+    // Samples per pixel to take in one go when rendering the whole image at once
+    pub fn pass_size(&self, render: &Render) -> usize {
+        match &render.adaptive {
+            Some(adaptive) => adaptive.min_spp,
+            None => render.spp,
+        }
+    }
+
+    // Whether the tile should be rendered again, elapsed is the time since the
+    // render started
+    pub fn needs_samples(&self, render: &Render, elapsed: Duration) -> bool {
+        if self.remaining_samples == 0 {
+            return false;
+        }
 
-            self.remaining_samples = new_remaining_samples;
+        match &render.adaptive {
+            Some(adaptive) => {
+                let out_of_time = adaptive.time_limit.is_some_and(|limit| elapsed >= limit)
+                    && self.samples_taken(render) >= adaptive.min_spp;
+                !out_of_time && self.error > adaptive.threshold
+            }
+            None => true,
         }
+    }
+
+    // Takes up to max_samples more samples per pixel and adds them to the film,
+    // skipping converged pixels when sampling adaptively. Tiles with remaining
+    // samples can be rendered again to refine them
+    pub fn render(mut self, render: &Render, max_samples: usize) -> Self {
+        let samples_this_pass = self.remaining_samples.min(max_samples);
+
+        let mut block = render.film.block(
+            self.pixel_x,
+            self.pixel_y,
+            self.width,
+            self.height,
+            &render.filter,
+        );
 
-        let mut render_buffer = render.buffer.write().unwrap();
+        for (i, stats) in self.pixels.iter_mut().enumerate() {
+            let x = self.pixel_x + i % self.width;
+            let y = self.pixel_y + i / self.width;
 
-        for i in 0..self.height {
-            let abs = (self.pixel_y + i) * render.width + self.pixel_x;
-            render_buffer[abs..(abs + self.width)]
-                .copy_from_slice(&self.temp_buffer[(i * self.width)..((i + 1) * self.width)]);
+            if let Some(adaptive) = &render.adaptive {
+                if stats.converged(adaptive) {
+                    continue;
+                }
+            }
+
+            render_pixel(x, y, samples_this_pass, stats, render, &mut block);
         }
 
+        render.film.add_block(&block);
+        self.remaining_samples -= samples_this_pass;
+
+        self.error = self
+            .pixels
+            .iter()
+            .filter(|stats| {
+                render
+                    .adaptive
+                    .as_ref()
+                    .is_none_or(|adaptive| !stats.converged(adaptive))
+            })
+            .map(PixelStats::relative_error)
+            .fold(0.0, f32::max);
+
         self
     }
 }
 
-fn get_pixel_color(
+// Splats the samples into the block, which may extend past the tile. Splatting
+// weights pixels by the sample density around them, which is biased once it
+// varies across the image, so adaptive sampling importance samples the filter
+// instead and keeps the samples in their own pixel
+fn render_pixel(
     x_abs: usize,
     y_abs: usize,
     samples_this_iter: usize,
-    samples_so_far: usize,
+    stats: &mut PixelStats,
     render: &Render,
-) -> Xyz {
-    let pixel_center_clip = Point3::new(
-        ((x_abs as f32 + 0.5) / (render.width as f32) - 0.5) * 2.0,
-        ((y_abs as f32 + 0.5) / (render.height as f32) - 0.5) * -2.0,
-        0.0,
+    block: &mut FilmBlock,
+) {
+    // Textures are filtered over the area each sample stands for, which shrinks
+    // as samples get denser, down to an eighth of a pixel
+    let footprint = (1.0 / (render.spp as f32).sqrt()).max(0.125);
+    let pixel_size = (
+        footprint / render.width as f32,
+        footprint / render.height as f32,
     );
 
-    let weight = 1.0 / render.spp as f32;
-
-    let mut xyz_sum = Xyz::new(0.0, 0.0, 0.0);
-
-    for i in 0..samples_this_iter {
-        let mut sampler = Sampler::new(x_abs, y_abs, i + samples_so_far, SEED);
+    for _ in 0..samples_this_iter {
+        let mut sampler = Sampler::new(x_abs, y_abs, stats.count, render.seed);
 
         let hero_wavelength = Wavelength::sample(&mut sampler);
 
-        let jitter_clip = Vec3::new(
-            0.5 * sampler.gen_0_1() / render.width as f32,
-            0.5 * sampler.gen_0_1() / render.height as f32,
-            0.0,
+        let (film_x, film_y, pixel_weight) = if render.adaptive.is_some() {
+            let ((dx, dy), weight) = render
+                .filter_sampler
+                .sample(sampler.gen_0_1(), sampler.gen_0_1());
+            (
+                x_abs as f32 + 0.5 + dx,
+                y_abs as f32 + 0.5 + dy,
+                Some(weight),
+            )
+        } else {
+            // Uniformly distributed over the pixel, the filter weights the samples
+            (
+                x_abs as f32 + sampler.gen_0_1(),
+                y_abs as f32 + sampler.gen_0_1(),
+                None,
+            )
+        };
+
+        let film = (film_x / render.width as f32, film_y / render.height as f32);
+        let camera_ray = render.camera.generate_ray_differential(
+            film,
+            pixel_size,
+            hero_wavelength,
+            &mut sampler,
         );
+        let sample = match camera_ray {
+            Some(camera_ray) => {
+                // Only draw a time when the shutter is open for a while, so static
+                // renders get the same samples as before
+                let (open, close) = render.shutter;
+                let ray = if close > open {
+                    let t = sampler.gen_0_1();
+                    camera_ray.ray.with_time((1.0 - t) * open + t * close)
+                } else {
+                    camera_ray.ray.with_time(open)
+                };
+
+                let radiance = render.integrator.radiance(
+                    &render.scene,
+                    ray,
+                    camera_ray.differential,
+                    hero_wavelength,
+                    camera_ray.wavelength_pdfs,
+                    &mut sampler,
+                );
+                render
+                    .film
+                    .sample(radiance * camera_ray.weight, hero_wavelength)
+            }
+            // Blocked inside the camera, but still a sample of the pixel
+            None => Xyz::new(0.0, 0.0, 0.0).into(),
+        };
+
+        stats.add(sample.xyz);
+        match pixel_weight {
+            Some(weight) => block.add_pixel_sample((x_abs, y_abs), sample, weight),
+            None => block.add_sample((film_x, film_y), sample, &render.filter),
+        }
+    }
+}
 
-        let target_world = &render.camera.clip_to_world * (pixel_center_clip + jitter_clip);
-        let origin_world = render.camera.position;
-        let ray = Ray::new(origin_world, target_world - origin_world);
+// Start and size in pixels of the idx-th tile of the image, row by row. The
+// tiles on the right and bottom edges are cut short by the image
+fn tile_bounds(width: usize, height: usize, idx: usize) -> Option<(usize, usize, usize, usize)> {
+    let tile_width = (width / 4).clamp(1, MAX_TILE_WIDTH);
+    let tile_height = (height / 4).clamp(1, MAX_TILE_HEIGHT);
 
-        xyz_sum += render
-            .integrator
-            .radiance(&render.scene, ray, hero_wavelength, &mut sampler)
-            .to_xyz(hero_wavelength);
+    let num_horiz_tiles = width.div_ceil(tile_width);
+    let num_vert_tiles = height.div_ceil(tile_height);
+    if idx >= num_vert_tiles * num_horiz_tiles {
+        return None;
     }
 
-    xyz_sum * weight
+    let pixel_x = (idx % num_horiz_tiles) * tile_width;
+    let pixel_y = (idx / num_horiz_tiles) * tile_height;
+    Some((
+        pixel_x,
+        pixel_y,
+        tile_width.min(width - pixel_x),
+        tile_height.min(height - pixel_y),
+    ))
 }
 
 impl PartialEq for TileData {
@@ -181,12 +317,70 @@ impl PartialOrd for TileData {
 
 impl Ord for TileData {
     fn cmp(&self, other: &Self) -> Ordering {
-        // Render from the inside out
-        self.remaining_samples.cmp(&other.remaining_samples).then(
-            self.distance_from_center
-                .partial_cmp(&other.distance_from_center)
-                .unwrap()
-                .reverse(),
-        )
+        // Tiles with the most samples left first, so that the whole image converges
+        // evenly, then the noisiest ones, then from the inside out
+        self.remaining_samples
+            .cmp(&other.remaining_samples)
+            .then(self.error.total_cmp(&other.error))
+            .then(
+                self.distance_from_center
+                    .partial_cmp(&other.distance_from_center)
+                    .unwrap()
+                    .reverse(),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixel_stats() {
+        let adaptive = AdaptiveSampling {
+            threshold: 0.01,
+            min_spp: 4,
+            time_limit: None,
+        };
+
+        let mut constant = PixelStats::default();
+        assert_eq!(constant.relative_error(), f32::INFINITY);
+        for _ in 0..3 {
+            constant.add(Xyz::new(0.5, 1.0, 0.0));
+        }
+        assert_eq!(constant.relative_error(), 0.0);
+        // Not enough samples yet
+        assert!(!constant.converged(&adaptive));
+        constant.add(Xyz::new(0.5, 1.0, 0.0));
+        assert!(constant.converged(&adaptive));
+
+        // Alternating 0 and 2 around a mean of 1, the sample variance is n / (n - 1)
+        let mut noisy = PixelStats::default();
+        for i in 0..100 {
+            let value = if i % 2 == 0 { 0.0 } else { 2.0 };
+            noisy.add(Xyz::new(value, value, value));
+        }
+        let expected = (100.0f32 / 99.0 / 100.0).sqrt() / (1.0 + ERROR_EPSILON);
+        assert!((noisy.relative_error() - expected).abs() < 1e-4);
+        assert!(!noisy.converged(&adaptive));
+    }
+
+    #[test]
+    fn test_tiles_cover_image() {
+        for &(width, height) in &[(300, 200), (256, 256), (1, 3), (7, 130)] {
+            let mut covered = vec![0; width * height];
+            let tiles = (0..).map_while(|idx| tile_bounds(width, height, idx));
+            for (x0, y0, tile_width, tile_height) in tiles {
+                assert!(tile_width > 0 && tile_height > 0);
+                for y in y0..y0 + tile_height {
+                    for x in x0..x0 + tile_width {
+                        covered[y * width + x] += 1;
+                    }
+                }
+            }
+
+            // Every pixel exactly once
+            assert!(covered.iter().all(|&count| count == 1));
+        }
     }
 }